@@ -0,0 +1,166 @@
+//! Routes for operators under `/v1/admin`. Every request needs the `admin_token` from the config as a
+//! bearer token. Without a configured token the routes reject every request.
+
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest};
+use rocket::serde::json::Json;
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+use rocket::Request;
+use crate::db;
+use crate::health;
+use crate::producer;
+use crate::sql::quote_ident;
+use conductor_common::admin as admin_com;
+use conductor_common::error as error_com;
+use conductor_common::producer as producer_com;
+use conductor_common::schema as schema_com;
+
+/// The token admin requests have to carry. None if the admin routes are locked.
+pub struct AdminToken(Option<String>);
+
+/// A request which carried the admin token.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let expected = match request.rocket().state::<AdminToken>().and_then(|token| token.0.as_deref()) {
+            Some(expected) => expected,
+            None => return request::Outcome::Failure((Status::Forbidden, ())),
+        };
+        match request.headers().get_one("Authorization").and_then(admin_com::bearer_token) {
+            Some(token) if token == expected => request::Outcome::Success(Admin),
+            _ => request::Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Manages the `AdminToken` which is read from the `admin_token` config value.
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Admin token", |rocket| async {
+        let token: Option<String> = rocket.figment().extract_inner("admin_token").ok().filter(|token: &String| !token.is_empty());
+        if token.is_none() {
+            log::info!("No admin token is configured so the admin routes are locked");
+        }
+        rocket.manage(AdminToken(token))
+    })
+}
+
+///
+/// Counts the registered producers. When `tables` is set the rows of every producer are counted
+/// too and their size is estimated from their schema. Producers whose table can't be read are left
+/// out of the table stats.
+///
+async fn stats(db: &db::QuestDbConn, tables: bool) -> admin_com::RegistryStats {
+    let rows = match db.run(|conn: &mut postgres::Client| conn.query("SELECT uuid, schema FROM producers WHERE deleted = false;", &[])).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't read the producers from the database. {}", err));
+            log::error!("{}", error_code);
+            return admin_com::RegistryStats { error: error_code, ..admin_com::RegistryStats::new(0) };
+        }
+    };
+    let stats = admin_com::RegistryStats::new(rows.len() as u64);
+    if !tables {
+        return stats;
+    }
+    let mut table_stats = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let uuid: String = row.try_get("uuid").unwrap_or_default();
+        //names come from the database so they're validated again before being used in sql
+        if producer_com::validate_uuid(&uuid).is_err() {
+            continue;
+        }
+        let schema: schema_com::Schema = row.try_get::<_, String>("schema").ok()
+            .and_then(|schema| serde_json::from_str(&schema).ok())
+            .unwrap_or_default();
+        let sql = format!("SELECT count() AS count FROM {};", quote_ident(&uuid));
+        match db.run(move |conn: &mut postgres::Client| conn.query_one(sql.as_str(), &[])).await {
+            Ok(count) => {
+                let rows = u64::try_from(count.try_get::<_, i64>("count").unwrap_or_default()).unwrap_or_default();
+                table_stats.push(admin_com::TableStats { estimated_bytes: admin_com::estimate_table_bytes(&schema, rows), uuid, rows });
+            }
+            Err(err) => log::warn!("Couldn't count the rows of producer {}. {}", uuid, err),
+        }
+    }
+    stats.with_tables(table_stats)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/admin/stats?<tables>", format = "msgpack")]
+pub async fn stats_pack(_admin: Admin, conn: db::QuestDbConn, tables: Option<bool>) -> MsgPack<admin_com::RegistryStats> {
+    MsgPack(stats(&conn, tables.unwrap_or_default()).await)
+}
+
+#[get("/v1/admin/stats?<tables>", format = "json")]
+pub async fn stats_json(_admin: Admin, conn: db::QuestDbConn, tables: Option<bool>) -> Json<admin_com::RegistryStats> {
+    Json(stats(&conn, tables.unwrap_or_default()).await)
+}
+
+/// Drops a table which doesn't belong to a registered producer.
+async fn drop_orphan_table(db: &db::QuestDbConn, table: &str) -> Result<(), error_com::ConductorError> {
+    //names come from the database so they're validated again before being used in sql
+    producer_com::validate_uuid(table)?;
+    let sql = format!("DROP TABLE {};", quote_ident(table));
+    match db.run(move |conn: &mut postgres::Client| conn.execute(sql.as_str(), &[])).await {
+        Ok(_) => {
+            log::warn!("Dropped the orphan table {}", table);
+            Ok(())
+        }
+        Err(err) => Err(error_com::ConductorError::InternalError(format!("Couldn't drop the orphan table {}. {}", table, err))),
+    }
+}
+
+async fn apply(db: &db::QuestDbConn, action: &admin_com::ReconcileAction) -> Result<(), error_com::ConductorError> {
+    match action {
+        admin_com::ReconcileAction::Adopt(table) => match producer::adopt(db, table).await.error {
+            error_com::ConductorError::NoError => Ok(()),
+            error => Err(error),
+        },
+        admin_com::ReconcileAction::Drop(table) => drop_orphan_table(db, table).await,
+        admin_com::ReconcileAction::Recreate(uuid) => producer::recreate_table(db, uuid).await,
+    }
+}
+
+///
+/// Resolves the inconsistencies found by the deep health check. Orphan tables are adopted, dropped
+/// or kept depending on `orphans` and the missing tables of producers are created again from their
+/// registered schema when `recreate_missing` is set. An action which fails doesn't stop the others.
+///
+async fn reconcile(db: &db::QuestDbConn, orphans: Option<&str>, recreate_missing: bool) -> admin_com::ReconcileReport {
+    let orphans = match orphans.map(str::parse).transpose() {
+        Ok(orphans) => orphans.unwrap_or_default(),
+        Err(error_code) => return admin_com::ReconcileReport::failed(error_code),
+    };
+    let health = health::deep_health(db).await;
+    if health.error != error_com::ConductorError::NoError {
+        return admin_com::ReconcileReport::failed(health.error);
+    }
+    let actions = admin_com::plan_reconcile(&health, orphans, recreate_missing);
+    let mut report = admin_com::ReconcileReport { error: error_com::ConductorError::NoError, applied: Vec::with_capacity(actions.len()), failed: Vec::new() };
+    for action in actions {
+        match apply(db, &action).await {
+            Ok(()) => report.applied.push(action),
+            Err(error) => {
+                log::error!("Reconciling failed for {:?}. {}", action, error);
+                report.failed.push(admin_com::ReconcileFailure { action, error });
+            }
+        }
+    }
+    report
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/admin/reconcile?<orphans>&<recreate_missing>", format = "msgpack")]
+pub async fn reconcile_pack(_admin: Admin, conn: db::QuestDbConn, orphans: Option<&str>, recreate_missing: Option<bool>) -> MsgPack<admin_com::ReconcileReport> {
+    MsgPack(reconcile(&conn, orphans, recreate_missing.unwrap_or_default()).await)
+}
+
+#[post("/v1/admin/reconcile?<orphans>&<recreate_missing>", format = "json")]
+pub async fn reconcile_json(_admin: Admin, conn: db::QuestDbConn, orphans: Option<&str>, recreate_missing: Option<bool>) -> Json<admin_com::ReconcileReport> {
+    Json(reconcile(&conn, orphans, recreate_missing.unwrap_or_default()).await)
+}
@@ -0,0 +1,55 @@
+//! Registers devices. A device is a producer and a reactor stored under one uuid so its emits and
+//! the actions queued for it share the same id. Once registered each half is used through the
+//! producer and reactor routes as usual.
+
+use rocket::http::Status;
+use rocket::State;
+use rocket::serde::json::Json;
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+use crate::db;
+use crate::format::Negotiated;
+use crate::producer;
+use crate::reactor;
+use conductor_common::device as device_com;
+use conductor_common::error as error_com;
+use conductor_common::producer as producer_com;
+
+///
+/// Registers the producer half of a device and then its reactor half under the same uuid. A device
+/// with a custom id has its actions checked first so a conflicting reactor doesn't leave the producer
+/// half registered on its own.
+///
+async fn register(db: &db::QuestDbConn, uuids: producer::UuidStrategy, registration: &device_com::DeviceRegistration) -> producer_com::RegistrationResult {
+    let failed = |error| producer_com::RegistrationResult { error, uuid: None };
+    if let Err(error) = registration.validate() {
+        log::error!("{}", error);
+        return failed(error);
+    }
+    if let Some(custom_id) = registration.get_producer().get_custom_id() {
+        if let Err(error) = reactor::check_device_actions(db, custom_id, registration.get_actions()).await {
+            return failed(error);
+        }
+    }
+    let uuid = match producer::register_producer(db, uuids, registration.get_producer()).await {
+        Ok((uuid, _)) => uuid,
+        Err(error) => return failed(error),
+    };
+    match reactor::register_device_actions(db, &registration.reactor_registration(Some(uuid.clone()))).await {
+        Ok(()) => producer_com::RegistrationResult { error: error_com::ConductorError::NoError, uuid: Some(uuid) },
+        Err(error) => failed(error),
+    }
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/device/register", format = "msgpack", data = "<data>")]
+pub async fn register_pack(conn: db::QuestDbConn, uuids: &State<producer::UuidStrategy>, data: MsgPack<device_com::DeviceRegistration>) -> (Status, Negotiated<producer_com::RegistrationResult>) {
+    let result = register(&conn, **uuids, &data).await;
+    (producer::registration_status(&result), Negotiated(result))
+}
+
+#[post("/v1/device/register", format = "json", data = "<data>")]
+pub async fn register_json(conn: db::QuestDbConn, uuids: &State<producer::UuidStrategy>, data: Json<device_com::DeviceRegistration>) -> (Status, Negotiated<producer_com::RegistrationResult>) {
+    let result = register(&conn, **uuids, &data).await;
+    (producer::registration_status(&result), Negotiated(result))
+}
@@ -0,0 +1,106 @@
+//! Chooses the encoding of a response independently of the encoding of the request and decodes
+//! request bodies without trusting them.
+
+use conductor_common::error::ConductorError;
+use conductor_common::format::WireFormat;
+#[cfg(feature = "msgpack")]
+use conductor_common::format::{check_msgpack_lengths, MAX_MSGPACK_LENGTH};
+#[cfg(feature = "msgpack")]
+use rocket::data::{Data, Limits};
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+use rocket::Request;
+
+/// A response which is encoded in the format chosen by `WireFormat::negotiate` from the request's
+/// `Content-Type` and `Accept` headers.
+pub struct Negotiated<T>(pub T);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Negotiated<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let headers = request.headers();
+        match WireFormat::negotiate(headers.get_one("Content-Type"), headers.get_one("Accept")) {
+            WireFormat::Json => Json(self.0).respond_to(request),
+            #[cfg(feature = "msgpack")]
+            WireFormat::MsgPack => MsgPack(self.0).respond_to(request),
+            // a server built without msgpack support can only answer in json
+            #[cfg(not(feature = "msgpack"))]
+            WireFormat::MsgPack => Json(self.0).respond_to(request),
+        }
+    }
+}
+
+/// Why a request body was rejected along with the status to respond with.
+pub type Rejection = (Status, ConductorError);
+
+///
+/// Reads a msgpack request body up to the `msgpack` limit and checks the lengths it declares with
+/// `check_msgpack_lengths`. Bodies which are too large or declare more elements than allowed are
+/// rejected with `PayloadTooLarge` before anything is decoded.
+///
+#[cfg(feature = "msgpack")]
+pub async fn read_msgpack(data: Data<'_>, limits: &Limits) -> Result<Vec<u8>, Rejection> {
+    let limit = limits.get("msgpack").unwrap_or(Limits::MESSAGE_PACK);
+    let bytes = match data.open(limit).into_bytes().await {
+        Ok(bytes) => bytes,
+        Err(error) => return Err((Status::BadRequest, ConductorError::InvalidData(format!("Couldn't read the request body: {}", error)))),
+    };
+    if !bytes.is_complete() {
+        return Err((Status::PayloadTooLarge, ConductorError::InvalidData(format!("The request body is larger than the limit of {}", limit))));
+    }
+    let bytes = bytes.into_inner();
+    check_msgpack_lengths(&bytes, MAX_MSGPACK_LENGTH).map_err(|error| (Status::PayloadTooLarge, error))?;
+    Ok(bytes)
+}
+
+/// Decodes a body returned by `read_msgpack`. Bodies which don't match `T` are rejected with
+/// `UnprocessableEntity`. The error only says what kind of problem it was, see `decode_body`.
+#[cfg(feature = "msgpack")]
+pub fn decode_msgpack<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Rejection> {
+    rmp_serde::from_read_ref(bytes).map_err(|error| {
+        let problem = match error {
+            rmp_serde::decode::Error::InvalidMarkerRead(_) | rmp_serde::decode::Error::InvalidDataRead(_) => "it ends before all of its values",
+            rmp_serde::decode::Error::TypeMismatch(_) => "a value has the wrong type",
+            rmp_serde::decode::Error::OutOfRange => "a number is out of range",
+            rmp_serde::decode::Error::LengthMismatch(_) => "an array has the wrong number of elements",
+            rmp_serde::decode::Error::Utf8Error(_) => "a string isn't valid utf-8",
+            _ => "it doesn't have the expected fields or values",
+        };
+        (Status::UnprocessableEntity, ConductorError::InvalidData(format!("Couldn't decode the msgpack request body as {}", problem)))
+    })
+}
+
+/// Decodes a json body. Bodies which don't match `T` are rejected with `UnprocessableEntity`. The error
+/// only says what kind of problem it was and where, see `decode_body`.
+pub fn decode_json<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Rejection> {
+    serde_json::from_slice(bytes).map_err(|error| {
+        let problem = match error.classify() {
+            serde_json::error::Category::Eof => "it ends before all of its values",
+            serde_json::error::Category::Syntax | serde_json::error::Category::Io => "it isn't valid json",
+            serde_json::error::Category::Data => "it doesn't have the expected fields or values",
+        };
+        (Status::UnprocessableEntity, ConductorError::InvalidData(format!("Couldn't decode the json request body as {} (line {} column {})", problem, error.line(), error.column())))
+    })
+}
+
+///
+/// Decodes an emit payload or any other request body sent in `format`. Producers are often untrusted
+/// devices so nothing they send may panic the decoder. Bodies which can't be decoded as `T` are rejected
+/// with an `InvalidData` error which describes the problem without repeating any of the body, as
+/// decode errors otherwise quote the values they didn't expect. Msgpack bodies have their lengths
+/// checked with `check_msgpack_lengths` first so they can't make the decoder allocate more than they hold.
+///
+pub fn decode_body<'a, T: Deserialize<'a>>(format: WireFormat, bytes: &'a [u8]) -> Result<T, Rejection> {
+    match format {
+        WireFormat::Json => decode_json(bytes),
+        #[cfg(feature = "msgpack")]
+        WireFormat::MsgPack => {
+            check_msgpack_lengths(bytes, MAX_MSGPACK_LENGTH).map_err(|error| (Status::PayloadTooLarge, error))?;
+            decode_msgpack(bytes)
+        }
+        #[cfg(not(feature = "msgpack"))]
+        WireFormat::MsgPack => Err((Status::UnsupportedMediaType, ConductorError::InvalidData("This instance was built without msgpack support".to_string()))),
+    }
+}
@@ -0,0 +1,57 @@
+use postgres::Row;
+use rocket::serde::json::Json;
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+use crate::db;
+use conductor_common::health as health_com;
+use conductor_common::error as error_com;
+
+/// Tables which QuestDB or Conductor create for themselves. They never belong to a producer.
+fn is_internal_table(table: &str) -> bool {
+    table == "producers" || table == "quota_usage" || table == "reactors" || table == "actions" || table.starts_with("telemetry") || table.starts_with("sys.")
+}
+
+///
+/// Cross checks the registered producers against the tables in the database. This only reads from
+/// the database.
+///
+pub(crate) async fn deep_health(db: &db::QuestDbConn) -> health_com::DeepHealthReport {
+    let result = db
+        .run(|conn: &mut postgres::Client| {
+            let registered = conn.query("SELECT uuid FROM producers WHERE deleted = false;", &[])?;
+            let tables = conn.query("SELECT * FROM tables();", &[])?;
+            Ok::<(Vec<Row>, Vec<Row>), postgres::Error>((registered, tables))
+        })
+        .await;
+    let (registered, tables) = match result {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't read the producers and tables from the database. {}", err));
+            log::error!("{}", error_code);
+            return health_com::DeepHealthReport {
+                error: error_code,
+                producers: 0,
+                orphan_tables: Vec::new(),
+                missing_tables: Vec::new(),
+            };
+        }
+    };
+    let registered: Vec<String> = registered.iter().filter_map(|row| row.try_get("uuid").ok()).collect();
+    //newer versions of QuestDB call the column table_name
+    let tables: Vec<String> = tables.iter()
+        .filter_map(|row| row.try_get("table_name").or_else(|_| row.try_get("name")).ok())
+        .filter(|table: &String| !is_internal_table(table))
+        .collect();
+    health_com::DeepHealthReport::compare(&registered, &tables)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/health/deep", format = "msgpack")]
+pub async fn deep_pack(conn: db::QuestDbConn) -> MsgPack<health_com::DeepHealthReport> {
+    MsgPack(deep_health(&conn).await)
+}
+
+#[get("/v1/health/deep", format = "json")]
+pub async fn deep_json(conn: db::QuestDbConn) -> Json<health_com::DeepHealthReport> {
+    Json(deep_health(&conn).await)
+}
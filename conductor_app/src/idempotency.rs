@@ -0,0 +1,67 @@
+//! Remembers the results of emits which were sent with an idempotency key so that retries of an emit
+//! which was already stored are answered with the original result instead of being stored again.
+//! Only successful results are remembered so an emit which failed is tried again when it's retried.
+//!
+//! Two attempts with the same key which are handled at the same time can both be stored as neither
+//! has a result to find yet.
+
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use conductor_common::error as error_com;
+use conductor_common::idempotency::{IdempotencyCache, DEFAULT_IDEMPOTENCY_TTL_SECS, IDEMPOTENCY_KEY_HEADER};
+use conductor_common::producer as producer_com;
+use rocket::fairing::AdHoc;
+use rocket::request::{self, FromRequest};
+use rocket::Request;
+use crate::quota;
+
+/// The results of recent emits by their idempotency key.
+pub struct IdempotentEmits(Mutex<IdempotencyCache<producer_com::EmitResult>>);
+
+impl IdempotentEmits {
+    ///
+    /// Runs an emit unless an emit for the producer with the same key has already succeeded within
+    /// the ttl, in which case its result is returned. Emits without a key are always run.
+    ///
+    pub async fn run<F>(&self, uuid: &str, key: &IdempotencyKey<'_>, emit: F) -> producer_com::EmitResult
+    where
+        F: Future<Output = producer_com::EmitResult>,
+    {
+        let key = match key.0 {
+            Some(key) => key,
+            None => return emit.await,
+        };
+        if let Some(result) = self.0.lock().unwrap().get(uuid, key, quota::now_micros()) {
+            log::debug!("Emit for {} with idempotency key {} was already stored", uuid, key);
+            return result;
+        }
+        let result = emit.await;
+        if result.error == error_com::ConductorError::NoError {
+            self.0.lock().unwrap().insert(uuid, key, result.clone(), quota::now_micros());
+        }
+        result
+    }
+}
+
+/// The idempotency key sent with a request if there was one.
+pub struct IdempotencyKey<'r>(Option<&'r str>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let key = request.headers().get_one(IDEMPOTENCY_KEY_HEADER).filter(|key| !key.is_empty());
+        request::Outcome::Success(IdempotencyKey(key))
+    }
+}
+
+/// Manages the `IdempotentEmits`. How long results are remembered in seconds is read from the
+/// `idempotency_ttl` config value.
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Idempotency keys", |rocket| async {
+        let ttl_secs = rocket.figment().extract_inner("idempotency_ttl").unwrap_or(DEFAULT_IDEMPOTENCY_TTL_SECS);
+        rocket.manage(IdempotentEmits(Mutex::new(IdempotencyCache::new(Duration::from_secs(ttl_secs)))))
+    })
+}
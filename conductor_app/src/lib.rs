@@ -0,0 +1,30 @@
+mod admin;
+mod db;
+mod device;
+mod format;
+mod health;
+mod idempotency;
+mod metrics;
+mod openapi;
+mod producer;
+mod quota;
+mod reactor;
+mod server;
+mod sql;
+mod sweeper;
+mod trace;
+mod version;
+
+pub use db::{acquire, producers_migration_sql, validate_database_url, warm_up, DatabaseConfigError};
+pub use format::{decode_body, Rejection};
+pub use metrics::{gauges_to_prometheus, latest_value_gauges, Gauge, Histogram, EMIT_DURATION_BUCKETS};
+pub use producer::{check_field_conversions, check_missing_table_policy, classify_registration_error, generate_delete_producer_sql, generate_page_sql, generate_rollup_sql, generate_select_sql, generate_soft_delete_sql, generate_time_range_sql, generate_transaction_inserts, generate_union_sql, is_missing_table_message, plan_delete_batch, registration_option_differences, validate_emit_columns, ConversionOptions, Producer, RowFilter, UuidStrategy, UuidStrategyError};
+pub use quota::QuotaUsage;
+pub use server::{ConductorServer, RouteGroup};
+pub use sql::{generate_create_table_sql, generate_migration_sql, get_insert_sql, quote_ident};
+pub use sweeper::is_expired;
+pub use trace::{phase, phase_sync};
+pub use version::version_info;
+
+#[macro_use]
+extern crate rocket;
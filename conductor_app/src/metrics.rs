@@ -0,0 +1,200 @@
+//! Prometheus metrics for the emit path. They're only collected when the app is built with the
+//! `metrics` feature, which also mounts the `/v1/metrics` route.
+//!
+//! The latest value of every numeric column of every producer can also be exposed as gauges by
+//! setting the `metrics_latest_values` config value. It's off by default as each column of each
+//! producer is its own series and every scrape reads the latest row of every producer.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use conductor_common::schema as schema_com;
+#[cfg(feature = "metrics")]
+use crate::{db, producer};
+use rocket::request::{self, FromRequest};
+use rocket::Request;
+
+/// Upper bounds in seconds of the buckets used for the duration of emits. Most emits are a single
+/// insert so the buckets are concentrated between a millisecond and a second.
+pub const EMIT_DURATION_BUCKETS: [f64; 12] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// A histogram of durations which can be updated concurrently.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram. `bounds` are the upper bounds of the buckets in seconds and must be
+    /// sorted in ascending order. Durations above the largest bound are only counted in `+Inf`.
+    #[must_use]
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a duration
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = self.bounds.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(u64::try_from(duration.as_micros()).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// The number of durations which have been recorded
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// The sum of the durations which have been recorded
+    #[must_use]
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+
+    /// Renders the histogram in the Prometheus text exposition format as the metric `name`.
+    #[must_use]
+    pub fn to_prometheus(&self, name: &str, help: &str) -> String {
+        let mut text = format!("# HELP {} {}\n# TYPE {} histogram\n", name, help, name);
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(text, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let count = self.count();
+        let _ = writeln!(text, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(text, "{}_sum {}", name, self.sum().as_secs_f64());
+        let _ = writeln!(text, "{}_count {}", name, count);
+        text
+    }
+}
+
+/// The latest value of a numeric column of a producer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gauge {
+    pub uuid: String,
+    pub column: String,
+    pub value: f64,
+}
+
+/// Picks the values of the numeric columns out of the latest row of a producer. Null values and
+/// columns which aren't numeric or aren't in the schema are left out. The gauges are sorted by column.
+#[must_use]
+pub fn latest_value_gauges(uuid: &str, latest: &HashMap<String, serde_json::Value>, schema: &schema_com::Schema) -> Vec<Gauge> {
+    let mut gauges: Vec<Gauge> = latest.iter()
+        .filter(|(column, _)| schema.get(*column).map_or(false, schema_com::DataTypes::is_numeric))
+        .filter_map(|(column, value)| value.as_f64().map(|value| Gauge { uuid: uuid.to_string(), column: column.clone(), value }))
+        .collect();
+    gauges.sort_by(|a, b| a.column.cmp(&b.column));
+    gauges
+}
+
+/// Escapes a label value for the Prometheus text exposition format.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Renders gauges in the Prometheus text exposition format as the metric `name` labelled by the uuid
+/// of the producer and the column.
+#[must_use]
+pub fn gauges_to_prometheus(name: &str, help: &str, gauges: &[Gauge]) -> String {
+    let mut text = format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name);
+    for gauge in gauges {
+        let _ = writeln!(text, "{}{{uuid=\"{}\",column=\"{}\"}} {}", name, escape_label(&gauge.uuid), escape_label(&gauge.column), gauge.value);
+    }
+    text
+}
+
+/// How long handling an emit took, from validating it to writing it to the database.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+pub struct EmitDuration(Histogram);
+
+impl Default for EmitDuration {
+    fn default() -> Self {
+        Self(Histogram::new(&EMIT_DURATION_BUCKETS))
+    }
+}
+
+/// Times the handling of an emit into the shared `EmitDuration` histogram if it's being collected.
+pub struct EmitTimer<'r>(Option<&'r Histogram>);
+
+impl<'r> EmitTimer<'r> {
+    pub async fn time<F: Future>(&self, future: F) -> F::Output {
+        let start = std::time::Instant::now();
+        let output = future.await;
+        if let Some(histogram) = self.0 {
+            histogram.observe(start.elapsed());
+        }
+        output
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for EmitTimer<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(EmitTimer(request.rocket().state::<EmitDuration>().map(|duration| &duration.0)))
+    }
+}
+
+/// Managed when the latest values of producers are exposed as gauges.
+#[cfg(feature = "metrics")]
+pub struct LatestValuesEnabled;
+
+/// A database connection to read the latest values of producers with. It's only acquired when the
+/// latest values are exposed.
+#[cfg(feature = "metrics")]
+pub struct LatestValues(Option<db::QuestDbConn>);
+
+#[cfg(feature = "metrics")]
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LatestValues {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        if request.rocket().state::<LatestValuesEnabled>().is_none() {
+            return request::Outcome::Success(LatestValues(None));
+        }
+        request::Outcome::Success(LatestValues(request.guard::<db::QuestDbConn>().await.succeeded()))
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[get("/v1/metrics")]
+pub async fn metrics(emit_duration: &rocket::State<EmitDuration>, latest_values: LatestValues) -> (rocket::http::ContentType, String) {
+    let mut text = emit_duration.0.to_prometheus("conductor_emit_duration_seconds", "Time taken to validate an emit and write it to the database.");
+    if let LatestValues(Some(conn)) = latest_values {
+        let gauges = producer::latest_value_gauges(&conn).await;
+        text.push_str(&gauges_to_prometheus("conductor_producer_latest_value", "The latest value of a numeric column of a producer.", &gauges));
+    }
+    (rocket::http::ContentType::new("text", "plain").with_params(("version", "0.0.4")), text)
+}
+
+/// Collects the metrics and serves them at `/v1/metrics`. The latest values of producers are exposed
+/// when the `metrics_latest_values` config value is true.
+#[cfg(feature = "metrics")]
+pub fn stage() -> rocket::fairing::AdHoc {
+    rocket::fairing::AdHoc::on_ignite("Metrics", |rocket| async {
+        let latest_values: bool = rocket.figment().extract_inner("metrics_latest_values").unwrap_or(false);
+        let rocket = rocket.manage(EmitDuration::default()).mount("/", routes![metrics]);
+        if latest_values {
+            rocket.manage(LatestValuesEnabled)
+        } else {
+            rocket
+        }
+    })
+}
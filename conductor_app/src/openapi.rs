@@ -0,0 +1,9 @@
+use rocket::serde::json::Json;
+
+///
+/// Serves the OpenAPI 3 document which describes the producer and reactor routes.
+///
+#[get("/v1/openapi.json")]
+pub fn openapi() -> Json<serde_json::Value> {
+    Json(conductor_common::openapi::document())
+}
@@ -1,13 +1,34 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 
+use chrono::TimeZone;
 use postgres::{types::ToSql, Row};
 use rocket::http::Status;
-use rocket::serde::{json::Json, msgpack::MsgPack, Deserialize, Serialize};
+#[cfg(feature = "msgpack")]
+use rocket::http::ContentType;
+use rocket::State;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+#[cfg(feature = "msgpack")]
+use rocket::data::{Data, Limits};
+#[cfg(feature = "msgpack")]
+use rocket::response::stream::ByteStream;
 use uuid::Uuid;
 use crate::db;
+use crate::format::{self, Negotiated};
+use crate::idempotency::{IdempotencyKey, IdempotentEmits};
+use crate::metrics;
+use crate::quota;
+use crate::sweeper;
+use crate::sql::{generate_create_table_sql, generate_migration_sql, get_insert_sql, quote_ident};
+use crate::trace;
 use conductor_common::producer as producer_com;
 use conductor_common::schema as schema_com;
 use conductor_common::error as error_com;
+#[cfg(feature = "msgpack")]
+use conductor_common::format as format_com;
 
 macro_rules! log_error_with_json {
     ($self:ident, $($args:tt)+) => {{
@@ -30,6 +51,112 @@ pub struct Producer {
     pub name: String,
     pub uuid: String,
     pub schema: String,
+    pub tombstone_column: Option<String>,
+    pub strict_float_precision: bool,
+    pub strict_columns: bool,
+    pub coerce_numeric_strings: bool,
+    pub lenient_bool: bool,
+    pub column_defaults: String,
+    pub required_columns: String,
+    pub id_source: String,
+    pub column_metadata: String,
+    pub quota: Option<i64>,
+    pub paused: bool,
+    pub ttl: Option<i64>,
+    pub registered_at: Option<i64>,
+    pub timestamp_precision: String,
+    pub time_series: bool,
+}
+
+impl Producer {
+    /// The producer a registration is stored as under `uuid`.
+    #[must_use]
+    pub fn from_registration(uuid: &str, registration: &producer_com::Registration) -> Self {
+        #[allow(clippy::cast_possible_wrap)]
+        let quota = registration.get_row_quota().map(|quota| quota as i64);
+        Self {
+            name: registration.get_name().to_string(),
+            uuid: uuid.to_string(),
+            schema: serde_json::to_string_pretty(registration.get_schema()).unwrap_or_default(),
+            tombstone_column: registration.get_tombstone_column().map(str::to_string),
+            strict_float_precision: registration.has_strict_float_precision(),
+            strict_columns: registration.has_strict_columns(),
+            coerce_numeric_strings: registration.coerces_numeric_strings(),
+            lenient_bool: registration.has_lenient_bool(),
+            column_defaults: serde_json::to_string(registration.get_column_defaults()).unwrap_or_default(),
+            required_columns: serde_json::to_string(registration.get_required_columns()).unwrap_or_default(),
+            id_source: registration.get_id_source().as_str().to_string(),
+            column_metadata: serde_json::to_string(registration.get_column_metadata()).unwrap_or_default(),
+            quota,
+            paused: false,
+            ttl: registration.get_ttl().and_then(|ttl| i64::try_from(ttl).ok()),
+            registered_at: i64::try_from(quota::now_micros()).ok(),
+            timestamp_precision: registration.get_timestamp_precision().as_str().to_string(),
+            time_series: registration.is_time_series(),
+        }
+    }
+
+    /// The number of rows the producer can write in each quota period. None if it's unlimited.
+    #[must_use]
+    pub fn row_quota(&self) -> Option<u64> {
+        self.quota.and_then(|quota| u64::try_from(quota).ok())
+    }
+
+    /// The unit of the timestamps the producer emits with. Producers registered before this was recorded use microseconds.
+    #[must_use]
+    pub fn timestamp_precision(&self) -> producer_com::TimestampPrecision {
+        producer_com::TimestampPrecision::from_db_str(&self.timestamp_precision).unwrap_or_default()
+    }
+
+    ///
+    /// Converts the timestamp of an emit to microseconds. Emits without a timestamp are given the current time.
+    /// Producers which aren't time series don't store a timestamp so their emits don't get one.
+    ///
+    /// # Errors
+    /// * `ConductorError::InvalidData` : The timestamp is too large to be represented in microseconds or
+    ///   the producer isn't a time series
+    ///
+    pub fn emit_timestamp_micros(&self, timestamp: Option<u64>) -> Result<Option<u64>, error_com::ConductorError> {
+        match timestamp {
+            Some(_) if !self.time_series => Err(error_com::ConductorError::InvalidData(
+                format!("Producer {} isn't a time series so its emits can't have a timestamp", self.uuid)
+            )),
+            Some(timestamp) => self.timestamp_precision().to_micros(timestamp).map(Some).ok_or_else(|| {
+                error_com::ConductorError::InvalidData(format!("The timestamp {} is out of range", timestamp))
+            }),
+            None if self.time_series => Ok(Some(quota::now_micros())),
+            None => Ok(None),
+        }
+    }
+
+    /// The number of seconds the producer can go without emitting before it's swept. None if it never expires.
+    #[must_use]
+    pub fn ttl_seconds(&self) -> Option<u64> {
+        self.ttl.and_then(|ttl| u64::try_from(ttl).ok())
+    }
+
+    #[must_use]
+    pub const fn conversion_options(&self) -> ConversionOptions {
+        ConversionOptions {
+            strict_float_precision: self.strict_float_precision,
+            strict_columns: self.strict_columns,
+            coerce_numeric_strings: self.coerce_numeric_strings,
+            lenient_bool: self.lenient_bool,
+        }
+    }
+}
+
+/// Options which change how emitted json values are converted before being stored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConversionOptions {
+    /// Reject values for `Float` columns which can't be stored in an f32 without losing precision
+    pub strict_float_precision: bool,
+    /// Reject emits with columns which aren't in the schema rather than dropping those columns
+    pub strict_columns: bool,
+    /// Parse strings emitted to numeric columns as numbers
+    pub coerce_numeric_strings: bool,
+    /// Accept 0, 1, "true" and "false" for bool columns
+    pub lenient_bool: bool,
 }
 
 ///
@@ -40,10 +167,31 @@ pub struct Producer {
 pub fn to_solid_type_from_json(
     val: &serde_json::Value,
     data_type: schema_com::DataTypes,
+    options: ConversionOptions,
 ) -> Result<Box<dyn postgres::types::ToSql + Sync + Send>, String> {
+    let coerced;
+    let val = match schema_com::coerce_numeric_string(val, data_type) {
+        Some(number) if options.coerce_numeric_strings => {
+            coerced = number;
+            &coerced
+        }
+        _ => match schema_com::coerce_lenient_bool(val, data_type) {
+            Some(flag) if options.lenient_bool => {
+                coerced = flag;
+                &coerced
+            }
+            _ => val,
+        },
+    };
     match data_type {
+        // json numbers keep integers exactly so an Int never passes through an f64. This holds for
+        // msgpack bodies as well since their integers are decoded straight into json numbers
         schema_com::DataTypes::Int => match val.as_i64() {
             Some(v) => Ok(Box::new(v)),
+            None if val.is_u64() => Err(format!(
+                "Not possible to convert json value to i64 (too big to fit). Value: {:?}",
+                val
+            )),
             None => Err(format!(
                 "Not possible to convert json value to i64. Value: {:?}",
                 val
@@ -59,6 +207,9 @@ pub fn to_solid_type_from_json(
                     if v > f64::from(f32::MAX) - f64::from(f32::EPSILON) || v < f64::from(f32::MIN) + f64::from(f32::EPSILON) {
                         return Err(format!("Not possible to convert json value to f32 (too big to fit). Value: {:?}", val));
                     }
+                    if options.strict_float_precision && !schema_com::fits_in_float(v) {
+                        return Err(format!("Not possible to convert json value to f32 without losing precision. Value: {:?}", val));
+                    }
                     // It should be safe to cast this to an f32. It fits
                     #[allow(clippy::cast_possible_truncation)]
                         Ok(Box::new(v as f32))
@@ -66,9 +217,9 @@ pub fn to_solid_type_from_json(
                 None => Err(format!("Not possible to convert json value to f32 (Couldn't get f64 first). Value: {:?}", val)),
             }
         }
-        schema_com::DataTypes::Time => match serde_json::from_value::<chrono::NaiveDateTime>(val.clone()) {
-            Ok(v) => Ok(Box::new(v)),
-            Err(_) => Err(format!(
+        schema_com::DataTypes::Time => match schema_com::time_from_json(val) {
+            Some(v) => Ok(Box::new(v)),
+            None => Err(format!(
                 "Not possible to convert json value to naive date time. Value: {:?}",
                 val
             )),
@@ -104,12 +255,31 @@ pub fn to_solid_type_from_json(
     }
 }
 
+///
+/// Converts every field of an emit to the type of its column the way it's converted when it's stored
+/// and reports whether each one converts. Nothing is written. The fields are sorted by column.
+///
+#[must_use]
+pub fn check_field_conversions(data: &HashMap<String, serde_json::Value>, schema: &schema_com::Schema, options: ConversionOptions) -> Vec<producer_com::FieldConversion> {
+    let mut fields: Vec<producer_com::FieldConversion> = data.iter().map(|(column, value)| {
+        let expected = schema.get(column).copied();
+        let error = match expected {
+            Some(data_type) => to_solid_type_from_json(value, data_type, options).err(),
+            None => Some(format!("The column {} isn't in the schema", column)),
+        };
+        producer_com::FieldConversion { column: column.clone(), value: value.clone(), expected, error }
+    }).collect();
+    fields.sort_by(|a, b| a.column.cmp(&b.column));
+    fields
+}
+
 ///
 /// Retrieves the registration row for a producer from the database based on it's uuid.
 ///
 /// # Errors
 /// * `ConductorError::InvalidUuid` : The uuid is empty
 /// * `ConductorError::Unregistered` : The uuid doesn't exist in the database
+/// * `ConductorError::InternalError` : The database couldn't be queried
 /// * `ConductorError::InternalError` : There were multiple entries in the database for the given
 /// uuid
 /// * `ConductorError::InternalError` : The row couldn't be deserialized.
@@ -127,13 +297,13 @@ async fn get_producer_row(
     //check if the uuid is in the db
     let uuid_copy = uuid.to_string();
     let get_producer_row = move |conn: &mut postgres::Client| {
-        conn.query("SELECT * FROM producers WHERE uuid = $1;", &[&uuid_copy])
+        conn.query("SELECT * FROM producers WHERE uuid = $1 AND deleted = false;", &[&uuid_copy])
     };
     let rows: Vec<Row> = match db.run(get_producer_row).await {
         Ok(rows) => rows,
         Err(error) => {
             return log_error_and_get_emit_result!(
-                error_com::ConductorError::Unregistered(format!("Error getting producer from database {}",
+                error_com::ConductorError::InternalError(format!("Error getting producer from database {}",
                 error))
 
             );
@@ -155,11 +325,7 @@ async fn get_producer_row(
         );
     }
     if let Some(row) = rows.get(0) {
-        let producer = Producer {
-            name: row.try_get("name").unwrap_or_default(),
-            uuid: row.try_get("uuid").unwrap_or_default(),
-            schema: row.try_get("schema").unwrap_or_default(),
-        };
+        let producer = producer_from_row(row);
         let default_string = String::default();
         if producer.name == default_string
             || producer.uuid == default_string
@@ -181,286 +347,2784 @@ async fn get_producer_row(
     }
 }
 
-///
-/// Validates that the producer schema given matches the one that is registered in the database
-///
-fn validate_emit_schema(data: &producer_com::Emit<'_, HashMap<String,serde_json::Value>>, producer: &Producer) -> bool {
-    if let Ok(schema) = serde_json::from_str::<HashMap<String, serde_json::Value>>(&producer.schema)
-    {
-        if schema == *data.get_data() {
-            return true;
-        }
+/// Reads a row of the producers table. Columns which are missing or null are left as their default.
+fn producer_from_row(row: &Row) -> Producer {
+    Producer {
+        name: row.try_get("name").unwrap_or_default(),
+        uuid: row.try_get("uuid").unwrap_or_default(),
+        schema: row.try_get("schema").unwrap_or_default(),
+        tombstone_column: row.try_get("tombstone_column").unwrap_or_default(),
+        strict_float_precision: row.try_get("strict_float_precision").unwrap_or_default(),
+        //producers registered before this was recorded are strict. The migration fills the column in with true
+        strict_columns: row.try_get::<_, Option<bool>>("strict_columns").ok().flatten().unwrap_or(true),
+        coerce_numeric_strings: row.try_get("coerce_numeric_strings").unwrap_or_default(),
+        lenient_bool: row.try_get::<_, Option<bool>>("lenient_bool").ok().flatten().unwrap_or_default(),
+        column_defaults: row.try_get("column_defaults").unwrap_or_default(),
+        required_columns: row.try_get("required_columns").unwrap_or_default(),
+        id_source: row.try_get("id_source").unwrap_or_default(),
+        column_metadata: row.try_get("column_metadata").unwrap_or_default(),
+        quota: row.try_get::<_, Option<i64>>("quota").ok().flatten(),
+        paused: row.try_get::<_, Option<bool>>("paused").ok().flatten().unwrap_or_default(),
+        ttl: row.try_get::<_, Option<i64>>("ttl").ok().flatten(),
+        registered_at: row.try_get::<_, Option<i64>>("registered_at").ok().flatten(),
+        timestamp_precision: row.try_get::<_, Option<String>>("timestamp_precision").ok().flatten().unwrap_or_default(),
+        //producers registered before this was recorded are time series. The migration fills the column in with true
+        time_series: row.try_get::<_, Option<bool>>("time_series").ok().flatten().unwrap_or(true),
     }
-    false
 }
 
 ///
-/// Record a new registration in the database.
+/// Validates that every column in the emitted data is part of the schema that is registered in the database
+/// and that every required column has a value or a default.
 ///
-async fn register(db: &db::QuestDbConn, registration: &producer_com::Registration) -> producer_com::RegistrationResult {
-    //TODO this should use an option
-    let error_code = validate_registration(registration);
-    if error_code != error_com::ConductorError::NoError {
-        return producer_com::RegistrationResult {
-            error: error_code,
-            uuid: None,
-        };
+/// # Errors
+/// * `ConductorError::InvalidSchema` : The data has columns which aren't in the schema while the producer
+/// has strict columns or required columns are missing. The message lists the offending columns.
+///
+fn validate_emit_schema(data: &HashMap<String, serde_json::Value>, columns: &ProducerColumns, options: ConversionOptions) -> Result<(), error_com::ConductorError> {
+    let (missing, extra) = schema_com::diff_emit_columns(data.keys(), &columns.schema);
+    let mut problems = Vec::new();
+    if options.strict_columns && !extra.is_empty() {
+        problems.push(format!("The columns {} aren't in the schema.", extra.join(", ")));
     }
-
-    match persist_registration(registration, db).await {
-        Ok(uuid) => producer_com::RegistrationResult {
-            error: error_code,
-            uuid: Some(uuid),
-        },
-        Err(err) => producer_com::RegistrationResult {
-            error: err,
-            uuid: None,
-        },
+    let mut absent: Vec<String> = missing.into_iter()
+        .chain(data.iter().filter(|(_, value)| value.is_null()).map(|(column, _)| column.clone()))
+        .filter(|column| columns.required.contains(column) && !columns.defaults.contains_key(column))
+        .collect();
+    absent.sort();
+    if !absent.is_empty() {
+        problems.push(format!("The required columns {} are missing.", absent.join(", ")));
+    }
+    if problems.is_empty() {
+        return Ok(());
     }
+    Err(error_com::ConductorError::InvalidSchema(format!("Emitted schema didn't match registered schema. {}", problems.join(" "))))
 }
 
-async fn emit(db: &db::QuestDbConn, data: &producer_com::Emit<'_,HashMap<String,serde_json::Value>>) -> producer_com::EmitResult {
-    let producer = match get_producer_row(db, data.get_uuid()).await {
-        Ok(producer) => producer,
-        Err(error_code) => {
-            return producer_com::EmitResult {
-                error: error_code,
-            };
-        }
-    };
-    if !validate_emit_schema(data, &producer) {
-        return producer_com::EmitResult {
-            error: error_com::ConductorError::InvalidSchema("Emitted schema didn't match registered schema".to_string()),
-        };
-    }
-    // we know the schema is good, the uuid is good. The emit is good. Lets do this thing
-    match persist_emit(data, db).await {
-        Ok(_) => producer_com::EmitResult {
-            error: error_com::ConductorError::NoError,
-        },
-        Err(err) => producer_com::EmitResult { error: err},
-    }
+///
+/// Validates the columns of an emit for a registered producer the way the emit handlers do before
+/// anything is converted. See `validate_emit_schema`.
+///
+/// # Errors
+/// * `ConductorError::InvalidSchema` : The columns of the emit don't match the registration
+/// * `ConductorError::InternalError` : The registration of the producer couldn't be deserialized
+///
+pub fn validate_emit_columns(producer: &Producer, data: &HashMap<String, serde_json::Value>) -> Result<(), error_com::ConductorError> {
+    validate_emit_schema(data, &get_producer_columns(producer)?, producer.conversion_options())
 }
 
-fn validate_registration(registration: &producer_com::Registration) -> error_com::ConductorError {
-    if registration.get_name().is_empty() {
-        log_error_with_json!(
-            registration,
-            "Producer registration failed. Producer name is empty."
+///
+/// Deserializes the schema that was stored in the database when the producer was registered.
+///
+/// # Errors
+/// * `ConductorError::NoMembers` : The stored schema is empty or couldn't be deserialized
+///
+fn get_producer_schema(producer: &Producer) -> Result<schema_com::Schema, error_com::ConductorError> {
+    if producer.schema.is_empty() {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::NoMembers(format!("Empty registered schema for uuid: {}",
+            producer.uuid))
         );
-        return error_com::ConductorError::NameInvalid("Producer registration failed. Producer name is empty.".to_string());
     }
-    if let Some(custom_id) = &registration.get_custom_id() {
-        if custom_id.is_empty() || custom_id.contains('.') || custom_id.contains('\"') {
-            log_error_with_json!(
-                registration,
-                "Producer registration failed. Custom ID has illegal chars or is empty."
-            );
-            return error_com::ConductorError::InvalidUuid("Producer registration failed. Custom ID has illegal chars or is empty.".to_string());
-        }
+    match serde_json::from_str(producer.schema.as_str()) {
+        Ok(schema) => Ok(schema),
+        Err(err) => log_error_and_get_emit_result!(
+            error_com::ConductorError::NoMembers(format!("Couldn't deserialize the registered schema for uuid: {} with error: {}",
+            producer.uuid, err))
+        ),
     }
-    if registration.contains_column("ts") {
-        log_error_with_json!(
-            registration,
-            "Producer registration failed. column with name ts. This is a reserved name."
-        );
-        return error_com::ConductorError::TimestampDefined("Producer registration failed. column with name ts. This is a reserved name.".to_string());
+}
+
+///
+/// Deserializes the column defaults that were stored in the database when the producer was registered.
+/// Producers registered without defaults have none.
+///
+/// # Errors
+/// * `ConductorError::InternalError` : The stored defaults couldn't be deserialized
+///
+fn get_producer_column_defaults(producer: &Producer) -> Result<schema_com::ColumnDefaults, error_com::ConductorError> {
+    if producer.column_defaults.is_empty() {
+        return Ok(schema_com::ColumnDefaults::new());
     }
-    if registration.get_schema().is_empty() {
-        log_error_with_json!(registration, "Producer registration failed. No columns in schema.");
-        return error_com::ConductorError::NoMembers("Producer registration failed. No columns in schema.".to_string());
+    match serde_json::from_str(producer.column_defaults.as_str()) {
+        Ok(defaults) => Ok(defaults),
+        Err(err) => log_error_and_get_emit_result!(
+            error_com::ConductorError::InternalError(format!("Couldn't deserialize the registered column defaults for uuid: {} with error: {}",
+            producer.uuid, err))
+        ),
     }
-    for col in registration.get_schema().keys() {
-        if col.contains('.') || col.contains('\"') {
-            log_error_with_json!(registration, "Producer registration failed. Column with name {} is invalid as it contains a '.' or a '\"'.", col);
-            return error_com::ConductorError::InvalidColumnNames(format!("Producer registration failed. Column with name {} is invalid as it contains a '.' or a '\"'.", col));
-        }
+}
+
+///
+/// Deserializes the required columns that were stored in the database when the producer was registered.
+/// Producers registered without required columns have none.
+///
+/// # Errors
+/// * `ConductorError::InternalError` : The stored required columns couldn't be deserialized
+///
+fn get_producer_required_columns(producer: &Producer) -> Result<schema_com::RequiredColumns, error_com::ConductorError> {
+    if producer.required_columns.is_empty() {
+        return Ok(schema_com::RequiredColumns::new());
     }
-    if registration.schema_len() > 2_147_483_647 {
-        //I mean this is invalid. But seriously how did we get here
-        log_error_with_json!(registration, "Producer schema registration had {} columns which is more than the maximum quest can support of 2,147,483,647.", registration.schema_len());
-        return error_com::ConductorError::TooManyColumns(format!("Producer schema registration had {} columns which is more than the maximum quest can support of 2,147,483,647.", registration.schema_len()));
+    match serde_json::from_str(producer.required_columns.as_str()) {
+        Ok(required) => Ok(required),
+        Err(err) => log_error_and_get_emit_result!(
+            error_com::ConductorError::InternalError(format!("Couldn't deserialize the registered required columns for uuid: {} with error: {}",
+            producer.uuid, err))
+        ),
     }
+}
 
-    error_com::ConductorError::NoError
+fn get_producer_metadata(producer: &Producer) -> Result<producer_com::ProducerMetadata, error_com::ConductorError> {
+    let column_metadata = if producer.column_metadata.is_empty() {
+        schema_com::ColumnMetadataMap::new()
+    } else {
+        match serde_json::from_str(producer.column_metadata.as_str()) {
+            Ok(column_metadata) => column_metadata,
+            Err(err) => return log_error_and_get_emit_result!(
+                error_com::ConductorError::InternalError(format!("Couldn't deserialize the column metadata for uuid: {} with error: {}",
+                producer.uuid, err))
+            ),
+        }
+    };
+    Ok(producer_com::ProducerMetadata { name: producer.name.clone(), column_metadata })
 }
 
-fn generate_create_table_sql(registration: &producer_com::Registration, table_name: &str) -> String {
-    //     CREATE TABLE my_table(symb SYMBOL, price DOUBLE, ts TIMESTAMP, s STRING) timestamp(ts);
-    let mut sql = format!("CREATE TABLE IF NOT EXISTS \"{}\" (ts TIMESTAMP", table_name);
-    for (col_name, col_type) in registration.get_schema() {
-        sql = sql + ", \"" + col_name + "\" " + col_type.to_quest_type_str();
-    }
-    sql += ") timestamp(ts);";
-    sql
+/// Everything that was registered about the columns of a producer.
+struct ProducerColumns {
+    schema: schema_com::Schema,
+    defaults: schema_com::ColumnDefaults,
+    required: schema_com::RequiredColumns,
 }
 
-#[inline]
-fn get_or_create_uuid_for_registration(registration: &producer_com::Registration) -> String {
-    match &registration.get_custom_id() {
-        Some(custom_id) => (*custom_id).to_string(),
-        None => Uuid::new_v4().to_string(),
+fn get_producer_columns(producer: &Producer) -> Result<ProducerColumns, error_com::ConductorError> {
+    Ok(ProducerColumns {
+        schema: get_producer_schema(producer)?,
+        defaults: get_producer_column_defaults(producer)?,
+        required: get_producer_required_columns(producer)?,
+    })
+}
+
+///
+/// Record a new registration in the database.
+///
+async fn register(db: &db::QuestDbConn, uuids: UuidStrategy, registration: &producer_com::Registration) -> producer_com::RegistrationResult {
+    match register_producer(db, uuids, registration).await {
+        Ok((uuid, _)) => producer_com::RegistrationResult {
+            error: error_com::ConductorError::NoError,
+            uuid: Some(uuid),
+        },
+        Err(err) => producer_com::RegistrationResult {
+            error: err,
+            uuid: None,
+        },
     }
 }
 
+///
+/// Registers a producer and returns its uuid along with whether it was created. A producer which
+/// is already registered under the custom id, the name based uuid from `uuids` or with an identical
+/// registration isn't created again.
+///
+pub(crate) async fn register_producer(db: &db::QuestDbConn, uuids: UuidStrategy, registration: &producer_com::Registration) -> Result<(String, bool), error_com::ConductorError> {
+    let producer = registration_trace_name(registration);
+    let error_code = trace::phase_sync("validate", producer, || validate_registration(registration));
+    if error_code != error_com::ConductorError::NoError {
+        return Err(error_code);
+    }
+
+    if let Some(known_uuid) = registration.get_custom_id().map(str::to_string).or_else(|| uuids.name_based(registration.get_name())) {
+        match trace::phase("lookup", producer, get_producer_row(db, &known_uuid)).await {
+            Ok(existing) => {
+                let result = reregister(&existing, registration);
+                return result.uuid.map(|uuid| (uuid, false)).ok_or(result.error);
+            }
+            Err(error_com::ConductorError::Unregistered(_)) => {}
+            Err(error_code) => return Err(error_code),
+        }
+    } else if let Some(uuid) = trace::phase("lookup", producer, find_identical_producer(db, registration)).await? {
+        return Ok((uuid, false));
+    }
 
-#[inline]
-fn generate_data_for_creation(registration: &producer_com::Registration, uuid: &str) -> (String, String, String, String) {
-    (
-        generate_create_table_sql(registration, uuid),
-        registration.get_name().to_string(),
-        serde_json::to_string_pretty(registration.get_schema()).unwrap_or_default(),
-        uuid.to_string(),
-    )
+    let uuid = trace::phase("db_write", producer, persist_registration(registration, uuids, db)).await?;
+    Ok((uuid, true))
 }
 
-async fn persist_registration(registration: &producer_com::Registration, db: &db::QuestDbConn) -> Result<String, error_com::ConductorError> {
-    let uuid = get_or_create_uuid_for_registration(registration);
-    let (create_table_sql, producer_name, schema_json, uuid_copy) = generate_data_for_creation(registration, &uuid);
+/// Identifies a registration in traces. It doesn't have a uuid yet unless a custom id was given.
+fn registration_trace_name(registration: &producer_com::Registration) -> &str {
+    registration.get_custom_id().unwrap_or_else(|| registration.get_name())
+}
 
-    let result: Result<u64, _> = db
+///
+/// Finds a producer which is registered with the same name and an identical registration. Registering
+/// it again returns its uuid instead of creating another table, which stops producers that restart
+/// often without a custom id from creating a new table each time.
+///
+/// # Errors
+/// * `ConductorError::SchemaConflict` : A producer is registered with the same name and schema but different options. Lists the options which differ
+/// * `ConductorError::InternalError` : The producers couldn't be read
+///
+async fn find_identical_producer(db: &db::QuestDbConn, registration: &producer_com::Registration) -> Result<Option<String>, error_com::ConductorError> {
+    let name = registration.get_name().to_string();
+    let schema_hash = schema_com::schema_hash(registration.get_schema());
+    let find_result = db
         .run(move |conn: &mut postgres::Client| {
-            //we will do both these in one go so that we don't add it to the producers table unless we were able to create its data table
-            log::info!("creating table with sql {}", create_table_sql);
-            let result = conn.execute(create_table_sql.as_str(), &[]);
-            if result.is_err() {
-                return result;
-            }
-            conn.execute(
-                "INSERT INTO producers VALUES($1, $2, $3);",
-                &[&producer_name, &uuid_copy, &schema_json],
-            )
+            conn.query("SELECT * FROM producers WHERE name = $1 AND schema_hash = $2 AND deleted = false;", &[&name, &schema_hash])
         })
         .await;
-    match result {
-        Ok(_) => Ok(uuid),
+    let rows = match find_result {
+        Ok(rows) => rows,
+        Err(err) => return log_error_and_get_emit_result!(
+            error_com::ConductorError::InternalError(format!("Couldn't look for an identical producer. {}", err))
+        ),
+    };
+    let mut differences = Vec::new();
+    for row in &rows {
+        let existing = producer_from_row(row);
+        let mut existing_registration = producer_to_registration(&existing)?;
+        for column in read_indexed_columns(db, &existing.uuid).await? {
+            existing_registration = existing_registration.with_indexed_column(column);
+        }
+        differences = registration_option_differences(&existing_registration, registration);
+        if differences.is_empty() {
+            return Ok(Some(existing.uuid));
+        }
+    }
+    if differences.is_empty() {
+        return Ok(None);
+    }
+    let error_code = error_com::ConductorError::SchemaConflict(differences);
+    log::error!("{}", error_code);
+    Err(error_code)
+}
+
+///
+/// Lists the options two registrations of the same schema differ in. The name, custom id and the
+/// purely descriptive column metadata aren't compared.
+///
+#[must_use]
+pub fn registration_option_differences(registered: &producer_com::Registration, registration: &producer_com::Registration) -> Vec<String> {
+    let options = [
+        ("tombstone_column", registered.get_tombstone_column() == registration.get_tombstone_column()),
+        ("strict_float_precision", registered.has_strict_float_precision() == registration.has_strict_float_precision()),
+        ("strict_columns", registered.has_strict_columns() == registration.has_strict_columns()),
+        ("coerce_numeric_strings", registered.coerces_numeric_strings() == registration.coerces_numeric_strings()),
+        ("lenient_bool", registered.has_lenient_bool() == registration.has_lenient_bool()),
+        ("row_quota", registered.get_row_quota() == registration.get_row_quota()),
+        ("ttl", registered.get_ttl() == registration.get_ttl()),
+        ("timestamp_precision", registered.get_timestamp_precision() == registration.get_timestamp_precision()),
+        ("time_series", registered.is_time_series() == registration.is_time_series()),
+        ("column_defaults", registered.get_column_defaults() == registration.get_column_defaults()),
+        ("required_columns", registered.get_required_columns() == registration.get_required_columns()),
+        ("indexed_columns", registered.get_indexed_columns() == registration.get_indexed_columns()),
+    ];
+    options.iter()
+        .filter(|(_, same)| !same)
+        .map(|(option, _)| (*option).to_string())
+        .collect()
+}
+
+///
+/// Reads the columns of a table which have an index. The table name must already have been checked
+/// for illegal chars.
+///
+/// # Errors
+/// * `ConductorError::InternalError` : The columns couldn't be read
+///
+async fn read_indexed_columns(db: &db::QuestDbConn, table: &str) -> Result<schema_com::IndexedColumns, error_com::ConductorError> {
+    let table_copy = table.to_string();
+    let rows = match db.run(move |conn: &mut postgres::Client| {
+        conn.query("SELECT \"column\", \"indexed\" FROM table_columns($1);", &[&table_copy])
+    }).await {
+        Ok(rows) => rows,
         Err(err) => {
-            log::error!(
-                "There was an error persisting the producer to the db: {}",
-                err
+            return log_error_and_get_emit_result!(
+                error_com::ConductorError::InternalError(format!("Couldn't read the columns of table {}. {}", table, err))
             );
-            Err(error_com::ConductorError::InternalError(format!("There was an error persisting the producer to the db: {}", err)))
         }
+    };
+    Ok(rows.iter()
+        .filter(|row| row.try_get("indexed").unwrap_or(false))
+        .filter_map(|row| row.try_get("column").ok())
+        .collect())
+}
+
+///
+/// Registers several producers one after another. A registration which fails doesn't stop the others.
+///
+async fn register_batch(db: &db::QuestDbConn, uuids: UuidStrategy, registrations: &[producer_com::Registration]) -> producer_com::RegisterBatchResult {
+    let mut results = Vec::with_capacity(registrations.len());
+    for registration in registrations {
+        results.push(register(db, uuids, registration).await);
     }
+    producer_com::RegisterBatchResult { error: error_com::ConductorError::NoError, results }
+}
+
+///
+/// Rebuilds the registration of a producer from its row in the producers table. The uuid becomes the
+/// custom id so registering it again recreates the same producer.
+///
+fn producer_to_registration(producer: &Producer) -> Result<producer_com::Registration, error_com::ConductorError> {
+    registration_with_schema(producer, get_producer_schema(producer)?)
 }
 
-fn get_insert_sql(emit: &producer_com::Emit<'_, HashMap<String,serde_json::Value>>, column_names: &[&String]) -> Result<String, String> {
-    if column_names.is_empty() {
-        return Err("Insert Sql must have at least one colum but there were none".to_string());
+/// The same as `producer_to_registration` with the schema replaced by another one.
+fn registration_with_schema(producer: &Producer, schema: schema_com::Schema) -> Result<producer_com::Registration, error_com::ConductorError> {
+    let defaults = get_producer_column_defaults(producer)?;
+    let required = get_producer_required_columns(producer)?;
+    let metadata = get_producer_metadata(producer)?;
+    let mut registration = producer_com::Registration::new(producer.name.clone(), schema, Some(producer.uuid.clone()));
+    if let Some(tombstone_column) = &producer.tombstone_column {
+        registration = registration.with_tombstone_column(tombstone_column.clone());
+    }
+    if producer.strict_float_precision {
+        registration = registration.with_strict_float_precision();
+    }
+    if !producer.strict_columns {
+        registration = registration.with_lenient_columns();
+    }
+    if producer.coerce_numeric_strings {
+        registration = registration.with_numeric_string_coercion();
+    }
+    if producer.lenient_bool {
+        registration = registration.with_lenient_bool();
+    }
+    if let Some(row_quota) = producer.row_quota() {
+        registration = registration.with_row_quota(row_quota);
+    }
+    if let Some(ttl) = producer.ttl_seconds() {
+        registration = registration.with_ttl(ttl);
     }
-    let mut column_iter = column_names.iter();
-    let mut columns = format!("\"{}\"", column_iter.next().unwrap());
-    for column_name in column_iter {
-        columns = columns + ", " + &format!("\"{}\"", column_name);
+    registration = registration.with_timestamp_precision(producer.timestamp_precision());
+    if !producer.time_series {
+        registration = registration.without_time_series();
     }
+    for (column, value) in defaults {
+        registration = registration.with_column_default(column, value);
+    }
+    for column in required {
+        registration = registration.with_required_column(column);
+    }
+    for (column, column_metadata) in metadata.column_metadata {
+        registration = registration.with_column_metadata(column, column_metadata);
+    }
+    Ok(registration)
+}
 
-    let mut values_str = String::from("$1");
-    for i in 1..column_names.len() {
-        values_str.push_str(format!(",${}", i).as_str());
+///
+/// Exports a page of the registry ordered by uuid. A page holds at most `EXPORT_PAGE_SIZE`
+/// registrations. Producers whose registration can't be rebuilt are logged and left out rather than
+/// failing the whole export.
+///
+async fn export(db: &db::QuestDbConn, offset: Option<u64>, limit: Option<u32>) -> producer_com::ExportResult {
+    let offset = offset.unwrap_or_default();
+    let limit = limit.unwrap_or(producer_com::EXPORT_PAGE_SIZE).clamp(1, producer_com::EXPORT_PAGE_SIZE);
+    //one extra row is read to find out whether there's another page
+    let sql = format!("SELECT * FROM producers WHERE deleted = false ORDER BY uuid LIMIT {}, {};", offset, offset + u64::from(limit) + 1);
+    let rows = match db.run(move |conn: &mut postgres::Client| conn.query(sql.as_str(), &[])).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't read the producers from the database. {}", err));
+            log::error!("{}", error_code);
+            return producer_com::ExportResult { error: error_code, registrations: Vec::new(), next: None };
+        }
+    };
+    let next = if rows.len() > limit as usize { Some(offset + u64::from(limit)) } else { None };
+    let mut registrations = Vec::with_capacity(rows.len().min(limit as usize));
+    for row in rows.iter().take(limit as usize) {
+        let producer = producer_from_row(row);
+        match producer_to_registration(&producer) {
+            Ok(registration) => registrations.push(registration),
+            Err(error_code) => log::error!("Producer {} was left out of the export. {}", producer.uuid, error_code),
+        }
     }
-    Ok(format!(
-        "INSERT INTO \"{}\" ({}) VALUES ({});",
-        emit.get_uuid(), columns, values_str
-    ))
+    producer_com::ExportResult { error: error_com::ConductorError::NoError, registrations, next }
 }
 
+///
+/// Handles a registration for a custom id or name based uuid which is already registered. Registering the same schema
+/// again succeeds without changing anything. A different schema is a conflict and the existing table
+/// is left untouched.
+///
+fn reregister(existing: &Producer, registration: &producer_com::Registration) -> producer_com::RegistrationResult {
+    let registered_schema = match get_producer_schema(existing) {
+        Ok(schema) => schema,
+        Err(error_code) => return producer_com::RegistrationResult { error: error_code, uuid: None },
+    };
+    let differences = schema_com::schema_differences(&registered_schema, registration.get_schema());
+    if differences.is_empty() {
+        return producer_com::RegistrationResult {
+            error: error_com::ConductorError::NoError,
+            uuid: Some(existing.uuid.clone()),
+        };
+    }
+    let error_code = error_com::ConductorError::SchemaConflict(differences);
+    log::error!("{}", error_code);
+    producer_com::RegistrationResult { error: error_code, uuid: None }
+}
 
-async fn persist_emit(emit: &producer_com::Emit<'_, HashMap<String,serde_json::Value>>, db: &db::QuestDbConn) -> Result<(), error_com::ConductorError> {
-    let schema_json = get_producer_row(db, emit.get_uuid()).await?.schema;
-    if schema_json.is_empty() {
-        return log_error_and_get_emit_result!(
-            error_com::ConductorError::NoMembers(format!("Error persisting producer emit to db. Empty registered schema for uuid: {}",
-            emit.get_uuid()))
-        );
+/// The status sent with a registration result. Conflicts are reported as 409 so that clients know
+/// to migrate the producer rather than retry.
+pub(crate) fn registration_status(result: &producer_com::RegistrationResult) -> Status {
+    match result.error {
+        error_com::ConductorError::SchemaConflict(_) => Status::Conflict,
+        _ => Status::Ok,
     }
-    let schema: schema_com::Schema;
-    match serde_json::from_str(schema_json.as_str()) {
-        Ok(s) => schema = s,
-        Err(err) => return log_error_and_get_emit_result!(error_com::ConductorError::NoMembers(format!("Error persisting producer emit to db. Empty registered schema for uuid: {} with error: {}", emit.get_uuid(), err))),
-    };
+}
 
-    //pull out keys and values to guarantee order!
-    let mut columns = Vec::new();
-    let mut params_store: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
-    for (key, val) in emit.get_data() {
-        columns.push(key);
-        let data_type;
-        if let Some(dt) = schema.get(key) {
-            data_type = dt;
-        } else {
+///
+/// Reads the schema of a table from its columns in QuestDB. The table name must already have been
+/// checked for illegal chars.
+///
+/// # Errors
+/// * `ConductorError::Unregistered` : The table doesn't exist
+/// * `ConductorError::InvalidSchema` : A column has an unsupported type or there's no designated ts column
+/// * `ConductorError::InternalError` : The columns couldn't be read
+///
+async fn read_table_schema(db: &db::QuestDbConn, table: &str) -> Result<schema_com::Schema, error_com::ConductorError> {
+    let table_copy = table.to_string();
+    let rows = match db.run(move |conn: &mut postgres::Client| {
+        conn.query("SELECT \"column\", \"type\", \"designated\" FROM table_columns($1);", &[&table_copy])
+    }).await {
+        Ok(rows) => rows,
+        Err(err) => {
             return log_error_and_get_emit_result!(
-                error_com::ConductorError::InvalidColumnNames(format!("Error persisting producer emit to db. Schema doesn't contain key {}",
-                key))
+                error_com::ConductorError::InternalError(format!("Couldn't read the columns of table {}. {}", table, err))
             );
         }
-
-        match to_solid_type_from_json(val, *data_type) {
-            Ok(param) => params_store.push(param),
-            Err(err) => {
-                return log_error_and_get_emit_result!(
-                    error_com::ConductorError::InvalidData(format!("Error persisting producer emit to db. Couldn't parse data packet. {}",
-                    err))
-
-                );
-            }
-        }
+    };
+    if rows.is_empty() {
+        return log_error_and_get_emit_result!(error_com::ConductorError::Unregistered(format!("Table {} doesn't exist", table)));
     }
-    let sql = get_insert_sql(emit, &columns).unwrap();
-
-    let write_result = db
-        .run(move |conn: &mut postgres::Client| {
-            //we will do both these in one go so that we don't add it to the producers table unless we were able to create its data table
-
-            let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
-            for p in &params_store {
-                params.push(p.as_ref());
-            }
-            conn.execute(sql.as_str(), params.as_slice())
-        })
-        .await;
-    match write_result {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            log_error_and_get_emit_result!(
-                error_com::ConductorError::InternalError(format!("Error persisting producer emit to db. Couldn't parse data packet. {}",
-                err))
+    let columns: Vec<(String, String, bool)> = rows.iter().map(|row| (
+        row.try_get("column").unwrap_or_default(),
+        row.try_get("type").unwrap_or_default(),
+        row.try_get("designated").unwrap_or(false),
+    )).collect();
+    schema_com::schema_from_table_columns(table, &columns).map_err(|error_code| {
+        log::error!("{}", error_code);
+        error_code
+    })
+}
 
-            )
+///
+/// Registers a table which already exists in QuestDB as a producer. The schema is read from the
+/// table and the table name is used as the uuid of the producer. The designated timestamp of the
+/// table has to be called ts.
+///
+pub(crate) async fn adopt(db: &db::QuestDbConn, table: &str) -> producer_com::RegistrationResult {
+    if table.is_empty() || table.contains('.') || table.contains('\"') || table.contains('\'') {
+        let error_code = error_com::ConductorError::InvalidUuid(format!("Table name {} is empty or has illegal chars", table));
+        log::error!("{}", error_code);
+        return producer_com::RegistrationResult { error: error_code, uuid: None };
+    }
+    match get_producer_row(db, table).await {
+        Ok(_) => {
+            let error_code = error_com::ConductorError::InvalidUuid(format!("Table {} is already registered as a producer", table));
+            log::error!("{}", error_code);
+            return producer_com::RegistrationResult { error: error_code, uuid: None };
         }
+        Err(error_com::ConductorError::Unregistered(_)) => {}
+        Err(error_code) => return producer_com::RegistrationResult { error: error_code, uuid: None },
     }
-}
 
+    let schema = match read_table_schema(db, table).await {
+        Ok(schema) => schema,
+        Err(error_code) => return producer_com::RegistrationResult { error: error_code, uuid: None },
+    };
 
-#[post("/v1/producer/register", format = "msgpack", data = "<data>")]
-pub async fn register_pack(
-    conn: db::QuestDbConn,
-    data: MsgPack<producer_com::Registration>,
-) -> MsgPack<producer_com::RegistrationResult> {
-    MsgPack(register(&conn, &data).await)
+    // the table already exists so registering only records the producer. The table name is its
+    // custom id so the uuid strategy isn't used
+    let registration = producer_com::Registration::new(table.to_string(), schema, Some(table.to_string()));
+    register(db, UuidStrategy::default(), &registration).await
 }
 
-#[post("/v1/producer/register", format = "json", data = "<data>")]
-pub async fn register_json(
-    conn: db::QuestDbConn,
-    data: Json<producer_com::Registration>,
-) -> Json<producer_com::RegistrationResult> {
-    Json(register(&conn, &data).await)
+///
+/// Looks up the producer an emit is for. When the producer isn't registered and the emit carries a
+/// registration it's registered first, which creates its table on the first emit. An emit without a
+/// uuid registers the producer under the uuid the server assigns it. Two of these racing for the
+/// same uuid both register it but only one table is created.
+///
+/// Emits which carry a registration also get the producer they were stored for.
+///
+async fn get_or_register_producer_row(db: &db::QuestDbConn, uuids: UuidStrategy, data: &producer_com::Emit<'_,HashMap<String,serde_json::Value>>) -> Result<(Producer, Option<producer_com::EmitRegistration>), error_com::ConductorError> {
+    let lookup = if data.get_uuid().is_empty() && data.get_registration().is_some() {
+        Err(error_com::ConductorError::Unregistered("The emit didn't have a uuid".to_string()))
+    } else {
+        get_producer_row(db, data.get_uuid()).await
+    };
+    match lookup {
+        Ok(producer) => {
+            let registration = data.get_registration().map(|_| producer_com::EmitRegistration { uuid: producer.uuid.clone(), created: false });
+            Ok((producer, registration))
+        }
+        Err(error_com::ConductorError::Unregistered(reason)) => {
+            let registration = match data.checked_registration()? {
+                Some(registration) => registration,
+                None => return Err(error_com::ConductorError::Unregistered(reason)),
+            };
+            let (uuid, created) = register_producer(db, uuids, registration).await?;
+            let producer = get_producer_row(db, &uuid).await?;
+            Ok((producer, Some(producer_com::EmitRegistration { uuid, created })))
+        }
+        Err(error_code) => Err(error_code),
+    }
 }
 
+///
+/// Stores an emit. The columns of the insert follow `order` where it names them and the rest come after.
+///
+async fn emit(db: &db::QuestDbConn, quotas: &quota::Quotas, missing_tables: producer_com::MissingTablePolicy, uuids: UuidStrategy, data: &producer_com::Emit<'_,HashMap<String,serde_json::Value>>, order: &[String]) -> producer_com::EmitResult {
+    let validated = trace::phase("validate", data.get_uuid(), async {
+        let (producer, registration) = get_or_register_producer_row(db, uuids, data).await?;
+        check_not_paused(&producer)?;
+        let columns = get_producer_columns(&producer)?;
+        let options = producer.conversion_options();
+        // a merge is validated once it has been merged with the latest row
+        if !data.is_merge() {
+            validate_emit_schema(data.get_data(), &columns, options)?;
+        }
+        Ok((producer, registration, columns, options))
+    }).await;
+    let (producer, registration, columns, options) = match validated {
+        Ok(validated) => validated,
+        Err(error_code) => {
+            return producer_com::EmitResult {
+                error: error_code,
+                assigned_timestamp: None,
+                registration: None,
+            };
+        }
+    };
+    let row_quota = producer.row_quota();
+    if let Err(error_code) = quotas.consume(&producer.uuid, 1, row_quota) {
+        log::error!("{}", error_code);
+        return producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None };
+    }
+    // we know the schema is good, the uuid is good. The emit is good. Lets do this thing
+    let result = match trace::phase("db_write", &producer.uuid, persist_emit(data, order, &producer, &columns, options, missing_tables, db)).await {
+        Ok(timestamp) => producer_com::EmitResult {
+            error: error_com::ConductorError::NoError,
+            assigned_timestamp: timestamp,
+            registration,
+        },
+        Err(err) => {
+            quotas.refund(&producer.uuid, 1, row_quota);
+            producer_com::EmitResult { error: err, assigned_timestamp: None, registration: None }
+        }
+    };
+    quotas.persist_if_due(db).await;
+    result
+}
+
+///
+/// Generates the insert an emit would be stored with without storing it. The uuid has to be
+/// registered. Merges are explained as plain emits as the latest row isn't read. Every field is
+/// converted so the result reports each one which doesn't convert rather than only the first.
+///
+async fn explain_emit(db: &db::QuestDbConn, data: &producer_com::Emit<'_,HashMap<String,serde_json::Value>>) -> producer_com::ExplainEmitResult {
+    let failed = |error, fields| producer_com::ExplainEmitResult { error, sql: String::new(), columns: schema_com::Schema::new(), fields };
+    let producer = match get_producer_row(db, data.get_uuid()).await {
+        Ok(producer) => producer,
+        Err(error_code) => return failed(error_code, Vec::new()),
+    };
+    let columns = match get_producer_columns(&producer) {
+        Ok(columns) => columns,
+        Err(error_code) => return failed(error_code, Vec::new()),
+    };
+    let options = producer.conversion_options();
+    let resolved = resolve_emit_data(data.get_uuid(), data.get_data(), &columns, options);
+    let fields = check_field_conversions(&resolved, &columns.schema, options);
+    let empty = |error| failed(error, fields.clone());
+    if let Err(error_code) = validate_emit_schema(data.get_data(), &columns, options) {
+        return empty(error_code);
+    }
+    let timestamp = match producer.emit_timestamp_micros(data.get_timestamp()) {
+        Ok(timestamp) => timestamp,
+        Err(error_code) => return empty(error_code),
+    };
+    match generate_insert(data.get_uuid(), &resolved, &[], timestamp, &columns, options) {
+        Ok((sql, _)) => {
+            let mut resolved_columns: schema_com::Schema = resolved.keys()
+                .filter_map(|column| columns.schema.get_key_value(column))
+                .map(|(column, data_type)| (column.clone(), *data_type))
+                .collect();
+            if producer.time_series {
+                resolved_columns.insert("ts".to_string(), schema_com::DataTypes::Time);
+            }
+            producer_com::ExplainEmitResult { error: error_com::ConductorError::NoError, sql, columns: resolved_columns, fields }
+        }
+        Err(error_code) => empty(error_code),
+    }
+}
+
+/// The most rows a batch emit can have. Read from the `max_batch_rows` config value.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxBatchRows(usize);
+
+async fn emit_batch(db: &db::QuestDbConn, quotas: &quota::Quotas, missing_tables: producer_com::MissingTablePolicy, max_rows: MaxBatchRows, batch: &producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>) -> producer_com::EmitResult {
+    //oversized batches are rejected before anything is looked up so they can't hold a connection
+    if let Err(error_code) = batch.check_rows(max_rows.0) {
+        log::error!("{}", error_code);
+        return producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None };
+    }
+    if batch.get_data().is_empty() {
+        return producer_com::EmitResult {
+            error: error_com::ConductorError::NoError,
+            assigned_timestamp: None,
+            registration: None,
+        };
+    }
+    let producer = match get_producer_row(db, batch.get_uuid()).await {
+        Ok(producer) => producer,
+        Err(error_code) => {
+            return producer_com::EmitResult {
+                error: error_code,
+                assigned_timestamp: None,
+                registration: None,
+            };
+        }
+    };
+    if let Err(error_code) = check_not_paused(&producer) {
+        return producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None };
+    }
+    let columns = match get_producer_columns(&producer) {
+        Ok(columns) => columns,
+        Err(error_code) => {
+            return producer_com::EmitResult {
+                error: error_code,
+                assigned_timestamp: None,
+                registration: None,
+            };
+        }
+    };
+    let options = producer.conversion_options();
+    if let Err(error_code) = batch.get_data().iter().try_for_each(|data| validate_emit_schema(data, &columns, options)) {
+        return producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None };
+    }
+    let rows = batch.get_data().len() as u64;
+    let row_quota = producer.row_quota();
+    if let Err(error_code) = quotas.consume(batch.get_uuid(), rows, row_quota) {
+        log::error!("{}", error_code);
+        return producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None };
+    }
+    let result = match persist_emit_batch(batch, &producer, &columns, options, missing_tables, db).await {
+        Ok(_) => producer_com::EmitResult {
+            error: error_com::ConductorError::NoError,
+            assigned_timestamp: None,
+            registration: None,
+        },
+        Err(err) => {
+            quotas.refund(batch.get_uuid(), rows, row_quota);
+            producer_com::EmitResult { error: err, assigned_timestamp: None, registration: None }
+        }
+    };
+    quotas.persist_if_due(db).await;
+    result
+}
+
+///
+/// Checks every batch of a transaction and generates the inserts which store it. `producers[i]` is
+/// the producer `batches[i]` is for. Nothing is written so a transaction with a batch which would be
+/// rejected can be refused before any of its rows are stored.
+///
+/// # Errors
+/// * `ConductorError::Paused` : One of the producers is paused
+/// * Any error `validate_emit_schema` or `generate_insert` produces for one of the rows
+///
+pub fn generate_transaction_inserts(batches: &[producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>], producers: &[Producer]) -> Result<Vec<(String, InsertParams)>, error_com::ConductorError> {
+    let mut inserts = Vec::with_capacity(batches.iter().map(|batch| batch.get_data().len()).sum());
+    for (batch, producer) in batches.iter().zip(producers) {
+        check_not_paused(producer)?;
+        let columns = get_producer_columns(producer)?;
+        let options = producer.conversion_options();
+        for data in batch.get_data() {
+            validate_emit_schema(data, &columns, options)?;
+            inserts.push(generate_insert(batch.get_uuid(), data, &[], None, &columns, options)?);
+        }
+    }
+    Ok(inserts)
+}
+
+///
+/// Stores the batches of a transaction which spans several producers. Every batch is checked before
+/// anything is written and all of the rows are written in one database transaction so either every
+/// row is stored or none are.
+///
+async fn emit_transaction(db: &db::QuestDbConn, quotas: &quota::Quotas, missing_tables: producer_com::MissingTablePolicy, max_rows: MaxBatchRows, batches: &[producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>]) -> producer_com::EmitResult {
+    let emit_result = |error_code: error_com::ConductorError| producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None };
+    let rows: usize = batches.iter().map(|batch| batch.get_data().len()).sum();
+    if rows > max_rows.0 {
+        let error_code = error_com::ConductorError::BatchTooLarge(format!("The transaction has {} rows which is more than the maximum of {}.", rows, max_rows.0));
+        log::error!("{}", error_code);
+        return emit_result(error_code);
+    }
+    let mut producers = Vec::with_capacity(batches.len());
+    for batch in batches {
+        match get_producer_row(db, batch.get_uuid()).await {
+            Ok(producer) => producers.push(producer),
+            Err(error_code) => return emit_result(error_code),
+        }
+    }
+    let inserts = match generate_transaction_inserts(batches, &producers) {
+        Ok(inserts) => inserts,
+        Err(error_code) => return emit_result(error_code),
+    };
+    let refund = |consumed: &[producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>]| {
+        for (batch, producer) in consumed.iter().zip(&producers) {
+            quotas.refund(batch.get_uuid(), batch.get_data().len() as u64, producer.row_quota());
+        }
+    };
+    for (index, (batch, producer)) in batches.iter().zip(&producers).enumerate() {
+        if let Err(error_code) = quotas.consume(batch.get_uuid(), batch.get_data().len() as u64, producer.row_quota()) {
+            log::error!("{}", error_code);
+            refund(&batches[..index]);
+            return emit_result(error_code);
+        }
+    }
+
+    let write = |inserts: Vec<(String, InsertParams)>| db
+        .run(move |conn: &mut postgres::Client| {
+            let mut transaction = conn.transaction()?;
+            for (sql, params_store) in &inserts {
+                transaction.execute(sql.as_str(), as_sql_params(params_store).as_slice())?;
+            }
+            transaction.commit()
+        });
+    let mut write_result = write(inserts).await;
+    if let Err(err) = &write_result {
+        //the whole transaction was rolled back so it's written again once the missing table is back
+        if let Some(producer) = producers.iter().find(|producer| is_missing_table_error(err, &producer.uuid)) {
+            write_result = match recreate_missing_table(db, producer, missing_tables).await.and_then(|_| generate_transaction_inserts(batches, &producers)) {
+                Ok(inserts) => write(inserts).await,
+                Err(error_code) => {
+                    refund(batches);
+                    return emit_result(error_code);
+                }
+            };
+        }
+    }
+    let result = match write_result {
+        Ok(_) => emit_result(error_com::ConductorError::NoError),
+        Err(err) => {
+            refund(batches);
+            let error_code = error_com::ConductorError::InternalError(format!("Error persisting the transaction to db. Nothing was stored. {}", err));
+            log::error!("{}", error_code);
+            emit_result(error_code)
+        }
+    };
+    quotas.persist_if_due(db).await;
+    result
+}
+
+///
+/// Stores the emits of a session one after another. Each emit is stored or rejected on its own the
+/// same way it would be if it was sent by itself.
+///
+async fn emit_session(db: &db::QuestDbConn, quotas: &quota::Quotas, missing_tables: producer_com::MissingTablePolicy, uuids: UuidStrategy, max_rows: MaxBatchRows, emits: &[producer_com::Emit<'_, HashMap<String,serde_json::Value>>]) -> producer_com::EmitSessionResult {
+    if emits.len() > max_rows.0 {
+        let error_code = error_com::ConductorError::BatchTooLarge(format!("The session has {} emits which is more than the maximum of {}.", emits.len(), max_rows.0));
+        log::error!("{}", error_code);
+        return producer_com::EmitSessionResult { error: error_code, results: Vec::new() };
+    }
+    let mut results = Vec::with_capacity(emits.len());
+    for data in emits {
+        results.push(emit(db, quotas, missing_tables, uuids, data, &[]).await);
+    }
+    producer_com::EmitSessionResult { error: error_com::ConductorError::NoError, results }
+}
+
+/// Sessions with more emits than `max_batch_rows` are answered with 413 Payload Too Large.
+fn session_status(result: &producer_com::EmitSessionResult) -> Status {
+    match result.error {
+        error_com::ConductorError::BatchTooLarge(_) => Status::PayloadTooLarge,
+        _ => Status::Ok,
+    }
+}
+
+/// Emits which were rejected because of a quota are answered with 429 Too Many Requests, emits
+/// for a paused producer with 503 Service Unavailable and batches with too many rows with 413 Payload Too Large.
+fn emit_status(result: &producer_com::EmitResult) -> Status {
+    match result.error {
+        error_com::ConductorError::QuotaExceeded(_) => Status::TooManyRequests,
+        error_com::ConductorError::Paused(_) => Status::ServiceUnavailable,
+        error_com::ConductorError::BatchTooLarge(_) => Status::PayloadTooLarge,
+        _ => Status::Ok,
+    }
+}
+
+/// The response to an emit whose msgpack body was rejected before it was decoded.
+#[cfg(feature = "msgpack")]
+fn rejected_emit((status, error): format::Rejection) -> (Status, Negotiated<producer_com::EmitResult>) {
+    log::error!("{}", error);
+    (status, Negotiated(producer_com::EmitResult { error, assigned_timestamp: None, registration: None }))
+}
+
+/// Emits for a producer are rejected while its ingestion is paused.
+fn check_not_paused(producer: &Producer) -> Result<(), error_com::ConductorError> {
+    if producer.paused {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::Paused(format!("Ingestion is paused for producer {}", producer.uuid))
+        );
+    }
+    Ok(())
+}
+
+///
+/// Pauses or resumes ingestion for a producer. Nothing else about the producer changes.
+///
+async fn set_paused(db: &db::QuestDbConn, uuid: &str, paused: bool) -> producer_com::PauseResult {
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::PauseResult { error: error_code, paused: false },
+    };
+    let uuid = producer.uuid.clone();
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute("UPDATE producers SET paused = $1 WHERE uuid = $2;", &[&paused, &uuid])
+        })
+        .await;
+    match write_result {
+        Ok(_) => {
+            log::info!("Ingestion for producer {} is {}", producer.uuid, if paused { "paused" } else { "resumed" });
+            producer_com::PauseResult { error: error_com::ConductorError::NoError, paused }
+        }
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't change whether producer {} is paused. {}", producer.uuid, err));
+            log::error!("{}", error_code);
+            producer_com::PauseResult { error: error_code, paused: producer.paused }
+        }
+    }
+}
+
+fn validate_registration(registration: &producer_com::Registration) -> error_com::ConductorError {
+    match registration.validate() {
+        Ok(()) => error_com::ConductorError::NoError,
+        Err(error_code) => {
+            log_error_with_json!(registration, "{}", error_code);
+            error_code
+        }
+    }
+}
+
+/// How the uuid of a producer registered without a custom id is made. Read from the `uuid_strategy`
+/// and `uuid_namespace` config values and defaults to a random uuid for every producer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UuidStrategy {
+    /// A random (v4) uuid.
+    #[default]
+    Random,
+    /// A name based (v5) uuid made from the namespace and the name of the producer. A producer
+    /// registered under the same name always gets the same uuid, even on another server.
+    Named(Uuid),
+}
+
+/// Why the `uuid_strategy` and `uuid_namespace` config values can't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UuidStrategyError {
+    /// `uuid_strategy` isn't one of `random` or `named`. Holds the value that was given.
+    UnknownStrategy(String),
+    /// `uuid_strategy` is `named` but `uuid_namespace` isn't set.
+    MissingNamespace,
+    /// `uuid_namespace` isn't a uuid. Holds the reason it couldn't be parsed.
+    MalformedNamespace(String),
+}
+
+impl std::error::Error for UuidStrategyError {}
+
+impl std::fmt::Display for UuidStrategyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UuidStrategyError::UnknownStrategy(strategy) => write!(f, "uuid_strategy {} isn't known. It should be random or named", strategy),
+            UuidStrategyError::MissingNamespace => write!(f, "uuid_strategy is named but uuid_namespace isn't set. Set it to the uuid the producer names are hashed under"),
+            UuidStrategyError::MalformedNamespace(reason) => write!(f, "uuid_namespace isn't a valid uuid ({})", reason),
+        }
+    }
+}
+
+impl UuidStrategy {
+    ///
+    /// Reads the strategy from the `uuid_strategy` and `uuid_namespace` config values. The namespace is
+    /// only used by the named strategy.
+    ///
+    /// # Errors
+    /// * `UuidStrategyError::UnknownStrategy` : The strategy isn't `random` or `named`
+    /// * `UuidStrategyError::MissingNamespace` : The strategy is `named` without a namespace
+    /// * `UuidStrategyError::MalformedNamespace` : The namespace couldn't be parsed as a uuid
+    ///
+    pub fn from_config(strategy: Option<&str>, namespace: Option<&str>) -> Result<Self, UuidStrategyError> {
+        match strategy.map(str::trim) {
+            None | Some("random") => Ok(Self::Random),
+            Some("named") => {
+                let namespace = namespace.filter(|namespace| !namespace.trim().is_empty()).ok_or(UuidStrategyError::MissingNamespace)?;
+                Uuid::parse_str(namespace.trim())
+                    .map(Self::Named)
+                    .map_err(|err| UuidStrategyError::MalformedNamespace(err.to_string()))
+            }
+            Some(strategy) => Err(UuidStrategyError::UnknownStrategy(strategy.to_string())),
+        }
+    }
+
+    /// The uuid a producer with this name is given when it doesn't have a custom id.
+    #[must_use]
+    pub fn generate(self, name: &str) -> String {
+        self.name_based(name).unwrap_or_else(|| Uuid::new_v4().to_string())
+    }
+
+    /// The uuid a producer with this name always gets. None when the uuid is random.
+    fn name_based(self, name: &str) -> Option<String> {
+        match self {
+            Self::Random => None,
+            Self::Named(namespace) => Some(Uuid::new_v5(&namespace, name.as_bytes()).to_string()),
+        }
+    }
+}
+
+/// Fails ignition when the uuid strategy can't be used rather than falling back to random uuids,
+/// which would give producers a new uuid each time they register.
+pub async fn manage_uuid_strategy(rocket: rocket::Rocket<rocket::Build>) -> rocket::fairing::Result {
+    let strategy: Option<String> = rocket.figment().extract_inner("uuid_strategy").ok();
+    let namespace: Option<String> = rocket.figment().extract_inner("uuid_namespace").ok();
+    match UuidStrategy::from_config(strategy.as_deref(), namespace.as_deref()) {
+        Ok(strategy) => Ok(rocket.manage(strategy)),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(rocket)
+        }
+    }
+}
+
+/// Returns the uuid for a new producer.
+#[inline]
+fn get_or_create_uuid_for_registration(registration: &producer_com::Registration, uuids: UuidStrategy) -> String {
+    match &registration.get_custom_id() {
+        Some(custom_id) => (*custom_id).to_string(),
+        None => uuids.generate(registration.get_name()),
+    }
+}
+
+
+///
+/// Converts an error produced while creating the table for a producer into the matching Conductor error.
+/// The table is created with `IF NOT EXISTS` so an existing table isn't an error. Errors which didn't
+/// come from the database are an `InternalError`. See `classify_registration_error`.
+///
+fn map_registration_db_error(err: &postgres::Error) -> error_com::ConductorError {
+    match err.as_db_error() {
+        Some(db_error) => classify_registration_error(db_error.code().code(), db_error.message()),
+        None => error_com::ConductorError::InternalError(format!("There was an error persisting the producer to the db: {}", err)),
+    }
+}
+
+///
+/// Converts the sql state and message of a database error produced while creating the table for a
+/// producer into the matching Conductor error. The standard sql state is used where it's given.
+/// QuestDB reports most failures with a generic sql state so they're also recognised by their
+/// message. Anything that isn't recognised is an `InternalError`.
+///
+#[must_use]
+pub fn classify_registration_error(code: &str, message: &str) -> error_com::ConductorError {
+    let lower_message = message.to_lowercase();
+    let column_name_rejected = matches!(code, "42701" | "42602" | "42939")
+        || lower_message.contains("duplicate column")
+        || lower_message.contains("invalid column name")
+        || lower_message.contains("invalid characters")
+        || lower_message.contains("reserved");
+    let type_rejected = code == "42704"
+        || (lower_message.contains("type") && (lower_message.contains("unsupported") || lower_message.contains("invalid") || lower_message.contains("unknown")));
+    if column_name_rejected {
+        error_com::ConductorError::InvalidColumnNames(format!("QuestDB rejected a column name: {}", message))
+    } else if type_rejected {
+        error_com::ConductorError::InvalidSchema(format!("QuestDB doesn't support a column type in the schema: {}", message))
+    } else {
+        error_com::ConductorError::InternalError(format!("There was an error persisting the producer to the db: {}", message))
+    }
+}
+
+///
+/// Returns true if a QuestDB error message says that `table` doesn't exist. Messages about a missing
+/// column or any other table don't count, so only a write to a dropped table is treated as one.
+///
+#[must_use]
+pub fn is_missing_table_message(message: &str, table: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("does not exist") && !message.contains("column") && message.contains(&table.to_lowercase())
+}
+
+///
+/// Returns true if a write failed because the data table of the producer doesn't exist. As with
+/// `map_registration_db_error` the error is recognised by its message.
+///
+fn is_missing_table_error(err: &postgres::Error, uuid: &str) -> bool {
+    err.as_db_error().map_or(false, |db_error| is_missing_table_message(db_error.message(), uuid))
+}
+
+///
+/// Decides whether the missing table of a producer may be created again.
+///
+/// # Errors
+/// * `ConductorError::TableMissing` : The policy is to reject emits for the producer
+///
+pub fn check_missing_table_policy(missing_tables: producer_com::MissingTablePolicy, uuid: &str) -> Result<(), error_com::ConductorError> {
+    match missing_tables {
+        producer_com::MissingTablePolicy::Reject => Err(
+            error_com::ConductorError::TableMissing(format!("The table of producer {} doesn't exist. Register the producer again.", uuid))
+        ),
+        producer_com::MissingTablePolicy::Recreate => Ok(()),
+    }
+}
+
+///
+/// Handles a write to the data table of a producer which was dropped outside of Conductor. With
+/// `MissingTablePolicy::Recreate` the table is created again from the registration so the write can be
+/// retried. Otherwise the emit is rejected.
+///
+/// # Errors
+/// * `ConductorError::TableMissing` : The policy is to reject the emit
+/// * `ConductorError::InternalError` : The table couldn't be created again
+///
+async fn recreate_missing_table(db: &db::QuestDbConn, producer: &Producer, missing_tables: producer_com::MissingTablePolicy) -> Result<(), error_com::ConductorError> {
+    if let Err(err) = check_missing_table_policy(missing_tables, &producer.uuid) {
+        return log_error_and_get_emit_result!(err);
+    }
+    let registration = producer_to_registration(producer)?;
+    let sql = generate_create_table_sql(&registration, &producer.uuid);
+    match db.run(move |conn: &mut postgres::Client| conn.execute(sql.as_str(), &[])).await {
+        Ok(_) => {
+            log::warn!("The table of producer {} was missing and has been created again", producer.uuid);
+            Ok(())
+        }
+        Err(err) => log_error_and_get_emit_result!(
+            error_com::ConductorError::InternalError(format!("Couldn't create the missing table of producer {} again. {}", producer.uuid, err))
+        ),
+    }
+}
+
+///
+/// Creates the table of a registered producer again from its registration. The admin reconcile uses
+/// it to restore tables which were dropped outside of Conductor.
+///
+/// # Errors
+/// * `ConductorError::Unregistered` : The producer isn't registered
+/// * `ConductorError::InternalError` : The table couldn't be created again
+///
+pub(crate) async fn recreate_table(db: &db::QuestDbConn, uuid: &str) -> Result<(), error_com::ConductorError> {
+    let producer = get_producer_row(db, uuid).await?;
+    recreate_missing_table(db, &producer, producer_com::MissingTablePolicy::Recreate).await
+}
+
+async fn persist_registration(registration: &producer_com::Registration, uuids: UuidStrategy, db: &db::QuestDbConn) -> Result<String, error_com::ConductorError> {
+    let uuid = get_or_create_uuid_for_registration(registration, uuids);
+    let create_table_sql = generate_create_table_sql(registration, &uuid);
+    let producer = Producer::from_registration(&uuid, registration);
+    let schema_hash = schema_com::schema_hash(registration.get_schema());
+
+    let result: Result<u64, _> = db
+        .run(move |conn: &mut postgres::Client| {
+            //we will do both these in one go so that we don't add it to the producers table unless we were able to create its data table
+            log::info!("creating table with sql {}", create_table_sql);
+            let result = conn.execute(create_table_sql.as_str(), &[]);
+            if result.is_err() {
+                return result;
+            }
+            conn.execute(
+                "INSERT INTO producers (name, uuid, schema, tombstone_column, strict_float_precision, column_defaults, required_columns, id_source, schema_hash, strict_columns, quota, coerce_numeric_strings, column_metadata, ttl, registered_at, timestamp_precision, time_series, lenient_bool) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18);",
+                &[&producer.name, &producer.uuid, &producer.schema, &producer.tombstone_column, &producer.strict_float_precision, &producer.column_defaults, &producer.required_columns, &producer.id_source, &schema_hash, &producer.strict_columns, &producer.quota, &producer.coerce_numeric_strings, &producer.column_metadata, &producer.ttl, &producer.registered_at, &producer.timestamp_precision, &producer.time_series, &producer.lenient_bool],
+            )
+        })
+        .await;
+    match result {
+        Ok(_) => Ok(uuid),
+        Err(err) => {
+            log_error_and_get_emit_result!(map_registration_db_error(&err))
+        }
+    }
+}
+
+type InsertParams = Vec<Box<dyn ToSql + Sync + Send>>;
+
+#[inline]
+fn as_sql_params(params_store: &[Box<dyn ToSql + Sync + Send>]) -> Vec<&(dyn ToSql + Sync)> {
+    let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(params_store.len());
+    for p in params_store {
+        params.push(p.as_ref());
+    }
+    params
+}
+
+///
+/// Fills the columns which were left out of emitted data with their default value instead of null.
+/// Columns which aren't in the schema are dropped when the producer doesn't have strict columns.
+///
+fn resolve_emit_data<'a>(table_name: &str, data: &'a HashMap<String, serde_json::Value>, columns: &ProducerColumns, options: ConversionOptions) -> Cow<'a, HashMap<String, serde_json::Value>> {
+    if columns.defaults.is_empty() && options.strict_columns {
+        return Cow::Borrowed(data);
+    }
+    let mut data_with_defaults = data.clone();
+    schema_com::fill_column_defaults(&mut data_with_defaults, &columns.defaults);
+    if !options.strict_columns {
+        let dropped = schema_com::retain_known_columns(&mut data_with_defaults, &columns.schema);
+        if !dropped.is_empty() {
+            log::debug!("Dropped columns {:?} which aren't in the schema of {}", dropped, table_name);
+        }
+    }
+    Cow::Owned(data_with_defaults)
+}
+
+///
+/// Converts a single set of emitted data into an insert statement and the parameters for that
+/// statement using the registered schema to determine the type of each column.
+///
+/// # Errors
+/// * `ConductorError::InvalidColumnNames` : The data contains a column which isn't in the schema
+/// * `ConductorError::InvalidData` : A value couldn't be converted to the type of it's column
+/// * `ConductorError::NoMembers` : The data doesn't contain any columns
+///
+/// The row is stored with `timestamp` when it's given, otherwise the database assigns one.
+///
+fn generate_insert(table_name: &str, data: &HashMap<String, serde_json::Value>, order: &[String], timestamp: Option<u64>, columns: &ProducerColumns, options: ConversionOptions) -> Result<(String, InsertParams), error_com::ConductorError> {
+    let data = resolve_emit_data(table_name, data, columns, options);
+    //pull out keys and values to guarantee order!
+    let ts_column = String::from("ts");
+    let mut column_names = Vec::new();
+    let mut params_store: InsertParams = Vec::new();
+    if let Some(timestamp) = timestamp {
+        match micros_to_naive_date_time(timestamp) {
+            Some(ts) => {
+                column_names.push(&ts_column);
+                params_store.push(Box::new(ts));
+            }
+            None => {
+                return log_error_and_get_emit_result!(
+                    error_com::ConductorError::InvalidData(format!("The timestamp {} is out of range", timestamp))
+                );
+            }
+        }
+    }
+    let (_, unknown) = schema_com::diff_emit_columns(data.keys(), &columns.schema);
+    if !unknown.is_empty() {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::InvalidColumnNames(format!("Error persisting producer emit to db. Schema doesn't contain the keys {}",
+            unknown.join(", ")))
+        );
+    }
+    for key in schema_com::order_columns(data.keys(), order) {
+        column_names.push(key);
+        match to_solid_type_from_json(&data[key], columns.schema[key], options) {
+            Ok(param) => params_store.push(param),
+            Err(err) => {
+                return log_error_and_get_emit_result!(
+                    error_com::ConductorError::InvalidData(format!("Error persisting producer emit to db. Couldn't parse data packet. {}",
+                    err))
+
+                );
+            }
+        }
+    }
+    match get_insert_sql(table_name, &column_names) {
+        Ok(sql) => Ok((sql, params_store)),
+        Err(err) => log_error_and_get_emit_result!(
+            error_com::ConductorError::NoMembers(format!("Error persisting producer emit to db. {}",
+            err))
+        ),
+    }
+}
+
+///
+/// Reads the latest row of a producer which hasn't been soft deleted. Returns `None` when the
+/// producer hasn't stored any rows yet.
+///
+async fn latest_row(db: &db::QuestDbConn, producer: &Producer, schema: &schema_com::Schema) -> Result<Option<HashMap<String, serde_json::Value>>, error_com::ConductorError> {
+    let filter = RowFilter { from: None, to: None, limit: Some(1), newest_first: true, include_deleted: false };
+    let (sql, params) = generate_select_sql(producer, &filter)?;
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query(sql.as_str(), as_time_params(&params).as_slice())
+        })
+        .await;
+    match read_result {
+        Ok(rows) => rows.first().map(|row| row_to_map(row, schema)).transpose(),
+        Err(err) => {
+            log_error_and_get_emit_result!(
+                error_com::ConductorError::InternalError(format!("Error reading the latest row of producer {} to merge into. {}",
+                producer.uuid, err))
+            )
+        }
+    }
+}
+
+///
+/// Reads the latest row of every producer and returns the values of its numeric columns as gauges.
+/// Producers which can't be read are left out.
+///
+#[cfg(feature = "metrics")]
+pub(crate) async fn latest_value_gauges(db: &db::QuestDbConn) -> Vec<metrics::Gauge> {
+    let rows = match db.run(|conn: &mut postgres::Client| conn.query("SELECT * FROM producers WHERE deleted = false;", &[])).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            log::error!("Couldn't read the producers for their latest values. {}", err);
+            return Vec::new();
+        }
+    };
+    let mut gauges = Vec::new();
+    for row in &rows {
+        let producer = producer_from_row(row);
+        let schema = match get_producer_schema(&producer) {
+            Ok(schema) => schema,
+            Err(_) => continue,
+        };
+        if let Ok(Some(latest)) = latest_row(db, &producer, &schema).await {
+            gauges.extend(metrics::latest_value_gauges(&producer.uuid, &latest, &schema));
+        }
+    }
+    gauges
+}
+
+///
+/// Writes an emit to the data table of its producer and returns the timestamp it was stored with in
+/// the timestamp precision of the producer. Emits without a timestamp are stored with the current time. When the emit is a merge the latest
+/// row is read first and the emit is overlaid onto it. Nothing stops another emit from being written
+/// between the read and the write so concurrent merges can be based on a stale row.
+///
+async fn persist_emit(emit: &producer_com::Emit<'_, HashMap<String,serde_json::Value>>, order: &[String], producer: &Producer, columns: &ProducerColumns, options: ConversionOptions, missing_tables: producer_com::MissingTablePolicy, db: &db::QuestDbConn) -> Result<Option<u64>, error_com::ConductorError> {
+    let merged;
+    let data = if emit.is_merge() {
+        merged = match latest_row(db, producer, &columns.schema).await? {
+            Some(latest) => schema_com::merge_with_latest(&latest, emit.get_data(), &columns.schema),
+            None => emit.get_data().clone(),
+        };
+        validate_emit_schema(&merged, columns, options)?;
+        &merged
+    } else {
+        emit.get_data()
+    };
+    let timestamp = producer.emit_timestamp_micros(emit.get_timestamp())?;
+    let write = |(sql, params_store): (String, InsertParams)| db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute(sql.as_str(), as_sql_params(&params_store).as_slice())
+        });
+    let write_result = match write(generate_insert(&producer.uuid, data, order, timestamp, columns, options)?).await {
+        Err(err) if is_missing_table_error(&err, &producer.uuid) => {
+            recreate_missing_table(db, producer, missing_tables).await?;
+            write(generate_insert(&producer.uuid, data, order, timestamp, columns, options)?).await
+        }
+        write_result => write_result,
+    };
+    match write_result {
+        Ok(_) => Ok(timestamp.map(|timestamp| producer.timestamp_precision().from_micros(timestamp))),
+        Err(err) => {
+            log_error_and_get_emit_result!(
+                error_com::ConductorError::InternalError(format!("Error persisting producer emit to db. Couldn't parse data packet. {}",
+                err))
+
+            )
+        }
+    }
+}
+
+async fn persist_emit_batch(batch: &producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>, producer: &Producer, columns: &ProducerColumns, options: ConversionOptions, missing_tables: producer_com::MissingTablePolicy, db: &db::QuestDbConn) -> Result<(), error_com::ConductorError> {
+    let generate_inserts = || batch.get_data().iter()
+        .map(|data| generate_insert(batch.get_uuid(), data, &[], None, columns, options))
+        .collect::<Result<Vec<_>, _>>();
+    let write = |inserts: Vec<(String, InsertParams)>| db
+        .run(move |conn: &mut postgres::Client| {
+            //the whole batch is written in one transaction so that either every emit is stored or none of them are
+            let mut transaction = conn.transaction()?;
+            for (sql, params_store) in &inserts {
+                transaction.execute(sql.as_str(), as_sql_params(params_store).as_slice())?;
+            }
+            transaction.commit()
+        });
+    let write_result = match write(generate_inserts()?).await {
+        Err(err) if is_missing_table_error(&err, &producer.uuid) => {
+            recreate_missing_table(db, producer, missing_tables).await?;
+            write(generate_inserts()?).await
+        }
+        write_result => write_result,
+    };
+    match write_result {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            log_error_and_get_emit_result!(
+                error_com::ConductorError::InternalError(format!("Error persisting producer emit batch to db. {}",
+                err))
+            )
+        }
+    }
+}
+
+///
+/// Converts a timestamp in microseconds since the unix epoch into the type used by the database.
+///
+fn micros_to_naive_date_time(micros: u64) -> Option<chrono::NaiveDateTime> {
+    let seconds = i64::try_from(micros / 1_000_000).ok()?;
+    let nanoseconds = u32::try_from((micros % 1_000_000) * 1_000).ok()?;
+    chrono::Utc.timestamp_opt(seconds, nanoseconds).single().map(|time| time.naive_utc())
+}
+
+///
+/// Converts a timestamp read from the database into microseconds since the unix epoch. This is the
+/// inverse of `micros_to_naive_date_time`.
+///
+fn naive_date_time_to_micros(time: chrono::NaiveDateTime) -> Option<u64> {
+    let time = chrono::Utc.from_utc_datetime(&time);
+    let seconds = u64::try_from(time.timestamp()).ok()?;
+    seconds.checked_mul(1_000_000)?.checked_add(u64::from(time.timestamp_subsec_micros()))
+}
+
+///
+/// Converts a value read from the database into json. This is the inverse of `to_solid_type_from_json`.
+///
+fn to_json_from_sql(row: &Row, index: usize, data_type: schema_com::DataTypes) -> Result<serde_json::Value, postgres::Error> {
+    Ok(match data_type {
+        schema_com::DataTypes::Int => serde_json::json!(row.try_get::<_, Option<i64>>(index)?),
+        schema_com::DataTypes::Float => serde_json::json!(row.try_get::<_, Option<f32>>(index)?),
+        schema_com::DataTypes::Double => serde_json::json!(row.try_get::<_, Option<f64>>(index)?),
+        schema_com::DataTypes::Time => serde_json::json!(row.try_get::<_, Option<chrono::NaiveDateTime>>(index)?),
+        schema_com::DataTypes::String => serde_json::json!(row.try_get::<_, Option<String>>(index)?),
+        schema_com::DataTypes::Bool => serde_json::json!(row.try_get::<_, Option<bool>>(index)?),
+        schema_com::DataTypes::Binary => serde_json::json!(row.try_get::<_, Option<Vec<u8>>>(index)?),
+    })
+}
+
+///
+/// Converts a row from a producers data table into a map of column name to value. Columns which
+/// aren't in the schema (such as the tombstone column) are left out.
+///
+fn row_to_map(row: &Row, schema: &schema_com::Schema) -> Result<HashMap<String, serde_json::Value>, error_com::ConductorError> {
+    let mut values = HashMap::with_capacity(row.len());
+    for (index, column) in row.columns().iter().enumerate() {
+        let data_type = if let Some(data_type) = schema.get(column.name()) {
+            *data_type
+        } else if column.name() == "ts" {
+            schema_com::DataTypes::Time
+        } else {
+            continue;
+        };
+        match to_json_from_sql(row, index, data_type) {
+            Ok(value) => {
+                values.insert(column.name().to_string(), value);
+            }
+            Err(err) => {
+                return log_error_and_get_emit_result!(
+                    error_com::ConductorError::InternalError(format!("Couldn't read column {} from the database. {}",
+                    column.name(), err))
+                );
+            }
+        }
+    }
+    Ok(values)
+}
+
+///
+/// Appends a row from a producers data table to the columns of a columnar query result. Every row
+/// of a query has the same columns so the columns stay the same length. As with `row_to_map`
+/// columns which aren't in the schema are left out.
+///
+fn push_row_to_columns(row: &Row, schema: &schema_com::Schema, columnar: &mut producer_com::ColumnarRows) -> Result<(), error_com::ConductorError> {
+    for (index, column) in row.columns().iter().enumerate() {
+        let read_result = if let Some(data_type) = schema.get(column.name()) {
+            to_json_from_sql(row, index, *data_type).map(|value| {
+                columnar.columns.entry(column.name().to_string()).or_default().push(value);
+            })
+        } else if column.name() == "ts" {
+            row.try_get::<_, chrono::NaiveDateTime>(index).map(|ts| {
+                columnar.ts.push(u64::try_from(chrono::Utc.from_utc_datetime(&ts).timestamp_micros()).unwrap_or_default());
+            })
+        } else {
+            continue;
+        };
+        if let Err(err) = read_result {
+            return log_error_and_get_emit_result!(
+                error_com::ConductorError::InternalError(format!("Couldn't read column {} from the database. {}",
+                column.name(), err))
+            );
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn as_time_params(params_store: &[chrono::NaiveDateTime]) -> Vec<&(dyn ToSql + Sync)> {
+    let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(params_store.len());
+    for p in params_store {
+        params.push(p);
+    }
+    params
+}
+
+/// Restricts which rows are returned when reading from the data table of a producer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RowFilter {
+    /// The earliest timestamp of a row in the timestamp precision of the producer
+    pub from: Option<u64>,
+    /// The latest timestamp of a row in the timestamp precision of the producer
+    pub to: Option<u64>,
+    pub limit: Option<u64>,
+    pub newest_first: bool,
+    /// Include rows which have been soft deleted
+    pub include_deleted: bool,
+}
+
+///
+/// Generates the conditions of a where clause which select the rows matching the filter along with
+/// the timestamp parameters referenced by the conditions. The limit and ordering aren't included.
+///
+fn generate_row_conditions(producer: &Producer, filter: &RowFilter) -> Result<(Vec<String>, Vec<chrono::NaiveDateTime>), error_com::ConductorError> {
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    if !producer.time_series && (filter.from.is_some() || filter.to.is_some()) {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::InvalidData(format!("Producer {} isn't a time series so its rows can't be selected by time", producer.uuid))
+        );
+    }
+    for (bound, comparison) in [(filter.from, ">="), (filter.to, "<=")] {
+        if let Some(bound) = bound {
+            match producer.timestamp_precision().to_micros(bound).and_then(micros_to_naive_date_time) {
+                Some(time) => params.push(time),
+                None => {
+                    return log_error_and_get_emit_result!(
+                        error_com::ConductorError::InvalidData(format!("The timestamp {} is out of range",
+                        bound))
+                    );
+                }
+            }
+            conditions.push(format!("ts {} ${}", comparison, params.len()));
+        }
+    }
+    if let Some(tombstone_column) = &producer.tombstone_column {
+        if !filter.include_deleted {
+            conditions.push(format!("{} = false", quote_ident(tombstone_column)));
+        }
+    }
+    Ok((conditions, params))
+}
+
+///
+/// Generates the sql used to read rows from the data table of a producer along with the timestamp
+/// parameters referenced by the sql. Plain tables don't have `ts` so their rows are read in the order
+/// they were inserted and `newest_first` takes the last rows.
+///
+/// # Errors
+/// * `ConductorError::InvalidData` : A timestamp of the filter is out of range or the producer isn't
+///   a time series and the filter has a timestamp
+///
+pub fn generate_select_sql(producer: &Producer, filter: &RowFilter) -> Result<(String, Vec<chrono::NaiveDateTime>), error_com::ConductorError> {
+    let (conditions, params) = generate_row_conditions(producer, filter)?;
+    let mut sql = format!("SELECT * FROM {}", quote_ident(&producer.uuid));
+    if !conditions.is_empty() {
+        sql = sql + " WHERE " + &conditions.join(" AND ");
+    }
+    if producer.time_series {
+        sql += if filter.newest_first { " ORDER BY ts DESC" } else { " ORDER BY ts" };
+    }
+    if let Some(limit) = filter.limit {
+        //a negative limit takes the last rows of a plain table
+        let sign = if filter.newest_first && !producer.time_series { "-" } else { "" };
+        sql += &format!(" LIMIT {}{}", sign, limit);
+    }
+    sql += ";";
+    Ok((sql, params))
+}
+
+///
+/// Generates the sql which reads one page of the rows `generate_select_sql` would read. The page
+/// holds at most `page_rows` rows starting after the first `offset` rows. The limit and `newest_first`
+/// of the filter are ignored as the caller decides how many pages to read.
+///
+/// # Errors
+/// * `ConductorError::InvalidData` : A timestamp of the filter is out of range or the producer isn't
+///   a time series and the filter has a timestamp
+///
+pub fn generate_page_sql(producer: &Producer, filter: &RowFilter, offset: u64, page_rows: u64) -> Result<(String, Vec<chrono::NaiveDateTime>), error_com::ConductorError> {
+    let (conditions, params) = generate_row_conditions(producer, filter)?;
+    let mut sql = format!("SELECT * FROM {}", quote_ident(&producer.uuid));
+    if !conditions.is_empty() {
+        sql = sql + " WHERE " + &conditions.join(" AND ");
+    }
+    if producer.time_series {
+        sql += " ORDER BY ts";
+    }
+    //QuestDB reads the rows from the first bound up to but not including the second
+    sql += &format!(" LIMIT {}, {};", offset, offset + page_rows);
+    Ok((sql, params))
+}
+
+async fn select_rows(db: &db::QuestDbConn, uuid: &str, filter: RowFilter, layout: producer_com::Layout) -> producer_com::QueryResult {
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+    };
+    let schema = match get_producer_schema(&producer) {
+        Ok(schema) => schema,
+        Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+    };
+    let (sql, params) = match generate_select_sql(&producer, &filter) {
+        Ok(select) => select,
+        Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+    };
+
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query(sql.as_str(), as_time_params(&params).as_slice())
+        })
+        .await;
+    let rows = match read_result {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Error reading rows for producer {}. {}", uuid, err));
+            log::error!("{}", error_code);
+            return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None };
+        }
+    };
+    if layout == producer_com::Layout::Columnar {
+        let mut columnar = producer_com::ColumnarRows::default();
+        for row in &rows {
+            if let Err(error_code) = push_row_to_columns(row, &schema, &mut columnar) {
+                return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None };
+            }
+        }
+        return producer_com::QueryResult {
+            error: error_com::ConductorError::NoError,
+            rows: Vec::new(),
+            columnar: Some(columnar),
+        };
+    }
+    let mut values = Vec::with_capacity(rows.len());
+    for row in &rows {
+        match row_to_map(row, &schema) {
+            Ok(row_values) => values.push(row_values),
+            Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+        }
+    }
+    producer_com::QueryResult {
+        error: error_com::ConductorError::NoError,
+        rows: values,
+        columnar: None,
+    }
+}
+
+///
+/// Reads the rows of a producer in the requested layout. Rows are returned unless the layout is `columnar`.
+///
+async fn query(db: &db::QuestDbConn, uuid: &str, filter: RowFilter, layout: Option<&str>) -> producer_com::QueryResult {
+    let layout = match layout.map(str::parse).transpose() {
+        Ok(layout) => layout.unwrap_or_default(),
+        Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+    };
+    select_rows(db, uuid, filter, layout).await
+}
+
+/// The query parameters of a rollup. `fn` is a keyword so the aggregate is renamed.
+#[derive(FromForm)]
+pub struct RollupParams<'r> {
+    uuid: &'r str,
+    column: &'r str,
+    #[field(name = "fn")]
+    aggregate: &'r str,
+    interval: &'r str,
+}
+
+///
+/// Generates the sql which aggregates a numeric column of a producer into time buckets using
+/// SAMPLE BY. The column and interval must have already been validated.
+///
+/// # Errors
+/// * `ConductorError::InvalidData` : The producer isn't a time series
+///
+pub fn generate_rollup_sql(producer: &Producer, column: &str, aggregate: producer_com::Aggregate, interval: &str) -> Result<String, error_com::ConductorError> {
+    if !producer.time_series {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::InvalidData(format!("Producer {} isn't a time series so it can't be rolled up", producer.uuid))
+        );
+    }
+    let filter = RowFilter { from: None, to: None, limit: None, newest_first: false, include_deleted: false };
+    let (conditions, _) = generate_row_conditions(producer, &filter)?;
+    let mut sql = format!("SELECT ts, cast({}({}) AS double) AS value FROM {}", aggregate.to_sql_str(), quote_ident(column), quote_ident(&producer.uuid));
+    if !conditions.is_empty() {
+        sql = sql + " WHERE " + &conditions.join(" AND ");
+    }
+    sql += &format!(" SAMPLE BY {} ALIGN TO CALENDAR;", interval);
+    Ok(sql)
+}
+
+async fn rollup(db: &db::QuestDbConn, params: &RollupParams<'_>) -> producer_com::RollupResult {
+    let aggregate: producer_com::Aggregate = match params.aggregate.parse() {
+        Ok(aggregate) => aggregate,
+        Err(error_code) => return producer_com::RollupResult { error: error_code, buckets: Vec::new() },
+    };
+    if let Err(error_code) = producer_com::validate_rollup_interval(params.interval) {
+        return producer_com::RollupResult { error: error_code, buckets: Vec::new() };
+    }
+    let producer = match get_producer_row(db, params.uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::RollupResult { error: error_code, buckets: Vec::new() },
+    };
+    let schema = match get_producer_schema(&producer) {
+        Ok(schema) => schema,
+        Err(error_code) => return producer_com::RollupResult { error: error_code, buckets: Vec::new() },
+    };
+    if let Err(error_code) = schema_com::validate_numeric_column(&schema, params.column) {
+        return producer_com::RollupResult { error: error_code, buckets: Vec::new() };
+    }
+    let sql = match generate_rollup_sql(&producer, params.column, aggregate, params.interval) {
+        Ok(sql) => sql,
+        Err(error_code) => return producer_com::RollupResult { error: error_code, buckets: Vec::new() },
+    };
+
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query(sql.as_str(), &[])
+        })
+        .await;
+    let rows = match read_result {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Error rolling up column {} for producer {}. {}", params.column, params.uuid, err));
+            log::error!("{}", error_code);
+            return producer_com::RollupResult { error: error_code, buckets: Vec::new() };
+        }
+    };
+    let mut buckets = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let bucket = row.try_get::<_, chrono::NaiveDateTime>("ts").and_then(|ts| {
+            let value: Option<f64> = row.try_get("value")?;
+            let ts = chrono::Utc.from_utc_datetime(&ts);
+            Ok(producer_com::RollupBucket {
+                ts: ts.timestamp() * 1_000_000 + i64::from(ts.timestamp_subsec_micros()),
+                //quest db uses NaN for buckets without a value which can't be sent as json
+                value: value.filter(|value| value.is_finite()),
+            })
+        });
+        match bucket {
+            Ok(bucket) => buckets.push(bucket),
+            Err(err) => {
+                let error_code = error_com::ConductorError::InternalError(format!("Couldn't read a rollup bucket from the database. {}", err));
+                log::error!("{}", error_code);
+                return producer_com::RollupResult { error: error_code, buckets: Vec::new() };
+            }
+        }
+    }
+    producer_com::RollupResult {
+        error: error_com::ConductorError::NoError,
+        buckets,
+    }
+}
+
+async fn columns(db: &db::QuestDbConn, uuid: &str) -> producer_com::ColumnsResult {
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::ColumnsResult { error: error_code, columns: Vec::new() },
+    };
+    let schema = match get_producer_schema(&producer) {
+        Ok(schema) => schema,
+        Err(error_code) => return producer_com::ColumnsResult { error: error_code, columns: Vec::new() },
+    };
+    let mut columns: Vec<String> = schema.into_keys().collect();
+    columns.sort();
+    if producer.time_series {
+        columns.insert(0, "ts".to_string());
+    }
+    producer_com::ColumnsResult {
+        error: error_com::ConductorError::NoError,
+        columns,
+    }
+}
+
+///
+/// Generates the sql which reads the oldest and newest timestamps of a producer and the number of
+/// rows between them along with the parameters referenced by the sql.
+///
+/// # Errors
+/// * `ConductorError::InvalidData` : The producer isn't a time series so its rows don't have timestamps
+///
+pub fn generate_time_range_sql(producer: &Producer) -> Result<(String, Vec<chrono::NaiveDateTime>), error_com::ConductorError> {
+    if !producer.time_series {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::InvalidData(format!("Producer {} isn't a time series so its rows don't have a time range", producer.uuid))
+        );
+    }
+    let (conditions, params) = generate_row_conditions(producer, &RowFilter::default())?;
+    let mut sql = format!("SELECT min(ts) AS min_ts, max(ts) AS max_ts, count() AS count FROM {}", quote_ident(&producer.uuid));
+    if !conditions.is_empty() {
+        sql = sql + " WHERE " + &conditions.join(" AND ");
+    }
+    sql += ";";
+    Ok((sql, params))
+}
+
+///
+/// Reads the oldest and newest timestamps of a producer and the number of rows between them with a
+/// single aggregate query. Soft deleted rows aren't included.
+///
+async fn time_range(db: &db::QuestDbConn, uuid: &str) -> producer_com::TimeRangeResult {
+    let empty = |error| producer_com::TimeRangeResult { error, min_ts: None, max_ts: None, count: 0 };
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return empty(error_code),
+    };
+    let (sql, params) = match generate_time_range_sql(&producer) {
+        Ok(range) => range,
+        Err(error_code) => return empty(error_code),
+    };
+
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query_one(sql.as_str(), as_time_params(&params).as_slice())
+        })
+        .await;
+    let range = read_result.and_then(|row| {
+        let min_ts: Option<chrono::NaiveDateTime> = row.try_get("min_ts")?;
+        let max_ts: Option<chrono::NaiveDateTime> = row.try_get("max_ts")?;
+        let count: i64 = row.try_get("count")?;
+        Ok((min_ts, max_ts, count))
+    });
+    match range {
+        Ok((min_ts, max_ts, count)) => producer_com::TimeRangeResult {
+            error: error_com::ConductorError::NoError,
+            min_ts: min_ts.and_then(naive_date_time_to_micros).map(|micros| producer.timestamp_precision().from_micros(micros)),
+            max_ts: max_ts.and_then(naive_date_time_to_micros).map(|micros| producer.timestamp_precision().from_micros(micros)),
+            count: u64::try_from(count).unwrap_or_default(),
+        },
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Error reading the time range of producer {}. {}", uuid, err));
+            log::error!("{}", error_code);
+            empty(error_code)
+        }
+    }
+}
+
+///
+/// Rebuilds the schema of a producer whose registration is missing from its data table. When
+/// `reregister` is set the producer is registered again from the table the same way `adopt` does.
+/// Producers without a table stay unregistered.
+///
+async fn recovered_schema(db: &db::QuestDbConn, uuid: &str, reason: String, reregister: bool) -> producer_com::SchemaResult {
+    let empty = |error| producer_com::SchemaResult { error, schema: HashMap::new(), id_source: None, metadata: None, recovered: false };
+    if producer_com::validate_uuid(uuid).is_err() {
+        return empty(error_com::ConductorError::Unregistered(reason));
+    }
+    let schema = match read_table_schema(db, uuid).await {
+        Ok(schema) => schema,
+        Err(error_com::ConductorError::Unregistered(_)) => return empty(error_com::ConductorError::Unregistered(reason)),
+        Err(error_code) => return empty(error_code),
+    };
+    log::warn!("Producer {} isn't registered but its table exists. Its schema was read from the table.", uuid);
+    if reregister {
+        // the uuid is given as the custom id so the uuid strategy isn't used
+        let registration = producer_com::Registration::new(uuid.to_string(), schema.clone(), Some(uuid.to_string()));
+        let result = register(db, UuidStrategy::default(), &registration).await;
+        if result.error != error_com::ConductorError::NoError {
+            return empty(result.error);
+        }
+        log::info!("Producer {} was registered again from its table", uuid);
+    }
+    let schema = schema.into_iter()
+        .map(|(column, data_type)| (column, schema_com::SchemaEntry { data_type, required: false }))
+        .collect();
+    producer_com::SchemaResult { error: error_com::ConductorError::NoError, schema, id_source: None, metadata: None, recovered: true }
+}
+
+///
+/// Reads the registered schema of a producer. When the registration is missing but the data table
+/// exists the schema is recovered from the table, see `recovered_schema`.
+///
+async fn registered_schema(db: &db::QuestDbConn, uuid: &str, recover: bool) -> producer_com::SchemaResult {
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_com::ConductorError::Unregistered(reason)) => return recovered_schema(db, uuid, reason, recover).await,
+        Err(error_code) => return producer_com::SchemaResult { error: error_code, schema: HashMap::new(), id_source: None, metadata: None, recovered: false },
+    };
+    let columns = match get_producer_columns(&producer) {
+        Ok(columns) => columns,
+        Err(error_code) => return producer_com::SchemaResult { error: error_code, schema: HashMap::new(), id_source: None, metadata: None, recovered: false },
+    };
+    let metadata = match get_producer_metadata(&producer) {
+        Ok(metadata) => metadata,
+        Err(error_code) => return producer_com::SchemaResult { error: error_code, schema: HashMap::new(), id_source: None, metadata: None, recovered: false },
+    };
+    let ProducerColumns { schema, required, .. } = columns;
+    let schema = schema.into_iter().map(|(column, data_type)| {
+        let required = required.contains(&column);
+        (column, schema_com::SchemaEntry { data_type, required })
+    }).collect();
+    producer_com::SchemaResult {
+        error: error_com::ConductorError::NoError,
+        schema,
+        id_source: producer_com::IdSource::from_db_str(&producer.id_source),
+        metadata: Some(metadata),
+        recovered: false,
+    }
+}
+
+///
+/// Changes the name and column metadata of a producer with a single update of its registration so
+/// that either both or neither are changed.
+///
+async fn update_producer(db: &db::QuestDbConn, uuid: &str, update: producer_com::ProducerUpdate) -> producer_com::ProducerUpdateResult {
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::ProducerUpdateResult { error: error_code, metadata: None },
+    };
+    let (columns, mut metadata) = match (get_producer_columns(&producer), get_producer_metadata(&producer)) {
+        (Ok(columns), Ok(metadata)) => (columns, metadata),
+        (Err(error_code), _) | (_, Err(error_code)) => return producer_com::ProducerUpdateResult { error: error_code, metadata: None },
+    };
+    if let Err(error_code) = update.validate(&columns.schema) {
+        log::error!("{}", error_code);
+        return producer_com::ProducerUpdateResult { error: error_code, metadata: None };
+    }
+    update.apply(&mut metadata);
+    let column_metadata = match serde_json::to_string(&metadata.column_metadata) {
+        Ok(column_metadata) => column_metadata,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't serialize the column metadata for uuid: {} with error: {}", producer.uuid, err));
+            log::error!("{}", error_code);
+            return producer_com::ProducerUpdateResult { error: error_code, metadata: None };
+        }
+    };
+    let name = metadata.name.clone();
+    let uuid = producer.uuid.clone();
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute("UPDATE producers SET name = $1, column_metadata = $2 WHERE uuid = $3;", &[&name, &column_metadata, &uuid])
+        })
+        .await;
+    match write_result {
+        Ok(_) => producer_com::ProducerUpdateResult { error: error_com::ConductorError::NoError, metadata: Some(metadata) },
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't update producer {}. {}", producer.uuid, err));
+            log::error!("{}", error_code);
+            producer_com::ProducerUpdateResult { error: error_code, metadata: None }
+        }
+    }
+}
+
+/// Serialises schema swaps so that the compare and the swap can't be interleaved with another swap.
+pub struct SchemaSwaps(tokio::sync::Mutex<()>);
+
+///
+/// Swaps the schema of a producer for a new one if the current schema has the expected hash. The
+/// table is migrated first and the registration is only updated once every column was added and
+/// dropped. The hash is compared against the stored schema rather than the stored hash as producers
+/// registered before hashes were recorded don't have one.
+///
+async fn swap_schema(db: &db::QuestDbConn, swaps: &SchemaSwaps, uuid: &str, swap: &producer_com::SchemaSwap) -> producer_com::SchemaSwapResult {
+    let failed = |error_code: error_com::ConductorError, schema_hash: Option<String>| {
+        log::error!("{}", error_code);
+        producer_com::SchemaSwapResult { error: error_code, schema_hash }
+    };
+    let _swapping = swaps.0.lock().await;
+    let producer = match get_producer_row(db, uuid).await {
+        Ok(producer) => producer,
+        Err(error_code) => return failed(error_code, None),
+    };
+    let current = match get_producer_schema(&producer) {
+        Ok(schema) => schema,
+        Err(error_code) => return failed(error_code, None),
+    };
+    let current_hash = schema_com::schema_hash(&current);
+    if current_hash != swap.get_expected_schema_hash() {
+        let differences = schema_com::schema_differences(&current, swap.get_new_schema());
+        return failed(error_com::ConductorError::SchemaConflict(differences), Some(current_hash));
+    }
+    // the rest of the registration has to hold for the new schema, such as required columns still being part of it
+    let registration = match registration_with_schema(&producer, swap.get_new_schema().clone()) {
+        Ok(registration) => registration,
+        Err(error_code) => return failed(error_code, Some(current_hash)),
+    };
+    if let Err(error_code) = registration.validate() {
+        return failed(error_code, Some(current_hash));
+    }
+    let migration = match schema_com::plan_migration(&current, swap.get_new_schema()) {
+        Ok(migration) => migration,
+        Err(error_code) => return failed(error_code, Some(current_hash)),
+    };
+    let statements = generate_migration_sql(&producer.uuid, &migration);
+    let schema_json = serde_json::to_string_pretty(registration.get_schema()).unwrap_or_default();
+    let new_hash = schema_com::schema_hash(registration.get_schema());
+    let stored_hash = new_hash.clone();
+    let uuid = producer.uuid.clone();
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            for statement in &statements {
+                log::info!("migrating table with sql {}", statement);
+                conn.execute(statement.as_str(), &[])?;
+            }
+            conn.execute("UPDATE producers SET schema = $1, schema_hash = $2 WHERE uuid = $3;", &[&schema_json, &stored_hash, &uuid])
+        })
+        .await;
+    match write_result {
+        Ok(_) => {
+            log::info!("Swapped the schema of producer {} from {} to {}", producer.uuid, current_hash, new_hash);
+            producer_com::SchemaSwapResult { error: error_com::ConductorError::NoError, schema_hash: Some(new_hash) }
+        }
+        Err(err) => failed(
+            error_com::ConductorError::InternalError(format!("Couldn't migrate the schema of producer {}. {}", producer.uuid, err)),
+            None,
+        ),
+    }
+}
+
+///
+/// Generates the sql which marks the rows of a producer in the time range of the filter as deleted
+/// along with the timestamp parameters referenced by the sql. The rows stay in the table.
+///
+/// # Errors
+/// * `ConductorError::InvalidData` : A timestamp of the filter is out of range
+///
+pub fn generate_soft_delete_sql(producer: &Producer, tombstone_column: &str, filter: &RowFilter) -> Result<(String, Vec<chrono::NaiveDateTime>), error_com::ConductorError> {
+    let filter = RowFilter { include_deleted: true, ..*filter };
+    let (conditions, params) = generate_row_conditions(producer, &filter)?;
+    let mut sql = format!("UPDATE {} SET {} = true", quote_ident(&producer.uuid), quote_ident(tombstone_column));
+    if !conditions.is_empty() {
+        sql = sql + " WHERE " + &conditions.join(" AND ");
+    }
+    sql += ";";
+    Ok((sql, params))
+}
+
+async fn soft_delete(db: &db::QuestDbConn, request: &producer_com::SoftDelete<'_>) -> producer_com::SoftDeleteResult {
+    let producer = match get_producer_row(db, request.get_uuid()).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::SoftDeleteResult { error: error_code, deleted: 0 },
+    };
+    let tombstone_column = match &producer.tombstone_column {
+        Some(tombstone_column) => tombstone_column.clone(),
+        None => {
+            let error_code = error_com::ConductorError::InvalidSchema(format!("Producer {} wasn't registered with soft deletes enabled", producer.uuid));
+            log::error!("{}", error_code);
+            return producer_com::SoftDeleteResult { error: error_code, deleted: 0 };
+        }
+    };
+    let filter = RowFilter { from: request.get_from(), to: request.get_to(), ..RowFilter::default() };
+    let (sql, params) = match generate_soft_delete_sql(&producer, &tombstone_column, &filter) {
+        Ok(delete) => delete,
+        Err(error_code) => return producer_com::SoftDeleteResult { error: error_code, deleted: 0 },
+    };
+
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute(sql.as_str(), as_time_params(&params).as_slice())
+        })
+        .await;
+    match write_result {
+        Ok(deleted) => producer_com::SoftDeleteResult {
+            error: error_com::ConductorError::NoError,
+            deleted,
+        },
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Error soft deleting rows for producer {}. {}", producer.uuid, err));
+            log::error!("{}", error_code);
+            producer_com::SoftDeleteResult { error: error_code, deleted: 0 }
+        }
+    }
+}
+
+///
+/// Removes all of the data stored for a producer while leaving it registered.
+///
+async fn truncate(db: &db::QuestDbConn, request: &producer_com::Truncate<'_>) -> producer_com::TruncateResult {
+    let producer = match get_producer_row(db, request.get_uuid()).await {
+        Ok(producer) => producer,
+        Err(error_code) => return producer_com::TruncateResult { error: error_code },
+    };
+    let sql = format!("TRUNCATE TABLE {};", quote_ident(&producer.uuid));
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute(sql.as_str(), &[])
+        })
+        .await;
+    match write_result {
+        Ok(_) => producer_com::TruncateResult {
+            error: error_com::ConductorError::NoError,
+        },
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Error truncating table for producer {}. {}", producer.uuid, err));
+            log::error!("{}", error_code);
+            producer_com::TruncateResult { error: error_code }
+        }
+    }
+}
+
+///
+/// Checks which of several uuids are registered with one read of the producers table. Malformed
+/// uuids are reported as not registered like they are by `check`.
+///
+async fn check_batch(db: &db::QuestDbConn, uuids: &[String]) -> producer_com::CheckBatchResult {
+    let rows = match db.run(|conn: &mut postgres::Client| conn.query("SELECT uuid FROM producers WHERE deleted = false;", &[])).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't read the producers from the database. {}", err));
+            log::error!("{}", error_code);
+            return producer_com::CheckBatchResult { error: error_code, registered: Vec::new() };
+        }
+    };
+    let known: HashSet<String> = rows.iter()
+        .filter_map(|row| row.try_get::<_, String>("uuid").ok())
+        .collect();
+    producer_com::CheckBatchResult {
+        error: error_com::ConductorError::NoError,
+        registered: uuids.iter().map(|uuid| producer_com::validate_uuid(uuid).is_ok() && known.contains(uuid)).collect(),
+    }
+}
+
+///
+/// Generates the sql which reads the rows of several producers with one `UNION ALL` along with the
+/// parameters referenced by the sql. Each producer's uuid is bound as the parameter which tags its rows.
+/// Only the columns of the shared schema are selected so that extra columns such as tombstones don't
+/// break the union. Time series are ordered by `ts` and plain tables are read in the order they were
+/// inserted. The uuids must already have been validated as they're also used as table names.
+///
+/// # Errors
+/// * `ConductorError::InvalidData` : Some of the producers are time series and some aren't
+///
+pub fn generate_union_sql(producers: &[Producer], schema: &schema_com::Schema, limit: Option<u64>) -> Result<(String, Vec<String>), error_com::ConductorError> {
+    let time_series = producers.iter().all(|producer| producer.time_series);
+    if !time_series && producers.iter().any(|producer| producer.time_series) {
+        return log_error_and_get_emit_result!(
+            error_com::ConductorError::InvalidData("Time series and plain tables can't be queried together".to_string())
+        );
+    }
+    let mut columns: Vec<&String> = schema.keys().collect();
+    columns.sort();
+    let mut selected = if time_series { vec![String::from("ts")] } else { Vec::new() };
+    selected.extend(columns.into_iter().map(|column| quote_ident(column)));
+    let filter = RowFilter { from: None, to: None, limit: None, newest_first: false, include_deleted: false };
+    let mut selects = Vec::with_capacity(producers.len());
+    let mut params = Vec::with_capacity(producers.len());
+    for producer in producers {
+        //the filter has no timestamps so the conditions don't reference any parameters
+        let (conditions, _) = generate_row_conditions(producer, &filter)?;
+        params.push(producer.uuid.clone());
+        let mut select = format!("SELECT cast(${} AS string) AS {}, {} FROM {}", params.len(), producer_com::SOURCE_UUID_COLUMN, selected.join(", "), quote_ident(&producer.uuid));
+        if !conditions.is_empty() {
+            select = select + " WHERE " + &conditions.join(" AND ");
+        }
+        selects.push(select);
+    }
+    let mut sql = format!("SELECT * FROM ({})", selects.join(" UNION ALL "));
+    if time_series {
+        sql += " ORDER BY ts";
+    }
+    if let Some(limit) = limit {
+        sql += &format!(" LIMIT {}", limit);
+    }
+    sql += ";";
+    Ok((sql, params))
+}
+
+///
+/// Reads the rows of several producers together. The schemas of the producers are compared with
+/// `schema_differences` first and the query is rejected unless they're all the same. Each row is tagged
+/// with the uuid of the producer it came from.
+///
+async fn query_union(db: &db::QuestDbConn, request: &producer_com::QueryUnion) -> producer_com::QueryResult {
+    let failed = |error_code: error_com::ConductorError| {
+        log::error!("{}", error_code);
+        producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None }
+    };
+    if request.get_uuids().is_empty() {
+        return failed(error_com::ConductorError::InvalidUuid("A union query needs at least one producer".to_string()));
+    }
+    let mut producers = Vec::with_capacity(request.get_uuids().len());
+    for uuid in request.get_uuids() {
+        if let Err(error_code) = producer_com::validate_uuid(uuid) {
+            return failed(error_code);
+        }
+        match get_producer_row(db, uuid).await {
+            Ok(producer) => producers.push(producer),
+            Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+        }
+    }
+    let schema = match get_producer_schema(&producers[0]) {
+        Ok(schema) => schema,
+        Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+    };
+    if schema.contains_key(producer_com::SOURCE_UUID_COLUMN) {
+        return failed(error_com::ConductorError::InvalidColumnNames(format!("The producers can't be queried together as they have a column called {}", producer_com::SOURCE_UUID_COLUMN)));
+    }
+    for producer in &producers[1..] {
+        let other = match get_producer_schema(producer) {
+            Ok(schema) => schema,
+            Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+        };
+        let differences = schema_com::schema_differences(&schema, &other);
+        if !differences.is_empty() {
+            return failed(error_com::ConductorError::SchemaConflict(differences));
+        }
+    }
+    let (sql, params) = match generate_union_sql(&producers, &schema, request.get_limit()) {
+        Ok(generated) => generated,
+        Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+    };
+
+    let rows = match db.run(move |conn: &mut postgres::Client| {
+        let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+        conn.query(sql.as_str(), &params)
+    }).await {
+        Ok(rows) => rows,
+        Err(err) => return failed(error_com::ConductorError::InternalError(format!("Error reading the union of producers {}. {}", request.get_uuids().join(", "), err))),
+    };
+    let mut values = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let mut row_values = match row_to_map(row, &schema) {
+            Ok(row_values) => row_values,
+            Err(error_code) => return producer_com::QueryResult { error: error_code, rows: Vec::new(), columnar: None },
+        };
+        let source: String = row.try_get(producer_com::SOURCE_UUID_COLUMN).unwrap_or_default();
+        row_values.insert(producer_com::SOURCE_UUID_COLUMN.to_string(), serde_json::Value::String(source));
+        values.push(row_values);
+    }
+    producer_com::QueryResult {
+        error: error_com::ConductorError::NoError,
+        rows: values,
+        columnar: None,
+    }
+}
+
+///
+/// Picks the producers a batch deletes from the uuid and name of every registered producer. The
+/// uuids the batch names which aren't registered are returned as failed outcomes.
+///
+#[must_use]
+pub fn plan_delete_batch(request: &producer_com::DeleteBatch, registered: &[(String, String)]) -> (Vec<String>, Vec<producer_com::DeleteOutcome>) {
+    let mut selected: Vec<String> = Vec::new();
+    for (uuid, name) in registered {
+        if request.selects(uuid, name) && !selected.contains(uuid) {
+            selected.push(uuid.clone());
+        }
+    }
+    let unregistered = request.get_uuids().iter()
+        .filter(|uuid| !selected.contains(uuid))
+        .map(|uuid| producer_com::DeleteOutcome {
+            uuid: uuid.clone(),
+            error: error_com::ConductorError::Unregistered(format!("No producer is registered with uuid {}", uuid)),
+        })
+        .collect();
+    (selected, unregistered)
+}
+
+///
+/// Generates the statements which delete a producer. QuestDB can't delete rows so its row in the
+/// producers table is marked as deleted once its table is dropped. The uuid must already have been
+/// validated as it's used as the table name. The second statement takes the uuid as its only parameter.
+///
+#[must_use]
+pub fn generate_delete_producer_sql(uuid: &str) -> [String; 2] {
+    [
+        format!("DROP TABLE {};", quote_ident(uuid)),
+        String::from("UPDATE producers SET deleted = true WHERE uuid = $1;"),
+    ]
+}
+
+///
+/// Deletes every producer selected by the batch. Every uuid is validated before anything is dropped.
+/// Each producer is deleted on its own so a failure only affects that producer and nothing else in
+/// the producers table is rewritten.
+///
+async fn delete_batch(db: &db::QuestDbConn, request: &producer_com::DeleteBatch) -> producer_com::DeleteBatchResult {
+    let invalid: Vec<producer_com::DeleteOutcome> = request.get_uuids().iter()
+        .filter_map(|uuid| producer_com::validate_uuid(uuid).err().map(|error| producer_com::DeleteOutcome { uuid: uuid.clone(), error }))
+        .collect();
+    if !invalid.is_empty() {
+        let error_code = error_com::ConductorError::InvalidUuid(format!("{} of the uuids are malformed. Nothing was deleted.", invalid.len()));
+        log::error!("{}", error_code);
+        return producer_com::DeleteBatchResult { error: error_code, results: invalid };
+    }
+
+    let registered = match db.run(|conn: &mut postgres::Client| conn.query("SELECT uuid, name FROM producers WHERE deleted = false;", &[])).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            let error_code = error_com::ConductorError::InternalError(format!("Couldn't read the producers from the database. {}", err));
+            log::error!("{}", error_code);
+            return producer_com::DeleteBatchResult { error: error_code, results: Vec::new() };
+        }
+    };
+    let registered: Vec<(String, String)> = registered.iter()
+        .map(|row| (row.try_get("uuid").unwrap_or_default(), row.try_get("name").unwrap_or_default()))
+        .collect();
+    let (selected, mut results) = plan_delete_batch(request, &registered);
+
+    for uuid in selected {
+        //names come from the database so they're validated again before being used in sql
+        if let Err(error) = producer_com::validate_uuid(&uuid) {
+            results.push(producer_com::DeleteOutcome { uuid, error });
+            continue;
+        }
+        let [drop_sql, mark_sql] = generate_delete_producer_sql(&uuid);
+        let dropped = db.run(move |conn: &mut postgres::Client| conn.execute(drop_sql.as_str(), &[])).await;
+        let uuid_copy = uuid.clone();
+        let error = match dropped {
+            Ok(_) => match db.run(move |conn: &mut postgres::Client| conn.execute(mark_sql.as_str(), &[&uuid_copy])).await {
+                Ok(_) => error_com::ConductorError::NoError,
+                Err(err) => error_com::ConductorError::InternalError(format!("The table of producer {} was dropped but it couldn't be removed from the producers table. {}", uuid, err)),
+            },
+            Err(err) => error_com::ConductorError::InternalError(format!("Couldn't drop the table for producer {}. {}", uuid, err)),
+        };
+        if error != error_com::ConductorError::NoError {
+            log::error!("{}", error);
+        }
+        results.push(producer_com::DeleteOutcome { uuid, error });
+    }
+    producer_com::DeleteBatchResult { error: error_com::ConductorError::NoError, results }
+}
+
+///
+/// Deletes every producer with a ttl which hasn't emitted within it. Producers which never emitted
+/// expire relative to when they were registered. Returns the uuids of the producers which were deleted.
+///
+pub(crate) async fn sweep_expired(db: &db::QuestDbConn) -> Vec<String> {
+    let rows = match db.run(|conn: &mut postgres::Client| conn.query("SELECT * FROM producers WHERE ttl != null AND deleted = false;", &[])).await {
+        Ok(rows) => rows,
+        Err(err) => {
+            log::error!("Couldn't read the producers with a ttl from the database. {}", err);
+            return Vec::new();
+        }
+    };
+    let now = quota::now_micros();
+    let mut expired = Vec::new();
+    for producer in rows.iter().map(producer_from_row) {
+        let ttl = match producer.ttl_seconds() {
+            Some(ttl) => ttl,
+            None => continue,
+        };
+        //names come from the database so they're validated again before being used in sql
+        if producer_com::validate_uuid(&producer.uuid).is_err() {
+            continue;
+        }
+        //the last emit is read from ts so plain tables can't expire. Registrations no longer accept a ttl without a time series
+        if !producer.time_series {
+            log::warn!("Producer {} has a ttl but isn't a time series so it can't be swept", producer.uuid);
+            continue;
+        }
+        let registered_at = producer.registered_at.and_then(|registered_at| u64::try_from(registered_at).ok()).unwrap_or(now);
+        let sql = format!("SELECT max(ts) AS max_ts FROM {};", quote_ident(&producer.uuid));
+        let last_emit = match db.run(move |conn: &mut postgres::Client| conn.query_one(sql.as_str(), &[])).await {
+            Ok(row) => row.try_get::<_, Option<chrono::NaiveDateTime>>("max_ts").ok().flatten().and_then(naive_date_time_to_micros),
+            Err(err) => {
+                log::warn!("Couldn't read the latest emit of producer {} so it wasn't swept. {}", producer.uuid, err);
+                continue;
+            }
+        };
+        if sweeper::is_expired(ttl, registered_at, last_emit, now) {
+            expired.push(producer.uuid);
+        }
+    }
+    if expired.is_empty() {
+        return expired;
+    }
+    let result = delete_batch(db, &producer_com::DeleteBatch::new(expired)).await;
+    result.results.into_iter().filter_map(|outcome| {
+        if outcome.error == error_com::ConductorError::NoError {
+            Some(outcome.uuid)
+        } else {
+            log::error!("Couldn't sweep expired producer {}. {}", outcome.uuid, outcome.error);
+            None
+        }
+    }).collect()
+}
+
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/register", format = "msgpack", data = "<data>")]
+pub async fn register_pack(
+    conn: db::QuestDbConn,
+    uuids: &State<UuidStrategy>,
+    limits: &Limits,
+    data: Data<'_>,
+) -> trace::Traced<(Status, Negotiated<producer_com::RegistrationResult>)> {
+    let data: producer_com::Registration = match format::read_msgpack(data, limits).await.and_then(|bytes| format::decode_msgpack(&bytes)) {
+        Ok(data) => data,
+        Err((status, error)) => return trace::Traced::new("", (status, Negotiated(producer_com::RegistrationResult { error, uuid: None }))),
+    };
+    let result = register(&conn, **uuids, &data).await;
+    trace::Traced::new(registration_trace_name(&data), (registration_status(&result), Negotiated(result)))
+}
+
+#[post("/v1/producer/register", format = "json", data = "<data>")]
+pub async fn register_json(
+    conn: db::QuestDbConn,
+    uuids: &State<UuidStrategy>,
+    data: Json<producer_com::Registration>,
+) -> trace::Traced<(Status, Negotiated<producer_com::RegistrationResult>)> {
+    let result = register(&conn, **uuids, &data).await;
+    trace::Traced::new(registration_trace_name(&data), (registration_status(&result), Negotiated(result)))
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/register_batch", format = "msgpack", data = "<data>")]
+pub async fn register_batch_pack(conn: db::QuestDbConn, uuids: &State<UuidStrategy>, limits: &Limits, data: Data<'_>) -> (Status, Negotiated<producer_com::RegisterBatchResult>) {
+    let data: Vec<producer_com::Registration> = match format::read_msgpack(data, limits).await.and_then(|bytes| format::decode_msgpack(&bytes)) {
+        Ok(data) => data,
+        Err((status, error)) => return (status, Negotiated(producer_com::RegisterBatchResult { error, results: Vec::new() })),
+    };
+    (Status::Ok, Negotiated(register_batch(&conn, **uuids, &data).await))
+}
+
+#[post("/v1/producer/register_batch", format = "json", data = "<data>")]
+pub async fn register_batch_json(conn: db::QuestDbConn, uuids: &State<UuidStrategy>, data: Json<Vec<producer_com::Registration>>) -> Negotiated<producer_com::RegisterBatchResult> {
+    Negotiated(register_batch(&conn, **uuids, &data).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/export?<offset>&<limit>", format = "msgpack")]
+pub async fn export_pack(conn: db::QuestDbConn, offset: Option<u64>, limit: Option<u32>) -> MsgPack<producer_com::ExportResult> {
+    MsgPack(export(&conn, offset, limit).await)
+}
+
+#[get("/v1/producer/export?<offset>&<limit>", format = "json")]
+pub async fn export_json(conn: db::QuestDbConn, offset: Option<u64>, limit: Option<u32>) -> Json<producer_com::ExportResult> {
+    Json(export(&conn, offset, limit).await)
+}
+
+#[cfg(feature = "msgpack")]
 #[post("/v1/producer/emit", format = "msgpack", data = "<data>")]
-pub async fn emit_pack(conn: db::QuestDbConn, data: MsgPack<producer_com::Emit<'_, HashMap<String,serde_json::Value>>>) -> MsgPack<producer_com::EmitResult> {
-    MsgPack(emit(&conn, &data).await)
+pub async fn emit_pack(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, uuids: &State<UuidStrategy>, idempotent: &State<IdempotentEmits>, key: IdempotencyKey<'_>, timer: metrics::EmitTimer<'_>, limits: &Limits, data: Data<'_>) -> trace::Traced<(Status, Negotiated<producer_com::EmitResult>)> {
+    let bytes = match format::read_msgpack(data, limits).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return trace::Traced::new("", rejected_emit(rejection)),
+    };
+    let data: producer_com::Emit<'_, HashMap<String,serde_json::Value>> = match format::decode_msgpack(&bytes) {
+        Ok(data) => data,
+        Err(rejection) => return trace::Traced::new("", rejected_emit(rejection)),
+    };
+    let result = timer.time(idempotent.run(data.get_uuid(), &key, emit(&conn, quotas, **missing_tables, **uuids, &data, &[]))).await;
+    trace::Traced::new(data.get_uuid(), (emit_status(&result), Negotiated(result)))
 }
 
 #[post("/v1/producer/emit", format = "json", data = "<data>")]
-pub async fn emit_json(conn: db::QuestDbConn, data: Json<producer_com::Emit<'_, HashMap<String,serde_json::Value>>>) -> Json<producer_com::EmitResult> {
-    Json(emit(&conn, &data).await)
+pub async fn emit_json(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, uuids: &State<UuidStrategy>, idempotent: &State<IdempotentEmits>, key: IdempotencyKey<'_>, timer: metrics::EmitTimer<'_>, data: Json<producer_com::Emit<'_, HashMap<String,serde_json::Value>>>) -> trace::Traced<(Status, Negotiated<producer_com::EmitResult>)> {
+    let result = timer.time(idempotent.run(data.get_uuid(), &key, emit(&conn, quotas, **missing_tables, **uuids, &data, &[]))).await;
+    trace::Traced::new(data.get_uuid(), (emit_status(&result), Negotiated(result)))
+}
+
+///
+/// Splits an ordered emit into a keyed emit and its column order then stores it like any other emit.
+///
+async fn emit_ordered(db: &db::QuestDbConn, quotas: &quota::Quotas, missing_tables: producer_com::MissingTablePolicy, uuids: UuidStrategy, data: producer_com::Emit<'_, producer_com::OrderedData>) -> producer_com::EmitResult {
+    match data.into_keyed() {
+        Ok((keyed, order)) => emit(db, quotas, missing_tables, uuids, &keyed, &order).await,
+        Err(error_code) => {
+            log::error!("{}", error_code);
+            producer_com::EmitResult { error: error_code, assigned_timestamp: None, registration: None }
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/emit_ordered", format = "msgpack", data = "<data>")]
+pub async fn emit_ordered_pack(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, uuids: &State<UuidStrategy>, idempotent: &State<IdempotentEmits>, key: IdempotencyKey<'_>, timer: metrics::EmitTimer<'_>, limits: &Limits, data: Data<'_>) -> trace::Traced<(Status, Negotiated<producer_com::EmitResult>)> {
+    let bytes = match format::read_msgpack(data, limits).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return trace::Traced::new("", rejected_emit(rejection)),
+    };
+    let data: producer_com::Emit<'_, producer_com::OrderedData> = match format::decode_msgpack(&bytes) {
+        Ok(data) => data,
+        Err(rejection) => return trace::Traced::new("", rejected_emit(rejection)),
+    };
+    let uuid = data.get_uuid().to_string();
+    let result = timer.time(idempotent.run(&uuid, &key, emit_ordered(&conn, quotas, **missing_tables, **uuids, data))).await;
+    trace::Traced::new(&uuid, (emit_status(&result), Negotiated(result)))
+}
+
+#[post("/v1/producer/emit_ordered", format = "json", data = "<data>")]
+pub async fn emit_ordered_json(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, uuids: &State<UuidStrategy>, idempotent: &State<IdempotentEmits>, key: IdempotencyKey<'_>, timer: metrics::EmitTimer<'_>, data: Json<producer_com::Emit<'_, producer_com::OrderedData>>) -> trace::Traced<(Status, Negotiated<producer_com::EmitResult>)> {
+    let data = data.into_inner();
+    let uuid = data.get_uuid().to_string();
+    let result = timer.time(idempotent.run(&uuid, &key, emit_ordered(&conn, quotas, **missing_tables, **uuids, data))).await;
+    trace::Traced::new(&uuid, (emit_status(&result), Negotiated(result)))
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/explain_emit", format = "msgpack", data = "<data>")]
+pub async fn explain_emit_pack(conn: db::QuestDbConn, limits: &Limits, data: Data<'_>) -> (Status, Negotiated<producer_com::ExplainEmitResult>) {
+    let bytes = match format::read_msgpack(data, limits).await {
+        Ok(bytes) => bytes,
+        Err((status, error)) => return (status, Negotiated(producer_com::ExplainEmitResult { error, sql: String::new(), columns: schema_com::Schema::new(), fields: Vec::new() })),
+    };
+    let data: producer_com::Emit<'_, HashMap<String,serde_json::Value>> = match format::decode_msgpack(&bytes) {
+        Ok(data) => data,
+        Err((status, error)) => return (status, Negotiated(producer_com::ExplainEmitResult { error, sql: String::new(), columns: schema_com::Schema::new(), fields: Vec::new() })),
+    };
+    (Status::Ok, Negotiated(explain_emit(&conn, &data).await))
+}
+
+#[post("/v1/producer/explain_emit", format = "json", data = "<data>")]
+pub async fn explain_emit_json(conn: db::QuestDbConn, data: Json<producer_com::Emit<'_, HashMap<String,serde_json::Value>>>) -> (Status, Negotiated<producer_com::ExplainEmitResult>) {
+    (Status::Ok, Negotiated(explain_emit(&conn, &data).await))
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/emit_batch", format = "msgpack", data = "<data>")]
+pub async fn emit_batch_pack(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, max_rows: &State<MaxBatchRows>, idempotent: &State<IdempotentEmits>, key: IdempotencyKey<'_>, timer: metrics::EmitTimer<'_>, limits: &Limits, data: Data<'_>) -> (Status, Negotiated<producer_com::EmitResult>) {
+    let bytes = match format::read_msgpack(data, limits).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return rejected_emit(rejection),
+    };
+    let data: producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>> = match format::decode_msgpack(&bytes) {
+        Ok(data) => data,
+        Err(rejection) => return rejected_emit(rejection),
+    };
+    let result = timer.time(idempotent.run(data.get_uuid(), &key, emit_batch(&conn, quotas, **missing_tables, **max_rows, &data))).await;
+    (emit_status(&result), Negotiated(result))
+}
+
+#[post("/v1/producer/emit_batch", format = "json", data = "<data>")]
+pub async fn emit_batch_json(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, max_rows: &State<MaxBatchRows>, idempotent: &State<IdempotentEmits>, key: IdempotencyKey<'_>, timer: metrics::EmitTimer<'_>, data: Json<producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>>) -> (Status, Negotiated<producer_com::EmitResult>) {
+    let result = timer.time(idempotent.run(data.get_uuid(), &key, emit_batch(&conn, quotas, **missing_tables, **max_rows, &data))).await;
+    (emit_status(&result), Negotiated(result))
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/emit_transaction", format = "msgpack", data = "<data>")]
+pub async fn emit_transaction_pack(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, max_rows: &State<MaxBatchRows>, timer: metrics::EmitTimer<'_>, limits: &Limits, data: Data<'_>) -> (Status, Negotiated<producer_com::EmitResult>) {
+    let bytes = match format::read_msgpack(data, limits).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return rejected_emit(rejection),
+    };
+    let data: Vec<producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>> = match format::decode_msgpack(&bytes) {
+        Ok(data) => data,
+        Err(rejection) => return rejected_emit(rejection),
+    };
+    let result = timer.time(emit_transaction(&conn, quotas, **missing_tables, **max_rows, &data)).await;
+    (emit_status(&result), Negotiated(result))
+}
+
+#[post("/v1/producer/emit_transaction", format = "json", data = "<data>")]
+pub async fn emit_transaction_json(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, max_rows: &State<MaxBatchRows>, timer: metrics::EmitTimer<'_>, data: Json<Vec<producer_com::EmitBatch<'_, HashMap<String,serde_json::Value>>>>) -> (Status, Negotiated<producer_com::EmitResult>) {
+    let result = timer.time(emit_transaction(&conn, quotas, **missing_tables, **max_rows, &data)).await;
+    (emit_status(&result), Negotiated(result))
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/emit_session", format = "msgpack", data = "<data>")]
+pub async fn emit_session_pack(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, uuids: &State<UuidStrategy>, max_rows: &State<MaxBatchRows>, limits: &Limits, data: Data<'_>) -> (Status, Negotiated<producer_com::EmitSessionResult>) {
+    let bytes = match format::read_msgpack(data, limits).await {
+        Ok(bytes) => bytes,
+        Err((status, error)) => return (status, Negotiated(producer_com::EmitSessionResult { error, results: Vec::new() })),
+    };
+    let data: Vec<producer_com::Emit<'_, HashMap<String,serde_json::Value>>> = match format::decode_msgpack(&bytes) {
+        Ok(data) => data,
+        Err((status, error)) => return (status, Negotiated(producer_com::EmitSessionResult { error, results: Vec::new() })),
+    };
+    let result = emit_session(&conn, quotas, **missing_tables, **uuids, **max_rows, &data).await;
+    (session_status(&result), Negotiated(result))
+}
+
+#[post("/v1/producer/emit_session", format = "json", data = "<data>")]
+pub async fn emit_session_json(conn: db::QuestDbConn, quotas: &State<quota::Quotas>, missing_tables: &State<producer_com::MissingTablePolicy>, uuids: &State<UuidStrategy>, max_rows: &State<MaxBatchRows>, data: Json<Vec<producer_com::Emit<'_, HashMap<String,serde_json::Value>>>>) -> (Status, Negotiated<producer_com::EmitSessionResult>) {
+    let result = emit_session(&conn, quotas, **missing_tables, **uuids, **max_rows, &data).await;
+    (session_status(&result), Negotiated(result))
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/query?<uuid>&<from>&<to>&<limit>&<include_deleted>&<layout>", format = "msgpack")]
+pub async fn query_pack(conn: db::QuestDbConn, uuid: &str, from: Option<u64>, to: Option<u64>, limit: Option<u64>, include_deleted: Option<bool>, layout: Option<&str>) -> MsgPack<producer_com::QueryResult> {
+    let filter = RowFilter { from, to, limit, newest_first: false, include_deleted: include_deleted.unwrap_or(false) };
+    MsgPack(query(&conn, uuid, filter, layout).await)
+}
+
+#[get("/v1/producer/query?<uuid>&<from>&<to>&<limit>&<include_deleted>&<layout>", format = "json")]
+pub async fn query_json(conn: db::QuestDbConn, uuid: &str, from: Option<u64>, to: Option<u64>, limit: Option<u64>, include_deleted: Option<bool>, layout: Option<&str>) -> Json<producer_com::QueryResult> {
+    let filter = RowFilter { from, to, limit, newest_first: false, include_deleted: include_deleted.unwrap_or(false) };
+    Json(query(&conn, uuid, filter, layout).await)
+}
+
+///
+/// A query answered before any rows are streamed fails with a status so clients reading frames don't
+/// have to look for an error inside the stream.
+///
+#[cfg(feature = "msgpack")]
+fn stream_status(error: &error_com::ConductorError) -> Status {
+    match error {
+        error_com::ConductorError::Unregistered(_) => Status::NotFound,
+        error_com::ConductorError::InternalError(_) => Status::InternalServerError,
+        _ => Status::BadRequest,
+    }
+}
+
+///
+/// The number of rows the stream route reads from the database at a time.
+///
+#[cfg(feature = "msgpack")]
+pub const STREAM_PAGE_ROWS: u64 = 1000;
+
+///
+/// Answers a query as a stream of length prefixed msgpack rows. Each row is a 4 byte big endian
+/// length followed by the row as a msgpack map so clients can decode rows as they arrive. The rows are
+/// read a page at a time and each page is written before the next is read so the whole result is
+/// never held in memory. A query which fails before any rows are sent is answered with a single
+/// msgpack `QueryResult` holding the error. The status has already been sent when a later page fails
+/// so the stream ends early and the error is logged.
+///
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/query?<uuid>&<from>&<to>&<limit>&<include_deleted>", format = "application/x-msgpack-stream")]
+pub async fn query_stream(conn: db::QuestDbConn, uuid: &str, from: Option<u64>, to: Option<u64>, limit: Option<u64>, include_deleted: Option<bool>) -> Result<(ContentType, ByteStream![Vec<u8>]), (Status, (ContentType, Vec<u8>))> {
+    let filter = RowFilter { from, to, limit, newest_first: false, include_deleted: include_deleted.unwrap_or(false) };
+    let failed = |error: error_com::ConductorError| {
+        let status = stream_status(&error);
+        let result = producer_com::QueryResult { error, rows: Vec::new(), columnar: None };
+        (status, (ContentType::MsgPack, rmp_serde::to_vec_named(&result).unwrap_or_default()))
+    };
+    let producer = get_producer_row(&conn, uuid).await.map_err(failed)?;
+    let schema = get_producer_schema(&producer).map_err(failed)?;
+    //the filter is checked before the status is sent
+    if let Err(error) = generate_page_sql(&producer, &filter, 0, STREAM_PAGE_ROWS) {
+        return Err(failed(error));
+    }
+
+    let stream = ByteStream! {
+        let mut offset = 0;
+        loop {
+            let page_rows = filter.limit.map_or(STREAM_PAGE_ROWS, |limit| STREAM_PAGE_ROWS.min(limit - offset));
+            if page_rows == 0 {
+                break;
+            }
+            let (sql, params) = match generate_page_sql(&producer, &filter, offset, page_rows) {
+                Ok(page) => page,
+                Err(error) => {
+                    log::error!("{}", error);
+                    break;
+                }
+            };
+            let read_result = conn.run(move |conn: &mut postgres::Client| {
+                conn.query(sql.as_str(), as_time_params(&params).as_slice())
+            }).await;
+            let rows = match read_result {
+                Ok(rows) => rows,
+                Err(err) => {
+                    log::error!("{}", error_com::ConductorError::InternalError(format!("Error reading rows for producer {}. The stream was ended early. {}", producer.uuid, err)));
+                    break;
+                }
+            };
+            let mut chunk = Vec::new();
+            let mut written = true;
+            for row in &rows {
+                if let Err(error) = row_to_map(row, &schema).and_then(|row_values| format_com::write_msgpack_frame(&mut chunk, &row_values)) {
+                    log::error!("{}", error);
+                    written = false;
+                    break;
+                }
+            }
+            //the rows before the one which couldn't be written are still sent
+            yield chunk;
+            if !written || (rows.len() as u64) < page_rows {
+                break;
+            }
+            offset += page_rows;
+        }
+    };
+    Ok((ContentType::new("application", "x-msgpack-stream"), stream))
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/latest?<uuid>&<include_deleted>", format = "msgpack")]
+pub async fn latest_pack(conn: db::QuestDbConn, uuid: &str, include_deleted: Option<bool>) -> MsgPack<producer_com::QueryResult> {
+    let filter = RowFilter { from: None, to: None, limit: Some(1), newest_first: true, include_deleted: include_deleted.unwrap_or(false) };
+    MsgPack(select_rows(&conn, uuid, filter, producer_com::Layout::Rows).await)
+}
+
+#[get("/v1/producer/latest?<uuid>&<include_deleted>", format = "json")]
+pub async fn latest_json(conn: db::QuestDbConn, uuid: &str, include_deleted: Option<bool>) -> Json<producer_com::QueryResult> {
+    let filter = RowFilter { from: None, to: None, limit: Some(1), newest_first: true, include_deleted: include_deleted.unwrap_or(false) };
+    Json(select_rows(&conn, uuid, filter, producer_com::Layout::Rows).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/rollup?<params..>", format = "msgpack")]
+pub async fn rollup_pack(conn: db::QuestDbConn, params: RollupParams<'_>) -> MsgPack<producer_com::RollupResult> {
+    MsgPack(rollup(&conn, &params).await)
+}
+
+#[get("/v1/producer/rollup?<params..>", format = "json")]
+pub async fn rollup_json(conn: db::QuestDbConn, params: RollupParams<'_>) -> Json<producer_com::RollupResult> {
+    Json(rollup(&conn, &params).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/columns?<uuid>", format = "msgpack")]
+pub async fn columns_pack(conn: db::QuestDbConn, uuid: &str) -> MsgPack<producer_com::ColumnsResult> {
+    MsgPack(columns(&conn, uuid).await)
+}
+
+#[get("/v1/producer/columns?<uuid>", format = "json")]
+pub async fn columns_json(conn: db::QuestDbConn, uuid: &str) -> Json<producer_com::ColumnsResult> {
+    Json(columns(&conn, uuid).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/range?<uuid>", format = "msgpack")]
+pub async fn time_range_pack(conn: db::QuestDbConn, uuid: &str) -> MsgPack<producer_com::TimeRangeResult> {
+    MsgPack(time_range(&conn, uuid).await)
+}
+
+#[get("/v1/producer/range?<uuid>", format = "json")]
+pub async fn time_range_json(conn: db::QuestDbConn, uuid: &str) -> Json<producer_com::TimeRangeResult> {
+    Json(time_range(&conn, uuid).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/adopt?<table>", format = "msgpack")]
+pub async fn adopt_pack(conn: db::QuestDbConn, table: &str) -> MsgPack<producer_com::RegistrationResult> {
+    MsgPack(adopt(&conn, table).await)
+}
+
+#[post("/v1/producer/adopt?<table>", format = "json")]
+pub async fn adopt_json(conn: db::QuestDbConn, table: &str) -> Json<producer_com::RegistrationResult> {
+    Json(adopt(&conn, table).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/producer/schema?<uuid>&<recover>", format = "msgpack")]
+pub async fn schema_pack(conn: db::QuestDbConn, uuid: &str, recover: Option<bool>) -> MsgPack<producer_com::SchemaResult> {
+    MsgPack(registered_schema(&conn, uuid, recover.unwrap_or(false)).await)
+}
+
+#[get("/v1/producer/schema?<uuid>&<recover>", format = "json")]
+pub async fn schema_json(conn: db::QuestDbConn, uuid: &str, recover: Option<bool>) -> Json<producer_com::SchemaResult> {
+    Json(registered_schema(&conn, uuid, recover.unwrap_or(false)).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[patch("/v1/producer/<uuid>", format = "msgpack", data = "<data>")]
+pub async fn update_pack(conn: db::QuestDbConn, uuid: &str, data: MsgPack<producer_com::ProducerUpdate>) -> Negotiated<producer_com::ProducerUpdateResult> {
+    Negotiated(update_producer(&conn, uuid, data.into_inner()).await)
+}
+
+#[patch("/v1/producer/<uuid>", format = "json", data = "<data>")]
+pub async fn update_json(conn: db::QuestDbConn, uuid: &str, data: Json<producer_com::ProducerUpdate>) -> Negotiated<producer_com::ProducerUpdateResult> {
+    Negotiated(update_producer(&conn, uuid, data.into_inner()).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/<uuid>/schema", format = "msgpack", data = "<data>")]
+pub async fn swap_schema_pack(conn: db::QuestDbConn, swaps: &State<SchemaSwaps>, uuid: &str, data: MsgPack<producer_com::SchemaSwap>) -> (Status, Negotiated<producer_com::SchemaSwapResult>) {
+    let result = swap_schema(&conn, swaps, uuid, &data).await;
+    (swap_status(&result), Negotiated(result))
+}
+
+#[post("/v1/producer/<uuid>/schema", format = "json", data = "<data>")]
+pub async fn swap_schema_json(conn: db::QuestDbConn, swaps: &State<SchemaSwaps>, uuid: &str, data: Json<producer_com::SchemaSwap>) -> (Status, Negotiated<producer_com::SchemaSwapResult>) {
+    let result = swap_schema(&conn, swaps, uuid, &data).await;
+    (swap_status(&result), Negotiated(result))
+}
+
+/// Swaps which lost the compare are reported as 409 in the same way as registration conflicts.
+fn swap_status(result: &producer_com::SchemaSwapResult) -> Status {
+    match result.error {
+        error_com::ConductorError::SchemaConflict(_) => Status::Conflict,
+        _ => Status::Ok,
+    }
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/<uuid>/pause", format = "msgpack")]
+pub async fn pause_pack(conn: db::QuestDbConn, uuid: &str) -> Negotiated<producer_com::PauseResult> {
+    Negotiated(set_paused(&conn, uuid, true).await)
+}
+
+#[post("/v1/producer/<uuid>/pause", format = "json")]
+pub async fn pause_json(conn: db::QuestDbConn, uuid: &str) -> Negotiated<producer_com::PauseResult> {
+    Negotiated(set_paused(&conn, uuid, true).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/<uuid>/resume", format = "msgpack")]
+pub async fn resume_pack(conn: db::QuestDbConn, uuid: &str) -> Negotiated<producer_com::PauseResult> {
+    Negotiated(set_paused(&conn, uuid, false).await)
+}
+
+#[post("/v1/producer/<uuid>/resume", format = "json")]
+pub async fn resume_json(conn: db::QuestDbConn, uuid: &str) -> Negotiated<producer_com::PauseResult> {
+    Negotiated(set_paused(&conn, uuid, false).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/soft_delete", format = "msgpack", data = "<data>")]
+pub async fn soft_delete_pack(conn: db::QuestDbConn, data: MsgPack<producer_com::SoftDelete<'_>>) -> Negotiated<producer_com::SoftDeleteResult> {
+    Negotiated(soft_delete(&conn, &data).await)
+}
+
+#[post("/v1/producer/soft_delete", format = "json", data = "<data>")]
+pub async fn soft_delete_json(conn: db::QuestDbConn, data: Json<producer_com::SoftDelete<'_>>) -> Negotiated<producer_com::SoftDeleteResult> {
+    Negotiated(soft_delete(&conn, &data).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/check_batch", format = "msgpack", data = "<data>")]
+pub async fn check_batch_pack(conn: db::QuestDbConn, data: MsgPack<Vec<String>>) -> Negotiated<producer_com::CheckBatchResult> {
+    Negotiated(check_batch(&conn, &data).await)
+}
+
+#[post("/v1/producer/check_batch", format = "json", data = "<data>")]
+pub async fn check_batch_json(conn: db::QuestDbConn, data: Json<Vec<String>>) -> Negotiated<producer_com::CheckBatchResult> {
+    Negotiated(check_batch(&conn, &data).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/query_union", format = "msgpack", data = "<data>")]
+pub async fn query_union_pack(conn: db::QuestDbConn, data: MsgPack<producer_com::QueryUnion>) -> Negotiated<producer_com::QueryResult> {
+    Negotiated(query_union(&conn, &data).await)
+}
+
+#[post("/v1/producer/query_union", format = "json", data = "<data>")]
+pub async fn query_union_json(conn: db::QuestDbConn, data: Json<producer_com::QueryUnion>) -> Negotiated<producer_com::QueryResult> {
+    Negotiated(query_union(&conn, &data).await)
 }
 
 #[get("/v1/producer/check?<uuid>", format = "json")]
 pub async fn check(conn: db::QuestDbConn, uuid: &str) -> Status {
     match get_producer_row(&conn, &uuid.to_string()).await {
         Ok(_) => Status::Ok,
-        Err(_) => Status::NotFound,
+        Err(error_com::ConductorError::Unregistered(_) | error_com::ConductorError::InvalidUuid(_)) => Status::NotFound,
+        Err(_) => Status::InternalServerError,
     }
 }
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/truncate", format = "msgpack", data = "<data>")]
+pub async fn truncate_pack(conn: db::QuestDbConn, data: MsgPack<producer_com::Truncate<'_>>) -> Negotiated<producer_com::TruncateResult> {
+    Negotiated(truncate(&conn, &data).await)
+}
+
+#[post("/v1/producer/truncate", format = "json", data = "<data>")]
+pub async fn truncate_json(conn: db::QuestDbConn, data: Json<producer_com::Truncate<'_>>) -> Negotiated<producer_com::TruncateResult> {
+    Negotiated(truncate(&conn, &data).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/producer/delete_batch", format = "msgpack", data = "<data>")]
+pub async fn delete_batch_pack(conn: db::QuestDbConn, data: MsgPack<producer_com::DeleteBatch>) -> Negotiated<producer_com::DeleteBatchResult> {
+    Negotiated(delete_batch(&conn, &data).await)
+}
+
+#[post("/v1/producer/delete_batch", format = "json", data = "<data>")]
+pub async fn delete_batch_json(conn: db::QuestDbConn, data: Json<producer_com::DeleteBatch>) -> Negotiated<producer_com::DeleteBatchResult> {
+    Negotiated(delete_batch(&conn, &data).await)
+}
+
+///
+/// Handles requests that were rejected because no database connection became available within the
+/// `AcquireTimeout`. Responds with only a `Busy` error in the format of the request rather than the
+/// default html page. It isn't wrapped in the result of any route as the catcher serves all of them
+/// so clients decode it on its own and can retry.
+///
+#[catch(503)]
+pub fn busy(request: &rocket::Request<'_>) -> Negotiated<error_com::ConductorError> {
+    let error = error_com::ConductorError::Busy("No database connection became available in time".to_string());
+    log::error!("{} for {}", error, request.uri());
+    Negotiated(error)
+}
+
+///
+/// Manages the `MissingTablePolicy` which decides what happens to emits for a producer whose table was
+/// dropped outside of Conductor. It's read from the `missing_tables` config value and defaults to rejecting them.
+/// The lock schema swaps are serialised with and the `max_batch_rows` config value are managed here as well.
+pub fn stage() -> rocket::fairing::AdHoc {
+    rocket::fairing::AdHoc::on_ignite("Missing producer tables", |rocket| async {
+        let policy: producer_com::MissingTablePolicy = rocket.figment().extract_inner("missing_tables").unwrap_or_default();
+        let max_rows = rocket.figment().extract_inner("max_batch_rows").unwrap_or(producer_com::DEFAULT_MAX_BATCH_ROWS);
+        rocket.manage(policy).manage(SchemaSwaps(tokio::sync::Mutex::new(()))).manage(MaxBatchRows(max_rows))
+    })
+}
@@ -0,0 +1,193 @@
+//! Enforces the row quotas of producers. Usage is counted in memory and written to the quota_usage
+//! table at most once per `PERSIST_INTERVAL` so that it survives a restart. Rows written after the
+//! last snapshot are forgotten if the server stops.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use conductor_common::error as error_com;
+use rocket::fairing::AdHoc;
+use rocket::{Build, Rocket};
+use crate::db;
+
+/// The length of a quota period when the server isn't configured otherwise. 30 days in seconds.
+const DEFAULT_QUOTA_PERIOD_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// How often the usage of producers is written to the database in microseconds.
+const PERSIST_INTERVAL: u64 = 60_000_000;
+
+/// The rows a producer has written since the start of its current quota period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaUsage {
+    /// The start of the current period in microseconds since the unix epoch
+    pub period_start: u64,
+    /// The rows written since the start of the period
+    pub rows: u64,
+}
+
+impl QuotaUsage {
+    /// Usage for a period starting at `period_start` in which nothing has been written.
+    #[must_use]
+    pub const fn new(period_start: u64) -> Self {
+        Self { period_start, rows: 0 }
+    }
+
+    /// Starts a new period if `period` microseconds have passed since the current one started. Periods
+    /// are back to back so the new period starts at a whole number of periods after the old one.
+    pub fn roll_over(&mut self, now: u64, period: u64) {
+        if period == 0 || now < self.period_start.saturating_add(period) {
+            return;
+        }
+        let elapsed_periods = (now - self.period_start) / period;
+        self.period_start += elapsed_periods * period;
+        self.rows = 0;
+    }
+
+    ///
+    /// Records that `rows` are about to be written at `now`. Nothing is recorded if writing them would
+    /// take the producer over its quota for the period.
+    ///
+    /// # Errors
+    /// * `QuotaExceeded`: The rows don't fit in what remains of the quota for the current period.
+    ///
+    pub fn consume(&mut self, rows: u64, quota: u64, now: u64, period: u64) -> Result<(), error_com::ConductorError> {
+        self.roll_over(now, period);
+        if self.rows.saturating_add(rows) > quota {
+            return Err(error_com::ConductorError::QuotaExceeded(format!(
+                "Writing {} rows would exceed the quota of {} rows. {} rows have been written this period.",
+                rows, quota, self.rows
+            )));
+        }
+        self.rows += rows;
+        Ok(())
+    }
+
+    /// Gives back rows which were consumed but couldn't be written.
+    pub fn refund(&mut self, rows: u64) {
+        self.rows = self.rows.saturating_sub(rows);
+    }
+}
+
+pub(crate) fn now_micros() -> u64 {
+    u64::try_from(chrono::Utc::now().timestamp_micros()).unwrap_or_default()
+}
+
+/// The usage of every producer with a quota.
+pub struct Quotas {
+    /// The length of a quota period in microseconds
+    period: u64,
+    usage: Mutex<HashMap<String, QuotaUsage>>,
+    /// Producers whose usage changed since the last snapshot
+    changed: Mutex<HashSet<String>>,
+    last_persisted: Mutex<u64>,
+}
+
+impl Quotas {
+    fn new(period_secs: u64, usage: HashMap<String, QuotaUsage>) -> Self {
+        Self {
+            period: period_secs.saturating_mul(1_000_000),
+            usage: Mutex::new(usage),
+            changed: Mutex::new(HashSet::new()),
+            last_persisted: Mutex::new(now_micros()),
+        }
+    }
+
+    ///
+    /// Records that a producer is about to write `rows`. Producers without a quota are never rejected.
+    ///
+    /// # Errors
+    /// * `QuotaExceeded`: The rows would take the producer over its quota.
+    ///
+    pub fn consume(&self, uuid: &str, rows: u64, quota: Option<u64>) -> Result<(), error_com::ConductorError> {
+        let quota = match quota {
+            Some(quota) => quota,
+            None => return Ok(()),
+        };
+        let now = now_micros();
+        let mut usage = self.usage.lock().unwrap();
+        usage.entry(uuid.to_string()).or_insert_with(|| QuotaUsage::new(now)).consume(rows, quota, now, self.period)?;
+        self.changed.lock().unwrap().insert(uuid.to_string());
+        Ok(())
+    }
+
+    /// Gives back rows which were consumed by `consume` but couldn't be written.
+    pub fn refund(&self, uuid: &str, rows: u64, quota: Option<u64>) {
+        if quota.is_some() {
+            if let Some(usage) = self.usage.lock().unwrap().get_mut(uuid) {
+                usage.refund(rows);
+            }
+        }
+    }
+
+    /// Returns the usage which changed since the last snapshot once `PERSIST_INTERVAL` has passed.
+    fn take_snapshot(&self) -> Option<Vec<(String, QuotaUsage)>> {
+        let now = now_micros();
+        let mut last_persisted = self.last_persisted.lock().unwrap();
+        if now < last_persisted.saturating_add(PERSIST_INTERVAL) {
+            return None;
+        }
+        *last_persisted = now;
+        let changed: Vec<String> = self.changed.lock().unwrap().drain().collect();
+        let usage = self.usage.lock().unwrap();
+        Some(changed.into_iter().filter_map(|uuid| usage.get(&uuid).map(|usage| (uuid, *usage))).collect())
+    }
+
+    /// Writes the usage which changed since the last snapshot if it's due.
+    pub async fn persist_if_due(&self, db: &db::QuestDbConn) {
+        let snapshot = match self.take_snapshot() {
+            Some(snapshot) if !snapshot.is_empty() => snapshot,
+            _ => return,
+        };
+        let write_result = db
+            .run(move |conn: &mut postgres::Client| {
+                let mut transaction = conn.transaction()?;
+                for (uuid, usage) in &snapshot {
+                    #[allow(clippy::cast_possible_wrap)]
+                    let (rows, period_start) = (usage.rows as i64, usage.period_start as i64);
+                    transaction.execute(
+                        "INSERT INTO quota_usage (uuid, rows, period_start, ts) VALUES ($1, $2, $3, now());",
+                        &[uuid, &rows, &period_start],
+                    )?;
+                }
+                transaction.commit()
+            })
+            .await;
+        if let Err(err) = write_result {
+            log::error!("Couldn't persist the quota usage of producers. {}", err);
+        }
+    }
+}
+
+async fn load_usage(rocket: &Rocket<Build>) -> HashMap<String, QuotaUsage> {
+    let conn = match db::QuestDbConn::get_one(rocket).await {
+        Some(conn) => conn,
+        None => return HashMap::new(),
+    };
+    let read_result = conn
+        .run(|conn: &mut postgres::Client| {
+            conn.query("SELECT uuid, rows, period_start FROM quota_usage LATEST ON ts PARTITION BY uuid;", &[])
+        })
+        .await;
+    match read_result {
+        Ok(rows) => rows.iter().filter_map(|row| {
+            let uuid: String = row.try_get("uuid").ok()?;
+            let rows: i64 = row.try_get("rows").ok()?;
+            let period_start: i64 = row.try_get("period_start").ok()?;
+            Some((uuid, QuotaUsage { period_start: u64::try_from(period_start).ok()?, rows: u64::try_from(rows).ok()? }))
+        }).collect(),
+        Err(err) => {
+            log::error!("Couldn't load the quota usage of producers. Usage starts from zero. {}", err);
+            HashMap::new()
+        }
+    }
+}
+
+/// Loads the persisted usage and manages the `Quotas`. The length of a quota period in seconds is read
+/// from the `quota_period` config value.
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Quotas", |rocket| async {
+        let period_secs = rocket.figment().extract_inner("quota_period").unwrap_or(DEFAULT_QUOTA_PERIOD_SECS);
+        let usage = load_usage(&rocket).await;
+        rocket.manage(Quotas::new(period_secs, usage))
+    })
+}
@@ -0,0 +1,372 @@
+//! Queues actions for reactors. Actions are queued for a registered reactor, handed to the reactor
+//! when it polls for pending actions and completed once the reactor reports their result.
+//!
+//! Actions stay pending until their result is reported so a reactor which polls again before
+//! reporting is given the same actions again.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use postgres::Row;
+use rocket::fairing::AdHoc;
+use rocket::serde::json::Json;
+use rocket::State;
+use tokio::sync::Notify;
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+use uuid::Uuid;
+use crate::db;
+use crate::format::Negotiated;
+use conductor_common::error as error_com;
+use conductor_common::producer as producer_com;
+use conductor_common::reactor as reactor_com;
+
+/// The longest a poll for pending actions is held open in seconds regardless of the wait requested.
+const MAX_POLL_WAIT: u64 = 60;
+
+/// How often the queue is checked while a poll is held open.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lets polls for pending actions be held open without holding a database connection. A connection
+/// is taken from the pool for each check of the queue and queuing an action wakes the polls waiting
+/// on this server. Polls still check every `POLL_INTERVAL` as another server can queue actions too.
+pub struct PendingPolls {
+    pool: db::QuestDbPool,
+    acquire_timeout: db::AcquireTimeout,
+    queued: Notify,
+}
+
+/// Manages the `PendingPolls` of the server. The database pool and its acquire timeout have to be attached first.
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Pending action polls", |rocket| async {
+        let pool = db::QuestDbConn::pool(&rocket).cloned();
+        let acquire_timeout = rocket.state::<db::AcquireTimeout>().copied().unwrap_or_default();
+        match pool {
+            Some(pool) => rocket.manage(PendingPolls { pool, acquire_timeout, queued: Notify::new() }),
+            None => {
+                log::error!("The database isn't attached so reactors can't poll for pending actions");
+                rocket
+            }
+        }
+    })
+}
+
+macro_rules! log_error {
+    ($errorCode:expr) => {{
+        log::error!("{}", $errorCode);
+        Err($errorCode)
+    }};
+}
+
+///
+/// Reads the actions a reactor registered.
+///
+/// # Errors
+/// * `ConductorError::InvalidUuid` : The uuid is empty or has illegal chars
+/// * `ConductorError::Unregistered` : No reactor is registered with the uuid
+/// * `ConductorError::InternalError` : The database couldn't be queried or the actions couldn't be read
+///
+async fn get_reactor_actions(db: &db::QuestDbConn, uuid: &str) -> Result<Vec<reactor_com::ActionRegistration>, error_com::ConductorError> {
+    producer_com::validate_uuid(uuid)?;
+    let uuid_copy = uuid.to_string();
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query("SELECT actions FROM reactors WHERE uuid = $1;", &[&uuid_copy])
+        })
+        .await;
+    let rows = match read_result {
+        Ok(rows) => rows,
+        Err(err) => {
+            return log_error!(error_com::ConductorError::InternalError(format!("Error getting reactor {} from database {}", uuid, err)));
+        }
+    };
+    let actions: String = match rows.first() {
+        Some(row) => row.try_get("actions").unwrap_or_default(),
+        None => {
+            return log_error!(error_com::ConductorError::Unregistered(format!("No reactor is registered with uuid {}", uuid)));
+        }
+    };
+    match serde_json::from_str(&actions) {
+        Ok(actions) => Ok(actions),
+        Err(err) => log_error!(error_com::ConductorError::InternalError(format!("Couldn't read the actions of reactor {}. {}", uuid, err))),
+    }
+}
+
+async fn register(db: &db::QuestDbConn, registration: &reactor_com::ReactorRegistration) -> producer_com::RegistrationResult {
+    if let Err(error) = registration.validate() {
+        log::error!("{}", error);
+        return producer_com::RegistrationResult { error, uuid: None };
+    }
+    if let Some(custom_id) = registration.get_custom_id() {
+        match get_reactor_actions(db, custom_id).await {
+            Ok(_) => {
+                let error = error_com::ConductorError::InvalidUuid(format!("A reactor is already registered with uuid {}", custom_id));
+                return producer_com::RegistrationResult { error, uuid: None };
+            }
+            Err(error_com::ConductorError::Unregistered(_)) => {}
+            Err(error) => return producer_com::RegistrationResult { error, uuid: None },
+        }
+    }
+
+    let uuid = registration.get_custom_id().map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    match persist_reactor(db, &uuid, registration).await {
+        Ok(()) => producer_com::RegistrationResult { error: error_com::ConductorError::NoError, uuid: Some(uuid) },
+        Err(error) => producer_com::RegistrationResult { error, uuid: None },
+    }
+}
+
+async fn persist_reactor(db: &db::QuestDbConn, uuid: &str, registration: &reactor_com::ReactorRegistration) -> Result<(), error_com::ConductorError> {
+    let name = registration.get_name().to_string();
+    let actions = serde_json::to_string(registration.get_actions()).unwrap_or_default();
+    let uuid_copy = uuid.to_string();
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute("INSERT INTO reactors (name, uuid, actions) VALUES ($1, $2, $3);", &[&name, &uuid_copy, &actions])
+        })
+        .await;
+    match write_result {
+        Ok(_) => Ok(()),
+        Err(err) => log_error!(error_com::ConductorError::InternalError(format!("There was an error persisting the reactor to the db: {}", err))),
+    }
+}
+
+///
+/// Checks the actions of a device against the reactor already registered under its uuid. Returns
+/// whether the reactor half is already registered.
+///
+/// # Errors
+/// * `ConductorError::SchemaConflict` : A reactor is registered under the uuid with different actions.
+/// The conflict lists the names of the actions which differ.
+/// * `ConductorError::InternalError` : The database couldn't be queried
+///
+pub(crate) async fn check_device_actions(db: &db::QuestDbConn, uuid: &str, actions: &[reactor_com::ActionRegistration]) -> Result<bool, error_com::ConductorError> {
+    let registered = match get_reactor_actions(db, uuid).await {
+        Ok(registered) => registered,
+        Err(error_com::ConductorError::Unregistered(_)) => return Ok(false),
+        Err(error) => return Err(error),
+    };
+    let mut differences: Vec<String> = registered.iter().chain(actions)
+        .filter(|action| !(registered.contains(action) && actions.contains(action)))
+        .map(|action| action.get_name().to_string())
+        .collect();
+    if differences.is_empty() {
+        return Ok(true);
+    }
+    differences.sort();
+    differences.dedup();
+    log_error!(error_com::ConductorError::SchemaConflict(differences))
+}
+
+///
+/// Stores the reactor half of a device under the uuid its producer half was registered with. A device
+/// which is registered again with the same actions is left as it is.
+///
+/// # Errors
+/// The errors of `check_device_actions` and `ConductorError::InternalError` if the reactor couldn't be stored.
+///
+pub(crate) async fn register_device_actions(db: &db::QuestDbConn, registration: &reactor_com::ReactorRegistration) -> Result<(), error_com::ConductorError> {
+    let uuid = match registration.get_custom_id() {
+        Some(uuid) => uuid,
+        None => return log_error!(error_com::ConductorError::InvalidUuid(String::from("The reactor half of a device needs the uuid of its producer half"))),
+    };
+    if check_device_actions(db, uuid, registration.get_actions()).await? {
+        return Ok(());
+    }
+    persist_reactor(db, uuid, registration).await
+}
+
+async fn enqueue(db: &db::QuestDbConn, polls: &PendingPolls, request: &reactor_com::EnqueueAction) -> reactor_com::EnqueueResult {
+    let actions = match get_reactor_actions(db, &request.reactor).await {
+        Ok(actions) => actions,
+        Err(error) => return reactor_com::EnqueueResult { error, id: None },
+    };
+    let action = match actions.iter().find(|action| action.get_name() == request.action) {
+        Some(action) => action,
+        None => {
+            let error = error_com::ConductorError::InvalidData(format!("The reactor {} doesn't have the action {}", request.reactor, request.action));
+            log::error!("{}", error);
+            return reactor_com::EnqueueResult { error, id: None };
+        }
+    };
+    if let Err(error) = action.check_version(request.expected_version).and_then(|_| action.validate_input(&request.parameters)) {
+        log::error!("{}", error);
+        return reactor_com::EnqueueResult { error, id: None };
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let (id_copy, reactor, action_name) = (id.clone(), request.reactor.clone(), request.action.clone());
+    let parameters = serde_json::to_string(&request.parameters).unwrap_or_default();
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute(
+                "INSERT INTO actions (id, reactor, action, parameters, status, ts) VALUES ($1, $2, $3, $4, 'pending', now());",
+                &[&id_copy, &reactor, &action_name, &parameters],
+            )
+        })
+        .await;
+    match write_result {
+        Ok(_) => {
+            polls.queued.notify_waiters();
+            reactor_com::EnqueueResult { error: error_com::ConductorError::NoError, id: Some(id) }
+        }
+        Err(err) => {
+            let error = error_com::ConductorError::InternalError(format!("Couldn't queue the action {} for reactor {}. {}", request.action, request.reactor, err));
+            log::error!("{}", error);
+            reactor_com::EnqueueResult { error, id: None }
+        }
+    }
+}
+
+fn row_to_action(row: &Row) -> Result<reactor_com::Action, error_com::ConductorError> {
+    let parameters: String = row.try_get("parameters").unwrap_or_default();
+    let parameters: HashMap<String, serde_json::Value> = match serde_json::from_str(&parameters) {
+        Ok(parameters) => parameters,
+        Err(err) => return log_error!(error_com::ConductorError::InternalError(format!("Couldn't read the parameters of a queued action. {}", err))),
+    };
+    Ok(reactor_com::Action {
+        id: row.try_get("id").unwrap_or_default(),
+        action: row.try_get("action").unwrap_or_default(),
+        parameters,
+        version: None,
+    })
+}
+
+async fn read_pending(db: &db::QuestDbConn, uuid: &str) -> Result<Vec<reactor_com::Action>, error_com::ConductorError> {
+    let uuid_copy = uuid.to_string();
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query("SELECT id, action, parameters FROM actions WHERE reactor = $1 AND status = 'pending' ORDER BY ts;", &[&uuid_copy])
+        })
+        .await;
+    match read_result {
+        Ok(rows) => rows.iter().map(row_to_action).collect(),
+        Err(err) => log_error!(error_com::ConductorError::InternalError(format!("Couldn't read the pending actions of reactor {}. {}", uuid, err))),
+    }
+}
+
+/// Takes a connection from the pool for one check of the queue.
+async fn connection(polls: &PendingPolls) -> Result<db::QuestDbConn, error_com::ConductorError> {
+    match db::QuestDbConn::from_pool(&polls.pool, polls.acquire_timeout).await {
+        Some(conn) => Ok(conn),
+        None => log_error!(error_com::ConductorError::Busy("No database connection became available in time".to_string())),
+    }
+}
+
+///
+/// Returns the pending actions of a reactor. When there aren't any the poll waits until an action is
+/// queued or `POLL_INTERVAL` passes and checks again until `wait` seconds (at most `MAX_POLL_WAIT`)
+/// have passed. No connection is held while waiting. Each action is given the version it was registered with.
+///
+async fn pending(polls: &PendingPolls, uuid: &str, wait: Option<u64>) -> Result<Vec<reactor_com::Action>, error_com::ConductorError> {
+    let registered = get_reactor_actions(&connection(polls).await?, uuid).await?;
+    let deadline = Instant::now() + Duration::from_secs(wait.unwrap_or_default().min(MAX_POLL_WAIT));
+    loop {
+        //created before the queue is read so an action queued in between still wakes the poll
+        let queued = polls.queued.notified();
+        let mut actions = read_pending(&connection(polls).await?, uuid).await?;
+        let now = Instant::now();
+        if !actions.is_empty() || now >= deadline {
+            for action in &mut actions {
+                action.version = registered.iter().find(|registration| registration.get_name() == action.action).and_then(reactor_com::ActionRegistration::get_version);
+            }
+            return Ok(actions);
+        }
+        let _woken = rocket::tokio::time::timeout(POLL_INTERVAL.min(deadline - now), queued).await;
+    }
+}
+
+async fn pending_result(polls: &PendingPolls, uuid: &str, wait: Option<u64>) -> reactor_com::PendingActionsResult {
+    match pending(polls, uuid, wait).await {
+        Ok(actions) => reactor_com::PendingActionsResult { error: error_com::ConductorError::NoError, actions },
+        Err(error) => reactor_com::PendingActionsResult { error, actions: Vec::new() },
+    }
+}
+
+async fn complete(db: &db::QuestDbConn, report: &reactor_com::ActionReport<'_>) -> reactor_com::ActionReportResult {
+    let id = report.action_id.to_string();
+    let read_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.query("SELECT status FROM actions WHERE id = $1;", &[&id])
+        })
+        .await;
+    let status: String = match read_result {
+        Ok(rows) => match rows.first() {
+            Some(row) => row.try_get("status").unwrap_or_default(),
+            None => {
+                let error = error_com::ConductorError::InvalidUuid(format!("No action has the id {}", report.action_id));
+                log::error!("{}", error);
+                return reactor_com::ActionReportResult { error };
+            }
+        },
+        Err(err) => {
+            let error = error_com::ConductorError::InternalError(format!("Couldn't read the action {}. {}", report.action_id, err));
+            log::error!("{}", error);
+            return reactor_com::ActionReportResult { error };
+        }
+    };
+    if status != "pending" {
+        let error = error_com::ConductorError::InvalidData(format!("The result of the action {} has already been reported", report.action_id));
+        log::error!("{}", error);
+        return reactor_com::ActionReportResult { error };
+    }
+
+    let id = report.action_id.to_string();
+    let (succeeded, message) = (report.result.succeeded, report.result.message.clone());
+    let write_result = db
+        .run(move |conn: &mut postgres::Client| {
+            conn.execute("UPDATE actions SET status = 'completed', succeeded = $1, message = $2 WHERE id = $3;", &[&succeeded, &message, &id])
+        })
+        .await;
+    match write_result {
+        Ok(_) => reactor_com::ActionReportResult { error: error_com::ConductorError::NoError },
+        Err(err) => {
+            let error = error_com::ConductorError::InternalError(format!("Couldn't record the result of the action {}. {}", report.action_id, err));
+            log::error!("{}", error);
+            reactor_com::ActionReportResult { error }
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/reactor/register", format = "msgpack", data = "<data>")]
+pub async fn register_pack(conn: db::QuestDbConn, data: MsgPack<reactor_com::ReactorRegistration>) -> Negotiated<producer_com::RegistrationResult> {
+    Negotiated(register(&conn, &data).await)
+}
+
+#[post("/v1/reactor/register", format = "json", data = "<data>")]
+pub async fn register_json(conn: db::QuestDbConn, data: Json<reactor_com::ReactorRegistration>) -> Negotiated<producer_com::RegistrationResult> {
+    Negotiated(register(&conn, &data).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/reactor/enqueue", format = "msgpack", data = "<data>")]
+pub async fn enqueue_pack(conn: db::QuestDbConn, polls: &State<PendingPolls>, data: MsgPack<reactor_com::EnqueueAction>) -> Negotiated<reactor_com::EnqueueResult> {
+    Negotiated(enqueue(&conn, polls, &data).await)
+}
+
+#[post("/v1/reactor/enqueue", format = "json", data = "<data>")]
+pub async fn enqueue_json(conn: db::QuestDbConn, polls: &State<PendingPolls>, data: Json<reactor_com::EnqueueAction>) -> Negotiated<reactor_com::EnqueueResult> {
+    Negotiated(enqueue(&conn, polls, &data).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/reactor/pending?<uuid>&<wait>", format = "msgpack")]
+pub async fn pending_pack(polls: &State<PendingPolls>, uuid: &str, wait: Option<u64>) -> MsgPack<reactor_com::PendingActionsResult> {
+    MsgPack(pending_result(polls, uuid, wait).await)
+}
+
+#[get("/v1/reactor/pending?<uuid>&<wait>", format = "json")]
+pub async fn pending_json(polls: &State<PendingPolls>, uuid: &str, wait: Option<u64>) -> Json<reactor_com::PendingActionsResult> {
+    Json(pending_result(polls, uuid, wait).await)
+}
+
+#[cfg(feature = "msgpack")]
+#[post("/v1/reactor/result", format = "msgpack", data = "<data>")]
+pub async fn result_pack(conn: db::QuestDbConn, data: MsgPack<reactor_com::ActionReport<'_>>) -> Negotiated<reactor_com::ActionReportResult> {
+    Negotiated(complete(&conn, &data).await)
+}
+
+#[post("/v1/reactor/result", format = "json", data = "<data>")]
+pub async fn result_json(conn: db::QuestDbConn, data: Json<reactor_com::ActionReport<'_>>) -> Negotiated<reactor_com::ActionReportResult> {
+    Negotiated(complete(&conn, &data).await)
+}
@@ -0,0 +1,298 @@
+use log::LevelFilter;
+use rocket::fairing::AdHoc;
+use rocket::{Build, Rocket};
+use simple_logger::SimpleLogger;
+use crate::admin;
+use crate::db;
+use crate::device;
+use crate::health;
+use crate::idempotency;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::openapi;
+use crate::producer;
+use crate::quota;
+use crate::reactor;
+use crate::sweeper;
+use crate::version;
+
+/// A set of routes which can be mounted independently of the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    /// Registering producers along with storing and reading their data under `/v1/producer`.
+    Producer,
+    /// Registering reactors and queueing their actions under `/v1/reactor`.
+    Reactor,
+    /// Registering a producer and a reactor together as one device under `/v1/device`.
+    Device,
+    /// The deep health check under `/v1/health` and the version of the instance at `/v1/version`.
+    Health,
+    /// The OpenAPI document at `/v1/openapi.json`.
+    OpenApi,
+    /// The routes for operators under `/v1/admin`. They need the `admin_token` from the config.
+    Admin,
+}
+
+impl RouteGroup {
+    /// Every route group. A server mounts all of them unless it's configured otherwise.
+    pub const ALL: [RouteGroup; 6] = [RouteGroup::Producer, RouteGroup::Reactor, RouteGroup::Device, RouteGroup::Health, RouteGroup::OpenApi, RouteGroup::Admin];
+}
+
+///
+/// Configures a Conductor server and builds the Rocket instance which runs it. Anything which isn't
+/// configured here is read from the Rocket config (`Rocket.toml` and `ROCKET_` environment
+/// variables) as usual. This lets Conductor be embedded in a larger binary.
+///
+#[derive(Debug, Clone)]
+pub struct ConductorServer {
+    log_level: Option<LevelFilter>,
+    database_url: Option<String>,
+    quota_period: Option<u64>,
+    warm_up_connections: Option<usize>,
+    route_groups: Vec<RouteGroup>,
+}
+
+impl Default for ConductorServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConductorServer {
+    /// A server which mounts every route group and takes the rest of its config from Rocket.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            log_level: None,
+            database_url: None,
+            quota_period: None,
+            warm_up_connections: None,
+            route_groups: RouteGroup::ALL.to_vec(),
+        }
+    }
+
+    ///
+    /// Installs a logger at this level when the server is built. Without it no logger is installed
+    /// so that a binary embedding Conductor can install its own.
+    ///
+    #[must_use]
+    pub const fn with_log_level(mut self, log_level: LevelFilter) -> Self {
+        self.log_level = Some(log_level);
+        self
+    }
+
+    /// The url of the QuestDB postgres endpoint. This overrides `databases.quest_db.url`.
+    #[must_use]
+    pub fn with_database_url(mut self, database_url: String) -> Self {
+        self.database_url = Some(database_url);
+        self
+    }
+
+    /// The length of a quota period in seconds. This overrides `quota_period`.
+    #[must_use]
+    pub const fn with_quota_period(mut self, seconds: u64) -> Self {
+        self.quota_period = Some(seconds);
+        self
+    }
+
+    ///
+    /// The number of database connections to open and validate while the server ignites so that the pool
+    /// is hot before it serves traffic. This overrides `warm_up_connections`.
+    ///
+    #[must_use]
+    pub const fn with_warm_up_connections(mut self, connections: usize) -> Self {
+        self.warm_up_connections = Some(connections);
+        self
+    }
+
+    /// Only mounts the given route groups rather than all of them.
+    #[must_use]
+    pub fn with_route_groups(mut self, route_groups: &[RouteGroup]) -> Self {
+        self.route_groups = route_groups.to_vec();
+        self
+    }
+
+    #[must_use]
+    pub const fn get_log_level(&self) -> Option<LevelFilter> {
+        self.log_level
+    }
+
+    #[must_use]
+    pub fn get_database_url(&self) -> Option<&str> {
+        self.database_url.as_deref()
+    }
+
+    #[must_use]
+    pub const fn get_quota_period(&self) -> Option<u64> {
+        self.quota_period
+    }
+
+    #[must_use]
+    pub const fn get_warm_up_connections(&self) -> Option<usize> {
+        self.warm_up_connections
+    }
+
+    #[must_use]
+    pub fn get_route_groups(&self) -> &[RouteGroup] {
+        &self.route_groups
+    }
+
+    ///
+    /// Builds the Rocket instance. It still has to be launched. The database isn't connected to
+    /// until then.
+    ///
+    #[must_use]
+    pub fn build(self) -> Rocket<Build> {
+        if let Some(log_level) = self.log_level {
+            if let Err(err) = SimpleLogger::new().with_level(log_level).init() {
+                eprintln!("Couldn't install the logger as one is already installed. {}", err);
+            }
+        }
+        let mut figment = rocket::Config::figment();
+        if let Some(database_url) = &self.database_url {
+            figment = figment.merge(("databases.quest_db.url", database_url));
+        }
+        if let Some(quota_period) = self.quota_period {
+            figment = figment.merge(("quota_period", quota_period));
+        }
+        if let Some(warm_up_connections) = self.warm_up_connections {
+            figment = figment.merge(("warm_up_connections", warm_up_connections));
+        }
+        let mut rocket = rocket::custom(figment)
+            .register("/", catchers![producer::busy])
+            .attach(AdHoc::try_on_ignite("Validate database config", db::validate_database_config))
+            .attach(db::QuestDbConn::fairing())
+            .attach(db::acquire_timeout_stage())
+            .attach(AdHoc::try_on_ignite(
+                "Creat application tables",
+                db::create_app_schema,
+            ))
+            .attach(AdHoc::on_ignite("Warm up database connections", db::warm_up_pool))
+            .attach(quota::stage())
+            .attach(idempotency::stage())
+            .attach(producer::stage())
+            .attach(AdHoc::try_on_ignite("Uuid strategy", producer::manage_uuid_strategy))
+            .attach(sweeper::stage())
+            .attach(reactor::stage())
+            .attach(admin::stage());
+        for route_group in &self.route_groups {
+            rocket = mount(rocket, *route_group);
+        }
+        #[cfg(feature = "metrics")]
+        let rocket = rocket.attach(metrics::stage());
+        rocket
+    }
+}
+
+/// Mounts the json routes of a group along with the message pack routes when they're enabled.
+fn mount(rocket: Rocket<Build>, route_group: RouteGroup) -> Rocket<Build> {
+    match route_group {
+        RouteGroup::Producer => {
+            let rocket = rocket.mount(
+                "/",
+                routes![
+                    producer::register_json,
+                    producer::register_batch_json,
+                    producer::export_json,
+                    producer::adopt_json,
+                    producer::emit_json,
+                    producer::emit_batch_json,
+                    producer::emit_session_json,
+                    producer::emit_transaction_json,
+                    producer::query_json,
+                    producer::latest_json,
+                    producer::columns_json,
+                    producer::time_range_json,
+                    producer::explain_emit_json,
+                    producer::schema_json,
+                    producer::update_json,
+                    producer::swap_schema_json,
+                    producer::soft_delete_json,
+                    producer::truncate_json,
+                    producer::rollup_json,
+                    producer::delete_batch_json,
+                    producer::pause_json,
+                    producer::resume_json,
+                    producer::check_batch_json,
+                    producer::query_union_json,
+                    producer::emit_ordered_json,
+                    producer::check
+                ],
+            );
+            #[cfg(feature = "msgpack")]
+            let rocket = rocket.mount(
+                "/",
+                routes![
+                    producer::register_pack,
+                    producer::register_batch_pack,
+                    producer::export_pack,
+                    producer::adopt_pack,
+                    producer::emit_pack,
+                    producer::emit_batch_pack,
+                    producer::emit_session_pack,
+                    producer::emit_transaction_pack,
+                    producer::query_pack,
+                    producer::query_stream,
+                    producer::latest_pack,
+                    producer::columns_pack,
+                    producer::time_range_pack,
+                    producer::explain_emit_pack,
+                    producer::schema_pack,
+                    producer::update_pack,
+                    producer::swap_schema_pack,
+                    producer::soft_delete_pack,
+                    producer::truncate_pack,
+                    producer::rollup_pack,
+                    producer::delete_batch_pack,
+                    producer::pause_pack,
+                    producer::resume_pack,
+                    producer::check_batch_pack,
+                    producer::query_union_pack,
+                    producer::emit_ordered_pack
+                ],
+            );
+            rocket
+        }
+        RouteGroup::Reactor => {
+            let rocket = rocket.mount(
+                "/",
+                routes![
+                    reactor::register_json,
+                    reactor::enqueue_json,
+                    reactor::pending_json,
+                    reactor::result_json
+                ],
+            );
+            #[cfg(feature = "msgpack")]
+            let rocket = rocket.mount(
+                "/",
+                routes![
+                    reactor::register_pack,
+                    reactor::enqueue_pack,
+                    reactor::pending_pack,
+                    reactor::result_pack
+                ],
+            );
+            rocket
+        }
+        RouteGroup::Device => {
+            let rocket = rocket.mount("/", routes![device::register_json]);
+            #[cfg(feature = "msgpack")]
+            let rocket = rocket.mount("/", routes![device::register_pack]);
+            rocket
+        }
+        RouteGroup::Health => {
+            let rocket = rocket.mount("/", routes![health::deep_json, version::version_json]);
+            #[cfg(feature = "msgpack")]
+            let rocket = rocket.mount("/", routes![health::deep_pack, version::version_pack]);
+            rocket
+        }
+        RouteGroup::OpenApi => rocket.mount("/", routes![openapi::openapi]),
+        RouteGroup::Admin => {
+            let rocket = rocket.mount("/", routes![admin::stats_json, admin::reconcile_json]);
+            #[cfg(feature = "msgpack")]
+            let rocket = rocket.mount("/", routes![admin::stats_pack, admin::reconcile_pack]);
+            rocket
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Helpers for building the sql which is sent to QuestDB.
+
+use conductor_common::producer::Registration;
+use conductor_common::schema::SchemaMigration;
+
+/// Quotes an identifier such as a table or column name so that it can be interpolated into sql.
+/// Double quotes within the name are escaped by doubling them so the name can't end the quoted
+/// identifier early.
+#[must_use]
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Builds an insert of one row into a table with a numbered parameter for each column in the order
+/// they're given.
+///
+/// # Errors
+/// Returns a description of the problem when there are no columns.
+pub fn get_insert_sql(table_name: &str, column_names: &[&String]) -> Result<String, String> {
+    if column_names.is_empty() {
+        return Err("Insert Sql must have at least one colum but there were none".to_string());
+    }
+    let mut column_iter = column_names.iter();
+    let mut columns = quote_ident(column_iter.next().unwrap());
+    for column_name in column_iter {
+        columns = columns + ", " + &quote_ident(column_name);
+    }
+
+    let mut values_str = String::from("$1");
+    for i in 2..=column_names.len() {
+        values_str.push_str(format!(",${}", i).as_str());
+    }
+    Ok(format!(
+        "INSERT INTO {} ({}) VALUES ({});",
+        quote_ident(table_name), columns, values_str
+    ))
+}
+
+/// Builds the sql which creates the table of a producer. Time series get the designated timestamp
+/// column `ts` first, plain tables only have the columns of the schema. The tombstone column comes last.
+/// Indexed columns are created as indexed symbols.
+#[must_use]
+pub fn generate_create_table_sql(registration: &Registration, table_name: &str) -> String {
+    //     CREATE TABLE my_table(symb SYMBOL, price DOUBLE, ts TIMESTAMP, s STRING) timestamp(ts);
+    let mut columns = Vec::new();
+    if registration.is_time_series() {
+        columns.push(String::from("ts TIMESTAMP"));
+    }
+    for (col_name, col_type) in registration.ordered_columns() {
+        if registration.is_indexed(col_name) {
+            // QuestDB only indexes symbols. Validation has already checked the column is a string.
+            columns.push(quote_ident(col_name) + " symbol INDEX");
+        } else {
+            columns.push(quote_ident(col_name) + " " + col_type.to_quest_type_str());
+        }
+    }
+    if let Some(tombstone_column) = registration.get_tombstone_column() {
+        columns.push(quote_ident(tombstone_column) + " boolean");
+    }
+    let mut sql = format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_ident(table_name), columns.join(", "));
+    if registration.is_time_series() {
+        sql += " timestamp(ts)";
+    }
+    sql += ";";
+    sql
+}
+
+/// Builds the statements which migrate the table of a producer, adding columns before dropping any.
+#[must_use]
+pub fn generate_migration_sql(table_name: &str, migration: &SchemaMigration) -> Vec<String> {
+    let table_name = quote_ident(table_name);
+    let added = migration.added.iter()
+        .map(|(column, data_type)| format!("ALTER TABLE {} ADD COLUMN {} {};", table_name, quote_ident(column), data_type.to_quest_type_str()));
+    let dropped = migration.dropped.iter()
+        .map(|column| format!("ALTER TABLE {} DROP COLUMN {};", table_name, quote_ident(column)));
+    added.chain(dropped).collect()
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+use rocket::fairing::AdHoc;
+use crate::db::{AcquireTimeout, QuestDbConn};
+use crate::producer;
+
+///
+/// Returns true if a producer with a ttl has gone too long without emitting and should be deleted.
+/// The ttl counts from the last emit or from the registration if the producer never emitted.
+///
+/// # Arguments
+/// * `ttl_seconds` : The ttl the producer was registered with
+/// * `registered_at` : When the producer was registered in microseconds since the unix epoch
+/// * `last_emit` : The timestamp of the latest row of the producer in microseconds. None if it has no rows
+/// * `now` : The current time in microseconds since the unix epoch
+///
+#[must_use]
+pub fn is_expired(ttl_seconds: u64, registered_at: u64, last_emit: Option<u64>, now: u64) -> bool {
+    let last_active = last_emit.map_or(registered_at, |last_emit| last_emit.max(registered_at));
+    now.saturating_sub(last_active) >= ttl_seconds.saturating_mul(1_000_000)
+}
+
+///
+/// Deletes producers whose ttl has run out every `ttl_sweep_interval` seconds once the server has
+/// launched. Nothing is swept unless the interval is configured.
+///
+pub fn stage() -> AdHoc {
+    AdHoc::on_liftoff("Producer ttl sweeper", |rocket| Box::pin(async move {
+        let interval_secs: u64 = rocket.figment().extract_inner("ttl_sweep_interval").unwrap_or(0);
+        if interval_secs == 0 {
+            return;
+        }
+        let pool = match QuestDbConn::pool(rocket) {
+            Some(pool) => pool.clone(),
+            None => {
+                log::error!("The database isn't attached so expired producers won't be swept");
+                return;
+            }
+        };
+        let acquire_timeout = rocket.state::<AcquireTimeout>().copied().unwrap_or_default();
+        log::info!("Sweeping expired producers every {} seconds", interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                match QuestDbConn::from_pool(&pool, acquire_timeout).await {
+                    Some(conn) => {
+                        let swept = producer::sweep_expired(&conn).await;
+                        if !swept.is_empty() {
+                            log::info!("Swept expired producers {}", swept.join(", "));
+                        }
+                    }
+                    None => log::warn!("No database connection became available to sweep expired producers"),
+                }
+            }
+        });
+    }))
+}
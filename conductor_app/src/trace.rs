@@ -0,0 +1,67 @@
+//! Times the phases of handling a request (validation, writing to the database and serialising the
+//! response). With the `tracing` feature each phase is a span carrying the producer it belongs to so
+//! that a tracing subscriber can see where the time goes. Without it the duration of each phase is
+//! logged at debug level.
+
+use std::future::Future;
+use rocket::response::{self, Responder};
+use rocket::Request;
+
+///
+/// Runs `future` as the phase `name` of a request for `producer`. `producer` is the uuid of the
+/// producer or its name when it doesn't have a uuid yet.
+///
+#[cfg(feature = "tracing")]
+pub async fn phase<F: Future>(name: &'static str, producer: &str, future: F) -> F::Output {
+    use tracing::Instrument;
+    future.instrument(tracing::info_span!("request_phase", phase = name, producer)).await
+}
+
+///
+/// Runs `future` as the phase `name` of a request for `producer`. `producer` is the uuid of the
+/// producer or its name when it doesn't have a uuid yet.
+///
+#[cfg(not(feature = "tracing"))]
+pub async fn phase<F: Future>(name: &'static str, producer: &str, future: F) -> F::Output {
+    let start = std::time::Instant::now();
+    let output = future.await;
+    log::debug!("{} for producer {} took {:?}", name, producer, start.elapsed());
+    output
+}
+
+/// The same as `phase` for work which doesn't need to wait.
+#[cfg(feature = "tracing")]
+pub fn phase_sync<T>(name: &'static str, producer: &str, work: impl FnOnce() -> T) -> T {
+    tracing::info_span!("request_phase", phase = name, producer).in_scope(work)
+}
+
+/// The same as `phase` for work which doesn't need to wait.
+#[cfg(not(feature = "tracing"))]
+pub fn phase_sync<T>(name: &'static str, producer: &str, work: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let output = work();
+    log::debug!("{} for producer {} took {:?}", name, producer, start.elapsed());
+    output
+}
+
+/// A response whose serialisation is timed as the `serialise` phase.
+pub struct Traced<R> {
+    producer: String,
+    inner: R,
+}
+
+impl<R> Traced<R> {
+    pub fn new(producer: &str, inner: R) -> Self {
+        Self {
+            producer: producer.to_string(),
+            inner,
+        }
+    }
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Traced<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        let Self { producer, inner } = self;
+        phase_sync("serialise", &producer, || inner.respond_to(request))
+    }
+}
@@ -0,0 +1,22 @@
+//! Tells clients which version of Conductor the instance runs so they can avoid features it doesn't support.
+
+use rocket::serde::json::Json;
+#[cfg(feature = "msgpack")]
+use rocket::serde::msgpack::MsgPack;
+use conductor_common::version as version_com;
+
+/// Describes this instance.
+pub fn version_info() -> version_com::VersionInfo {
+    version_com::VersionInfo::new(version_com::VERSION)
+}
+
+#[cfg(feature = "msgpack")]
+#[get("/v1/version", format = "msgpack")]
+pub fn version_pack() -> MsgPack<version_com::VersionInfo> {
+    MsgPack(version_info())
+}
+
+#[get("/v1/version", format = "json")]
+pub fn version_json() -> Json<version_com::VersionInfo> {
+    Json(version_info())
+}
@@ -0,0 +1,173 @@
+//! Types used by the administrative routes of a Conductor instance.
+
+use serde::{Deserialize, Serialize};
+use crate::error;
+use crate::health::DeepHealthReport;
+use crate::schema;
+
+/// The number of rows stored by a producer and roughly how much space they take up.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct TableStats {
+    pub uuid: String,
+    pub rows: u64,
+    /// An estimate from the column types of the producer. See `estimate_table_bytes`.
+    pub estimated_bytes: u64,
+}
+
+/// An overview of the registry of a Conductor instance. The tables are only counted when they're
+/// asked for as it reads every table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct RegistryStats {
+    pub error: error::ConductorError,
+    /// The number of registered producers
+    pub producers: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<Vec<TableStats>>,
+    /// The number of rows across every table. Only set when the tables were counted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_rows: Option<u64>,
+    /// The estimated size of every table. Only set when the tables were counted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_bytes: Option<u64>,
+}
+
+impl RegistryStats {
+    /// The stats of a registry with this many producers whose tables weren't counted.
+    #[must_use]
+    pub const fn new(producers: u64) -> Self {
+        Self {
+            error: error::ConductorError::NoError,
+            producers,
+            tables: None,
+            total_rows: None,
+            estimated_bytes: None,
+        }
+    }
+
+    /// Adds the stats of each table along with their totals.
+    #[must_use]
+    pub fn with_tables(mut self, tables: Vec<TableStats>) -> Self {
+        self.total_rows = Some(tables.iter().map(|table| table.rows).sum());
+        self.estimated_bytes = Some(tables.iter().map(|table| table.estimated_bytes).sum());
+        self.tables = Some(tables);
+        self
+    }
+}
+
+/// Estimates how many bytes the rows of a producer take up from the types of its columns and its
+/// designated timestamp. Indexes, partitions and the real length of strings and binary values aren't
+/// taken into account.
+#[must_use]
+pub fn estimate_table_bytes(schema: &schema::Schema, rows: u64) -> u64 {
+    let row_bytes = schema::DataTypes::Time.approx_byte_size() + schema.values().map(schema::DataTypes::approx_byte_size).sum::<u64>();
+    row_bytes.saturating_mul(rows)
+}
+
+/// Reads the token from the value of an `Authorization` header which uses the bearer scheme.
+#[must_use]
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    if token.is_empty() {
+        None
+    } else {
+        Some(token)
+    }
+}
+
+/// What a reconcile does with tables which don't belong to a registered producer.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub enum OrphanTablePolicy {
+    /// Orphan tables are left as they are
+    #[default]
+    Keep,
+    /// Each orphan table is registered as a producer with the schema read from its columns
+    Adopt,
+    /// Each orphan table is dropped along with its rows
+    Drop,
+}
+
+impl std::str::FromStr for OrphanTablePolicy {
+    type Err = error::ConductorError;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy.to_ascii_lowercase().as_str() {
+            "keep" => Ok(OrphanTablePolicy::Keep),
+            "adopt" => Ok(OrphanTablePolicy::Adopt),
+            "drop" => Ok(OrphanTablePolicy::Drop),
+            _ => Err(error::ConductorError::InvalidData(format!("{} isn't a supported orphan table policy. Use keep, adopt or drop.", policy))),
+        }
+    }
+}
+
+/// A change made by a reconcile to bring the registry and the tables of the database back in line.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub enum ReconcileAction {
+    /// Register the orphan table as a producer
+    Adopt(String),
+    /// Drop the orphan table
+    Drop(String),
+    /// Create the missing table of a registered producer again from its registered schema
+    Recreate(String),
+}
+
+///
+/// Plans the actions which resolve the inconsistencies found by a deep health check. Orphan tables
+/// are handled according to `orphans` and the tables of producers are only created again when
+/// `recreate_missing` is set. Tables are handled in the order the report lists them.
+///
+#[must_use]
+pub fn plan_reconcile(report: &DeepHealthReport, orphans: OrphanTablePolicy, recreate_missing: bool) -> Vec<ReconcileAction> {
+    let orphan_actions = report.orphan_tables.iter().filter_map(|table| match orphans {
+        OrphanTablePolicy::Keep => None,
+        OrphanTablePolicy::Adopt => Some(ReconcileAction::Adopt(table.clone())),
+        OrphanTablePolicy::Drop => Some(ReconcileAction::Drop(table.clone())),
+    });
+    let missing_actions = report.missing_tables.iter()
+        .filter(|_| recreate_missing)
+        .map(|uuid| ReconcileAction::Recreate(uuid.clone()));
+    orphan_actions.chain(missing_actions).collect()
+}
+
+/// A reconcile action which couldn't be carried out.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ReconcileFailure {
+    pub action: ReconcileAction,
+    pub error: error::ConductorError,
+}
+
+/// The response to a reconcile. Every planned action is either applied or failed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ReconcileReport {
+    pub error: error::ConductorError,
+    pub applied: Vec<ReconcileAction>,
+    pub failed: Vec<ReconcileFailure>,
+}
+
+impl ReconcileReport {
+    /// A reconcile which couldn't start, usually because the registry couldn't be read.
+    #[must_use]
+    pub const fn failed(error: error::ConductorError) -> Self {
+        Self {
+            error,
+            applied: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// Returns true if the reconcile ran and every action was applied.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.error == error::ConductorError::NoError && self.failed.is_empty()
+    }
+}
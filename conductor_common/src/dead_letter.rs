@@ -0,0 +1,185 @@
+//! Storage for emits which couldn't be delivered to Conductor so that they can be replayed once the
+//! server is reachable again. Useful for unattended devices which shouldn't lose data during an outage.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use crate::producer::Error;
+
+/// An emit which couldn't be delivered.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DeadLetter {
+    pub uuid: String,
+    /// When the emit was attempted in microseconds since the unix epoch. The row is replayed with
+    /// this timestamp rather than the time it was finally delivered. Dead letters stored by older
+    /// versions don't have one and are given the time they're replayed.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    pub data: HashMap<String, serde_json::Value>,
+}
+
+/// What happened to an emit which may have been stored as a dead letter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    /// The emit was delivered to Conductor.
+    Sent,
+    /// The emit couldn't be delivered and was stored in the dead letter sink to be replayed later.
+    DeadLettered,
+}
+
+/// Somewhere to keep emits which couldn't be delivered until they're replayed.
+pub trait DeadLetterSink {
+    /// Stores an emit which couldn't be delivered.
+    ///
+    /// # Errors
+    /// Implementation specific. The dead letter is lost if an error is returned.
+    fn store(&mut self, dead_letter: DeadLetter) -> Result<(), Error>;
+
+    /// Returns every stored dead letter in the order they were stored without removing them.
+    ///
+    /// # Errors
+    /// Implementation specific.
+    fn load(&mut self) -> Result<Vec<DeadLetter>, Error>;
+
+    /// Replaces every stored dead letter with `dead_letters`. Replaying calls this once it's done
+    /// with the dead letters which still weren't delivered.
+    ///
+    /// # Errors
+    /// Implementation specific. The stored dead letters must be left as they were if an error is
+    /// returned.
+    fn replace(&mut self, dead_letters: Vec<DeadLetter>) -> Result<(), Error>;
+}
+
+/// Keeps dead letters in memory. They're lost when the sink is dropped.
+#[derive(Debug, Default)]
+pub struct MemoryDeadLetterSink {
+    dead_letters: Vec<DeadLetter>,
+}
+
+impl MemoryDeadLetterSink {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            dead_letters: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.dead_letters.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.dead_letters.is_empty()
+    }
+}
+
+impl DeadLetterSink for MemoryDeadLetterSink {
+    fn store(&mut self, dead_letter: DeadLetter) -> Result<(), Error> {
+        self.dead_letters.push(dead_letter);
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Vec<DeadLetter>, Error> {
+        Ok(self.dead_letters.clone())
+    }
+
+    fn replace(&mut self, dead_letters: Vec<DeadLetter>) -> Result<(), Error> {
+        self.dead_letters = dead_letters;
+        Ok(())
+    }
+}
+
+/// Keeps dead letters in a file so that they survive restarts. Each dead letter is stored as one
+/// line of json.
+#[derive(Debug, Clone)]
+pub struct FileDeadLetterSink {
+    path: PathBuf,
+}
+
+impl FileDeadLetterSink {
+    /// Creates a sink which stores dead letters in the file at `path`. The file is created when the
+    /// first dead letter is stored.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn get_path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+impl DeadLetterSink for FileDeadLetterSink {
+    fn store(&mut self, dead_letter: DeadLetter) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(&dead_letter).map_err(Error::JsonSerialisationFailure)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| Error::DeadLetterFailure(format!("Couldn't open {}. {}", self.path.display(), err)))?;
+        file.write_all(&line)
+            .map_err(|err| Error::DeadLetterFailure(format!("Couldn't write to {}. {}", self.path.display(), err)))
+    }
+
+    fn load(&mut self) -> Result<Vec<DeadLetter>, Error> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(Error::DeadLetterFailure(format!("Couldn't open {}. {}", self.path.display(), err))),
+        };
+        let mut dead_letters = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|err| Error::DeadLetterFailure(format!("Couldn't read {}. {}", self.path.display(), err)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            dead_letters.push(serde_json::from_str(&line).map_err(Error::JsonDeserializationFailure)?);
+        }
+        Ok(dead_letters)
+    }
+
+    /// The new dead letters are written to a temporary file next to the sink which is then moved over
+    /// it so that a failure part way through doesn't lose the dead letters that were stored.
+    fn replace(&mut self, dead_letters: Vec<DeadLetter>) -> Result<(), Error> {
+        let mut contents = Vec::new();
+        for dead_letter in &dead_letters {
+            serde_json::to_writer(&mut contents, dead_letter).map_err(Error::JsonSerialisationFailure)?;
+            contents.push(b'\n');
+        }
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        let mut file = File::create(&temp_path)
+            .map_err(|err| Error::DeadLetterFailure(format!("Couldn't create {}. {}", temp_path.display(), err)))?;
+        file.write_all(&contents)
+            .and_then(|()| file.sync_all())
+            .map_err(|err| Error::DeadLetterFailure(format!("Couldn't write to {}. {}", temp_path.display(), err)))?;
+        std::fs::rename(&temp_path, &self.path)
+            .map_err(|err| Error::DeadLetterFailure(format!("Couldn't replace {}. {}", self.path.display(), err)))
+    }
+}
+
+/// Returns true if an emit which failed with this error could succeed later, which makes it worth
+/// keeping as a dead letter. Emits which the server rejected because of their contents won't.
+#[must_use]
+pub fn is_transient(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::NetworkError(_)
+            | Error::Unreachable(_)
+            | Error::UnexpectedStatus(_)
+            | Error::MsgPackDeserializationFailure(_)
+            | Error::ConductorError(crate::error::ConductorError::Busy(_))
+            | Error::ConductorError(crate::error::ConductorError::InternalError(_))
+            | Error::ConductorError(crate::error::ConductorError::QuotaExceeded(_))
+            | Error::ConductorError(crate::error::ConductorError::Paused(_))
+    )
+}
@@ -0,0 +1,96 @@
+//! Registers a device with Conductor. A device is a producer and a reactor registered together
+//! under one uuid so the data it emits and the actions queued for it share the same id.
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+use crate::error;
+use crate::format;
+use crate::producer::{Error, Registration, RegistrationResult};
+use crate::reactor::{ActionRegistration, ReactorRegistration};
+
+/// Contains the information required to register a device with a Conductor server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct DeviceRegistration {
+    producer: Registration,
+    actions: Vec<ActionRegistration>,
+}
+
+impl DeviceRegistration {
+    /// Creates a device from the registration of its producer half. The name and custom id of the
+    /// producer are used for the whole device.
+    #[must_use]
+    pub const fn new(producer: Registration) -> Self {
+        Self {
+            producer,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Adds an action which the device can perform.
+    #[must_use]
+    pub fn with_action(mut self, action: ActionRegistration) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_producer(&self) -> &Registration {
+        &self.producer
+    }
+
+    #[must_use]
+    pub fn get_actions(&self) -> &[ActionRegistration] {
+        &self.actions
+    }
+
+    /// The reactor half of the device registered under `uuid`.
+    #[must_use]
+    pub fn reactor_registration(&self, uuid: Option<String>) -> ReactorRegistration {
+        self.actions.iter().cloned().fold(
+            ReactorRegistration::new(self.producer.get_name().to_string(), uuid),
+            ReactorRegistration::with_action,
+        )
+    }
+
+    ///
+    /// Checks both halves of the registration the same way the server does before it's stored.
+    ///
+    /// # Errors
+    /// The errors of `Registration::validate` and `ReactorRegistration::validate`.
+    ///
+    pub fn validate(&self) -> Result<(), error::ConductorError> {
+        self.producer.validate()?;
+        self.reactor_registration(self.producer.get_custom_id().map(str::to_string)).validate()
+    }
+}
+
+///
+/// Serialises a device registration and gives the url it's sent to.
+///
+/// # Errors
+/// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+/// * `MsgPackSerialisationFailure`: Produced when the registration cannot be serialised to the message pack format.
+///
+pub fn generate_registration_data(registration: &DeviceRegistration, conductor_domain: &Url) -> Result<(Vec<u8>, Url), Error> {
+    let url = match conductor_domain.join("/v1/device/register") {
+        Ok(u) => u,
+        Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+    };
+    let payload = match rmp_serde::to_vec_named(registration) {
+        Ok(p) => p,
+        Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+    };
+    Ok((payload, url))
+}
+
+pub(crate) fn decode_registration(body: &[u8]) -> Result<String, Error> {
+    let result: RegistrationResult = format::decode_response(body)?;
+    match (result.error, result.uuid) {
+        (error::ConductorError::NoError, Some(uuid)) => Ok(uuid),
+        (error::ConductorError::NoError, None) => Err(Error::ConductorError(error::ConductorError::InternalError(
+            "The device was registered but the server didn't send its uuid".to_string()
+        ))),
+        (err, _) => Err(Error::ConductorError(err)),
+    }
+}
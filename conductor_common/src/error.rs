@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 
 /// Errors produced by the Conductor Instance
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub enum ConductorError {
     /// Indicates that there was no error. This exists to be more compatible with being sent over
     /// the wire to clients which may not have proper support for options.
@@ -14,7 +15,7 @@ pub enum ConductorError {
     NoMembers(String),
     /// Indicates that there was an issue with at least one of the columns in the schema using illegal characters or formatting
     InvalidColumnNames(String),
-    /// Indicates the schema is too large (> 2_147_483_647)
+    /// Indicates the schema has more than `producer::MAX_COLUMNS` columns
     TooManyColumns(String),
     /// A generic Conductor error
     InternalError(String),
@@ -28,6 +29,24 @@ pub enum ConductorError {
     InvalidData(String),
     /// The schema sent in an emit doesn't match the one which was registered.
     InvalidSchema(String),
+    /// The table for the producer already exists in the database. This usually means that a custom id was reused for a different producer.
+    TableExists(String),
+    /// The Conductor instance couldn't get a database connection in time to handle the request. The request can be retried later.
+    Busy(String),
+    /// A producer with the custom id is already registered with a different schema. Contains the columns which differ. The producer has to be migrated rather than registered again.
+    SchemaConflict(Vec<String>),
+    /// The producer has written as many rows as its quota allows for the current period. Emits are accepted again once the period resets.
+    QuotaExceeded(String),
+    /// Ingestion is paused for the producer so its emits are rejected. Emits are accepted again once it's resumed.
+    Paused(String),
+    /// The version of an action the caller expected doesn't match the version the reactor registered it with.
+    VersionMismatch(String),
+    /// The data table of the producer was dropped outside of Conductor. The producer has to be registered again before it can emit.
+    TableMissing(String),
+    /// The batch has more rows than the `max_batch_rows` the Conductor instance allows. None of its rows were stored.
+    BatchTooLarge(String),
+    /// The request was made with a wire protocol version the Conductor instance doesn't support. The client or the instance has to be upgraded.
+    UnsupportedProtocol(String),
 }
 
 impl std::error::Error for ConductorError {}
@@ -46,6 +65,15 @@ impl fmt::Display for ConductorError {
             ConductorError::Unregistered(message) => write!(f, "Unregistered: {}", message),
             ConductorError::InvalidData(message) => write!(f, "InvalidData: {}", message),
             ConductorError::InvalidSchema(message) => write!(f, "InvalidSchema: {}", message),
+            ConductorError::TableExists(message) => write!(f, "TableExists: {}", message),
+            ConductorError::Busy(message) => write!(f, "Busy: {}", message),
+            ConductorError::SchemaConflict(columns) => write!(f, "SchemaConflict: The registered schema differs in the columns {}", columns.join(", ")),
+            ConductorError::QuotaExceeded(message) => write!(f, "QuotaExceeded: {}", message),
+            ConductorError::Paused(message) => write!(f, "Paused: {}", message),
+            ConductorError::VersionMismatch(message) => write!(f, "VersionMismatch: {}", message),
+            ConductorError::TableMissing(message) => write!(f, "TableMissing: {}", message),
+            ConductorError::BatchTooLarge(message) => write!(f, "BatchTooLarge: {}", message),
+            ConductorError::UnsupportedProtocol(message) => write!(f, "UnsupportedProtocol: {}", message),
         }
     }
 }
\ No newline at end of file
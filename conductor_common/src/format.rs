@@ -0,0 +1,287 @@
+//! The encodings Conductor can use for request and response bodies.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::error::ConductorError;
+use crate::producer::Error;
+
+/// An encoding of a request or response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    Json,
+    MsgPack,
+}
+
+impl WireFormat {
+    /// Returns the format for a media type such as `application/json`. Parameters are ignored.
+    #[must_use]
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        if essence.eq_ignore_ascii_case("application/json") {
+            Some(Self::Json)
+        } else if essence.eq_ignore_ascii_case("application/msgpack") || essence.eq_ignore_ascii_case("application/x-msgpack") {
+            Some(Self::MsgPack)
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub const fn media_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MsgPack => "application/msgpack",
+        }
+    }
+
+    ///
+    /// Encodes a value in this format. Msgpack values are encoded with their field names the same
+    /// way request bodies are.
+    ///
+    /// # Errors
+    /// * `JsonSerialisationFailure`: The value couldn't be encoded as json.
+    /// * `MsgPackSerialisationFailure`: The value couldn't be encoded as msgpack.
+    ///
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Json => serde_json::to_vec(value).map_err(Error::JsonSerialisationFailure),
+            Self::MsgPack => rmp_serde::to_vec_named(value).map_err(Error::MsgPackSerialisationFailure),
+        }
+    }
+
+    ///
+    /// Chooses the format of a response from the `Content-Type` and `Accept` headers of the request.
+    ///
+    /// The response uses the format the request was sent in unless the `Accept` header prefers the
+    /// other one. Quality values are honoured and wildcards stand for the request's format. When the
+    /// request's format can't be determined json is used.
+    ///
+    #[must_use]
+    pub fn negotiate(content_type: Option<&str>, accept: Option<&str>) -> Self {
+        let request_format = content_type.and_then(Self::from_media_type).unwrap_or(Self::Json);
+        let accept = match accept {
+            Some(accept) => accept,
+            None => return request_format,
+        };
+        let mut best: Option<(Self, f32)> = None;
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';');
+            let media_type = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .find_map(|quality| quality.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality <= 0.0 {
+                continue;
+            }
+            let format = match media_type {
+                "*/*" | "application/*" => request_format,
+                _ => match Self::from_media_type(media_type) {
+                    Some(format) => format,
+                    None => continue,
+                },
+            };
+            // on a tie the request's format wins
+            let better = match best {
+                None => true,
+                Some((_, best_quality)) => quality > best_quality || (quality >= best_quality && format == request_format),
+            };
+            if better {
+                best = Some((format, quality));
+            }
+        }
+        best.map_or(request_format, |(format, _)| format)
+    }
+}
+
+impl fmt::Display for WireFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.media_type())
+    }
+}
+
+/// The most elements a single msgpack array or map in a request body may declare. This bounds the
+/// number of rows in a batch and the number of columns in an emit or registration.
+pub const MAX_MSGPACK_LENGTH: usize = 65_536;
+
+fn read_msgpack_length(bytes: &[u8], position: &mut usize, width: usize) -> Result<usize, ConductorError> {
+    let end = position.checked_add(width)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| ConductorError::InvalidData("The msgpack payload ends in the middle of a length".to_string()))?;
+    let length = bytes[*position..end].iter().fold(0_usize, |length, byte| (length << 8) | usize::from(*byte));
+    *position = end;
+    Ok(length)
+}
+
+///
+/// Walks the markers of a msgpack encoded value without decoding it and checks the lengths they
+/// declare. Nothing is allocated so a small payload declaring an enormous array or map is rejected
+/// before a decoder reserves space for it.
+///
+/// An array or map may declare at most `max_length` elements and, as every value takes at least one
+/// byte, never more values than there are bytes left in the payload. Strings, binary and extension
+/// values may not declare more bytes than are left either. Violations are reported as `InvalidData`.
+///
+pub fn check_msgpack_lengths(bytes: &[u8], max_length: usize) -> Result<(), ConductorError> {
+    let mut position = 0;
+    // the number of values which are still to be read
+    let mut pending: usize = 1;
+    while pending > 0 {
+        let marker = *bytes.get(position)
+            .ok_or_else(|| ConductorError::InvalidData("The msgpack payload ends before all of its values".to_string()))?;
+        position += 1;
+        pending -= 1;
+        // the bytes of data following the marker, the number of elements and whether they're map entries
+        let (data, elements, map) = match marker {
+            0x00..=0x7f | 0xc0 | 0xc2 | 0xc3 | 0xe0..=0xff => (0, 0, false),
+            0x80..=0x8f => (0, usize::from(marker & 0x0f), true),
+            0x90..=0x9f => (0, usize::from(marker & 0x0f), false),
+            0xa0..=0xbf => (usize::from(marker & 0x1f), 0, false),
+            0xc4 | 0xd9 => (read_msgpack_length(bytes, &mut position, 1)?, 0, false),
+            0xc5 | 0xda => (read_msgpack_length(bytes, &mut position, 2)?, 0, false),
+            0xc6 | 0xdb => (read_msgpack_length(bytes, &mut position, 4)?, 0, false),
+            // extensions are followed by their type before the data
+            0xc7 => (read_msgpack_length(bytes, &mut position, 1)? + 1, 0, false),
+            0xc8 => (read_msgpack_length(bytes, &mut position, 2)? + 1, 0, false),
+            0xc9 => (read_msgpack_length(bytes, &mut position, 4)? + 1, 0, false),
+            0xcc | 0xd0 => (1, 0, false),
+            0xcd | 0xd1 => (2, 0, false),
+            0xca | 0xce | 0xd2 => (4, 0, false),
+            0xcb | 0xcf | 0xd3 => (8, 0, false),
+            0xd4 => (2, 0, false),
+            0xd5 => (3, 0, false),
+            0xd6 => (5, 0, false),
+            0xd7 => (9, 0, false),
+            0xd8 => (17, 0, false),
+            0xdc => (0, read_msgpack_length(bytes, &mut position, 2)?, false),
+            0xdd => (0, read_msgpack_length(bytes, &mut position, 4)?, false),
+            0xde => (0, read_msgpack_length(bytes, &mut position, 2)?, true),
+            0xdf => (0, read_msgpack_length(bytes, &mut position, 4)?, true),
+            0xc1 => return Err(ConductorError::InvalidData("The msgpack payload contains the reserved marker 0xc1".to_string())),
+        };
+        if elements > max_length {
+            return Err(ConductorError::InvalidData(format!(
+                "A msgpack {} declares {} elements which is more than the limit of {}",
+                if map { "map" } else { "array" }, elements, max_length
+            )));
+        }
+        pending = pending.saturating_add(if map { elements.saturating_mul(2) } else { elements });
+        position = position.saturating_add(data);
+        if position > bytes.len() || pending > bytes.len() - position {
+            return Err(ConductorError::InvalidData("The msgpack payload declares more data than it contains".to_string()));
+        }
+    }
+    Ok(())
+}
+
+///
+/// Decodes a msgpack response body. Requests the server rejects before a route handles them, such as
+/// when no database connection is available, are answered with only a `ConductorError` which is
+/// returned as the error.
+///
+/// # Errors
+/// * `ConductorError`: The body was a bare error.
+/// * `MsgPackDeserializationFailure`: The body was neither a `T` nor an error. Holds why it wasn't a `T`.
+///
+pub fn decode_response<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    match rmp_serde::from_read_ref(body) {
+        Ok(value) => Ok(value),
+        Err(err) => match rmp_serde::from_read_ref::<_, ConductorError>(body) {
+            Ok(error) => Err(Error::ConductorError(error)),
+            Err(_) => Err(Error::MsgPackDeserializationFailure(err)),
+        },
+    }
+}
+
+/// The media type of query results sent as a stream of msgpack rows. See `write_msgpack_frame`.
+pub const MSGPACK_STREAM_MEDIA_TYPE: &str = "application/x-msgpack-stream";
+
+///
+/// Appends a value to a msgpack stream. Each value is written as its length in bytes as a big endian
+/// `u32` followed by the value encoded as named msgpack so the stream can be split without decoding it.
+///
+/// # Errors
+/// * `InternalError`: The value couldn't be encoded or is larger than `u32::MAX` bytes.
+///
+pub fn write_msgpack_frame<T: Serialize>(stream: &mut Vec<u8>, value: &T) -> Result<(), ConductorError> {
+    let encoded = rmp_serde::to_vec_named(value)
+        .map_err(|err| ConductorError::InternalError(format!("Couldn't encode a value of the msgpack stream. {}", err)))?;
+    let length = u32::try_from(encoded.len())
+        .map_err(|_| ConductorError::InternalError(format!("A value of the msgpack stream is {} bytes which is too large to frame", encoded.len())))?;
+    stream.extend_from_slice(&length.to_be_bytes());
+    stream.extend_from_slice(&encoded);
+    Ok(())
+}
+
+///
+/// Decodes the values of a msgpack stream written with `write_msgpack_frame` one at a time as they're
+/// read, so a large query doesn't have to be held in memory before the first row is used. The stream
+/// ends cleanly between values. It stops after the first error.
+///
+pub struct MsgPackFrames<R, T> {
+    reader: R,
+    finished: bool,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<R: Read, T: DeserializeOwned> MsgPackFrames<R, T> {
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            finished: false,
+            value: PhantomData,
+        }
+    }
+
+    /// Reads the length of the next value. None if the stream ended before it.
+    fn read_length(&mut self) -> Result<Option<u32>, std::io::Error> {
+        let mut prefix = [0_u8; 4];
+        let mut filled = 0;
+        while filled < prefix.len() {
+            match self.reader.read(&mut prefix[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Some(u32::from_be_bytes(prefix)))
+    }
+
+    fn read_value(&mut self, length: u32) -> Result<T, Error> {
+        // the buffer grows as the value is read rather than trusting the length up front
+        let mut encoded = Vec::new();
+        let read = (&mut self.reader).take(u64::from(length)).read_to_end(&mut encoded)
+            .map_err(|err| Error::GenericDeserializationFailure(Box::new(err)))?;
+        if read != length as usize {
+            return Err(Error::GenericDeserializationFailure(Box::new(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))));
+        }
+        rmp_serde::from_read_ref(&encoded).map_err(Error::MsgPackDeserializationFailure)
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for MsgPackFrames<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let value = match self.read_length() {
+            Ok(Some(length)) => self.read_value(length),
+            Ok(None) => {
+                self.finished = true;
+                return None;
+            }
+            Err(err) => Err(Error::GenericDeserializationFailure(Box::new(err))),
+        };
+        self.finished = value.is_err();
+        Some(value)
+    }
+}
@@ -0,0 +1,45 @@
+//! Types describing the health of a Conductor instance.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use crate::error;
+
+/// Describes whether the producers registered with Conductor are consistent with the tables that
+/// exist in the database.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct DeepHealthReport {
+    pub error: error::ConductorError,
+    /// The number of registered producers
+    pub producers: u64,
+    /// Tables which exist in the database but don't belong to a registered producer
+    pub orphan_tables: Vec<String>,
+    /// Registered producers whose table doesn't exist in the database
+    pub missing_tables: Vec<String>,
+}
+
+impl DeepHealthReport {
+    /// Compares the uuids of the registered producers against the names of the data tables in the
+    /// database. The results are sorted.
+    #[must_use]
+    pub fn compare(registered: &[String], tables: &[String]) -> Self {
+        let registered_set: HashSet<&String> = registered.iter().collect();
+        let table_set: HashSet<&String> = tables.iter().collect();
+        let mut orphan_tables: Vec<String> = table_set.difference(&registered_set).map(|table| (*table).clone()).collect();
+        let mut missing_tables: Vec<String> = registered_set.difference(&table_set).map(|uuid| (*uuid).clone()).collect();
+        orphan_tables.sort();
+        missing_tables.sort();
+        Self {
+            error: error::ConductorError::NoError,
+            producers: registered.len() as u64,
+            orphan_tables,
+            missing_tables,
+        }
+    }
+
+    /// Returns true if every registered producer has a table and every table belongs to a producer.
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        self.orphan_tables.is_empty() && self.missing_tables.is_empty()
+    }
+}
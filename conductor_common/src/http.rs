@@ -0,0 +1,19 @@
+//! The http clients requests to Conductor are sent with when no client is given. Every reqwest client
+//! has its own connection pool so sharing one client lets requests reuse the connections of earlier
+//! ones instead of opening a new connection each time. Use the `_with_client` variants to send
+//! requests through a client of your own.
+
+use std::sync::OnceLock;
+
+/// The client shared by the async producers and reactors.
+#[cfg(feature = "async")]
+pub fn async_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// The client shared by the blocking producers and reactors.
+pub fn blocking_client() -> &'static reqwest::blocking::Client {
+    static CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::blocking::Client::new)
+}
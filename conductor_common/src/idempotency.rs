@@ -0,0 +1,61 @@
+//! Lets clients retry an emit without it being written twice. The client sends the same
+//! `Idempotency-Key` header with every attempt of an emit and the server answers attempts whose key
+//! it has already seen with the result of the first one rather than writing the data again.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// The header which holds the idempotency key of a request.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// How long the result of a request is remembered when the server isn't configured otherwise. 10
+/// minutes in seconds.
+pub const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 10 * 60;
+
+/// The results of recent requests by the producer they were for and their idempotency key. Results are
+/// forgotten once they're older than the ttl. Times are in microseconds since the unix epoch.
+#[derive(Debug)]
+pub struct IdempotencyCache<T> {
+    /// How long a result is remembered in microseconds
+    ttl: u64,
+    results: HashMap<(String, String), (u64, T)>,
+}
+
+impl<T: Clone> IdempotencyCache<T> {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl: u64::try_from(ttl.as_micros()).unwrap_or(u64::MAX),
+            results: HashMap::new(),
+        }
+    }
+
+    /// The result of the request for the producer with the key if it was made within the ttl.
+    #[must_use]
+    pub fn get(&self, uuid: &str, key: &str, now: u64) -> Option<T> {
+        match self.results.get(&(uuid.to_string(), key.to_string())) {
+            Some((stored, result)) if now < stored.saturating_add(self.ttl) => Some(result.clone()),
+            _ => None,
+        }
+    }
+
+    /// Remembers the result of a request. Results which have expired are forgotten.
+    pub fn insert(&mut self, uuid: &str, key: &str, result: T, now: u64) {
+        let ttl = self.ttl;
+        self.results.retain(|_, (stored, _)| now < stored.saturating_add(ttl));
+        self.results.insert((uuid.to_string(), key.to_string()), (now, result));
+    }
+
+    /// The number of results which are remembered including any which have expired but haven't been
+    /// forgotten yet.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
@@ -1,6 +1,17 @@
+pub use serde;
+pub use serde_json;
+pub use reqwest;
 pub mod reactor;
+pub mod device;
 pub mod producer;
 pub mod schema;
 pub mod error;
-
-
+pub mod dead_letter;
+pub mod health;
+pub mod version;
+pub mod format;
+pub mod idempotency;
+pub mod http;
+pub mod admin;
+#[cfg(feature = "openapi")]
+pub mod openapi;
@@ -0,0 +1,265 @@
+//! Generates an OpenAPI 3 document describing the routes provided by a Conductor instance. The
+//! request and response schemas are generated from the same types that are sent over the wire.
+
+use std::collections::HashMap;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+use crate::device;
+use crate::format;
+use crate::producer;
+use crate::reactor;
+
+/// The media types accepted and produced by every route.
+const MEDIA_TYPES: [&str; 2] = ["application/json", "application/msgpack"];
+
+type EmitData = HashMap<String, Value>;
+
+fn content_for<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    let schema = json!(generator.subschema_for::<T>());
+    let mut content = Map::new();
+    for media_type in &MEDIA_TYPES {
+        content.insert((*media_type).to_string(), json!({ "schema": schema }));
+    }
+    Value::Object(content)
+}
+
+fn post_operation<Request: JsonSchema, Response: JsonSchema>(generator: &mut SchemaGenerator, summary: &str) -> Value {
+    json!({
+        "post": {
+            "summary": summary,
+            "requestBody": {
+                "required": true,
+                "content": content_for::<Request>(generator),
+            },
+            "responses": {
+                "200": {
+                    "description": "The result of the request. The error field is NoError when it succeeded.",
+                    "content": content_for::<Response>(generator),
+                },
+            },
+        },
+    })
+}
+
+fn get_operation<Response: JsonSchema>(generator: &mut SchemaGenerator, summary: &str, parameters: &[(&str, &str, bool)]) -> Value {
+    let parameters: Vec<Value> = parameters.iter().map(|(name, parameter_type, required)| json!({
+        "name": name,
+        "in": "query",
+        "required": required,
+        "schema": { "type": parameter_type },
+    })).collect();
+    json!({
+        "get": {
+            "summary": summary,
+            "parameters": parameters,
+            "responses": {
+                "200": {
+                    "description": "The result of the request. The error field is NoError when it succeeded.",
+                    "content": content_for::<Response>(generator),
+                },
+            },
+        },
+    })
+}
+
+///
+/// Builds the OpenAPI 3 document for the producer and reactor routes. The error model is included in the
+/// components as `ConductorError`.
+///
+#[must_use]
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let mut paths = Map::new();
+    paths.insert("/v1/producer/register".to_string(),
+                 post_operation::<producer::Registration, producer::RegistrationResult>(&mut generator, "Register a producer and create the table for its data"));
+    paths.insert("/v1/producer/register_batch".to_string(),
+                 post_operation::<Vec<producer::Registration>, producer::RegisterBatchResult>(&mut generator, "Register several producers such as those from an export"));
+    paths.insert("/v1/producer/export".to_string(),
+                 get_operation::<producer::ExportResult>(&mut generator, "Export a page of the registrations of every producer", &[
+                     ("offset", "integer", false),
+                     ("limit", "integer", false),
+                 ]));
+    paths.insert("/v1/producer/adopt".to_string(), json!({
+        "post": {
+            "summary": "Register a table which already exists in QuestDB as a producer",
+            "parameters": [{ "name": "table", "in": "query", "required": true, "schema": { "type": "string" } }],
+            "responses": {
+                "200": {
+                    "description": "The result of the request. The error field is NoError when it succeeded.",
+                    "content": content_for::<producer::RegistrationResult>(&mut generator),
+                },
+            },
+        },
+    }));
+    paths.insert("/v1/producer/emit".to_string(),
+                 post_operation::<producer::Emit<EmitData>, producer::EmitResult>(&mut generator, "Store a single data packet"));
+    paths.insert("/v1/producer/explain_emit".to_string(),
+                 post_operation::<producer::Emit<EmitData>, producer::ExplainEmitResult>(&mut generator, "Generate the insert a data packet would be stored with without storing it"));
+    paths.insert("/v1/producer/emit_batch".to_string(),
+                 post_operation::<producer::EmitBatch<EmitData>, producer::EmitResult>(&mut generator, "Store several data packets atomically"));
+    paths.insert("/v1/producer/emit_transaction".to_string(),
+                 post_operation::<Vec<producer::EmitBatch<EmitData>>, producer::EmitResult>(&mut generator, "Store batches for several producers atomically"));
+    paths.insert("/v1/producer/emit_session".to_string(),
+                 post_operation::<Vec<producer::Emit<EmitData>>, producer::EmitSessionResult>(&mut generator, "Store data packets for any number of producers on one database connection. Each one is stored or rejected on its own"));
+    paths.insert("/v1/producer/soft_delete".to_string(),
+                 post_operation::<producer::SoftDelete, producer::SoftDeleteResult>(&mut generator, "Mark rows within a time range as deleted"));
+    paths.insert("/v1/producer/truncate".to_string(),
+                 post_operation::<producer::Truncate, producer::TruncateResult>(&mut generator, "Remove every row of a producer while keeping its registration"));
+    paths.insert("/v1/producer/delete_batch".to_string(),
+                 post_operation::<producer::DeleteBatch, producer::DeleteBatchResult>(&mut generator, "Delete several producers along with their data"));
+    paths.insert("/v1/producer/{uuid}".to_string(), json!({
+        "patch": {
+            "summary": "Change the name and column metadata of a producer without changing its schema",
+            "parameters": [{ "name": "uuid", "in": "path", "required": true, "schema": { "type": "string" } }],
+            "requestBody": {
+                "required": true,
+                "content": content_for::<producer::ProducerUpdate>(&mut generator),
+            },
+            "responses": {
+                "200": {
+                    "description": "The result of the request. The error field is NoError when it succeeded.",
+                    "content": content_for::<producer::ProducerUpdateResult>(&mut generator),
+                },
+            },
+        },
+    }));
+    for (action, summary) in &[("pause", "Reject the emits of a producer until it's resumed"), ("resume", "Accept the emits of a paused producer again")] {
+        paths.insert(format!("/v1/producer/{{uuid}}/{}", action), json!({
+            "post": {
+                "summary": summary,
+                "parameters": [{ "name": "uuid", "in": "path", "required": true, "schema": { "type": "string" } }],
+                "responses": {
+                    "200": {
+                        "description": "The result of the request. The error field is NoError when it succeeded.",
+                        "content": content_for::<producer::PauseResult>(&mut generator),
+                    },
+                },
+            },
+        }));
+    }
+    paths.insert("/v1/producer/{uuid}/schema".to_string(), json!({
+        "post": {
+            "summary": "Migrate the schema of a producer if its current schema has the expected hash",
+            "parameters": [{ "name": "uuid", "in": "path", "required": true, "schema": { "type": "string" } }],
+            "requestBody": {
+                "required": true,
+                "content": content_for::<producer::SchemaSwap>(&mut generator),
+            },
+            "responses": {
+                "200": {
+                    "description": "The result of the request. The error field is NoError when it succeeded.",
+                    "content": content_for::<producer::SchemaSwapResult>(&mut generator),
+                },
+                "409": {
+                    "description": "The current schema didn't have the expected hash.",
+                    "content": content_for::<producer::SchemaSwapResult>(&mut generator),
+                },
+            },
+        },
+    }));
+    let mut query = get_operation::<producer::QueryResult>(&mut generator, "Read the rows of a producer", &[
+        ("uuid", "string", true),
+        ("from", "integer", false),
+        ("to", "integer", false),
+        ("limit", "integer", false),
+        ("include_deleted", "boolean", false),
+        ("layout", "string", false),
+    ]);
+    // Each row of the stream is a 4 byte big endian length followed by the row as a msgpack map.
+    query["get"]["responses"]["200"]["content"][format::MSGPACK_STREAM_MEDIA_TYPE] = json!({
+        "schema": { "type": "string", "format": "binary" },
+    });
+    paths.insert("/v1/producer/query".to_string(), query);
+    paths.insert("/v1/producer/latest".to_string(),
+                 get_operation::<producer::QueryResult>(&mut generator, "Read the newest row of a producer", &[
+                     ("uuid", "string", true),
+                     ("include_deleted", "boolean", false),
+                 ]));
+    paths.insert("/v1/producer/columns".to_string(),
+                 get_operation::<producer::ColumnsResult>(&mut generator, "List the columns of a producer", &[
+                     ("uuid", "string", true),
+                 ]));
+    paths.insert("/v1/producer/range".to_string(),
+                 get_operation::<producer::TimeRangeResult>(&mut generator, "Read the oldest and newest timestamps of a producer and its row count", &[
+                     ("uuid", "string", true),
+                 ]));
+    paths.insert("/v1/producer/schema".to_string(),
+                 get_operation::<producer::SchemaResult>(&mut generator, "Read the registered schema of a producer", &[
+                     ("uuid", "string", true),
+                     ("recover", "boolean", false),
+                 ]));
+    paths.insert("/v1/producer/rollup".to_string(),
+                 get_operation::<producer::RollupResult>(&mut generator, "Aggregate a numeric column of a producer into time buckets", &[
+                     ("uuid", "string", true),
+                     ("column", "string", true),
+                     ("fn", "string", true),
+                     ("interval", "string", true),
+                 ]));
+    paths.insert("/v1/producer/check".to_string(), json!({
+        "get": {
+            "summary": "Check whether a producer is registered",
+            "parameters": [{ "name": "uuid", "in": "query", "required": true, "schema": { "type": "string" } }],
+            "responses": {
+                "200": { "description": "The producer is registered" },
+                "404": { "description": "The producer isn't registered" },
+                "500": { "description": "The registration couldn't be checked" },
+            },
+        },
+    }));
+    paths.insert("/v1/producer/check_batch".to_string(),
+                 post_operation::<Vec<String>, producer::CheckBatchResult>(&mut generator, "Check which of several producers are registered"));
+    paths.insert("/v1/producer/emit_ordered".to_string(),
+                 post_operation::<producer::Emit<producer::OrderedData>, producer::EmitResult>(&mut generator, "Store data for a producer with the columns written in the order they're given"));
+    paths.insert("/v1/producer/query_union".to_string(),
+                 post_operation::<producer::QueryUnion, producer::QueryResult>(&mut generator, "Read the rows of several producers with identical schemas together"));
+    paths.insert("/v1/reactor/register".to_string(),
+                 post_operation::<reactor::ReactorRegistration, producer::RegistrationResult>(&mut generator, "Register a reactor along with the actions it can perform"));
+    paths.insert("/v1/reactor/enqueue".to_string(),
+                 post_operation::<reactor::EnqueueAction, reactor::EnqueueResult>(&mut generator, "Queue an action for a reactor"));
+    paths.insert("/v1/reactor/pending".to_string(),
+                 get_operation::<reactor::PendingActionsResult>(&mut generator, "Wait for the pending actions of a reactor", &[
+                     ("uuid", "string", true),
+                     ("wait", "integer", false),
+                 ]));
+    paths.insert("/v1/reactor/result".to_string(),
+                 post_operation::<reactor::ActionReport, reactor::ActionReportResult>(&mut generator, "Report the result of performing an action"));
+    paths.insert("/v1/device/register".to_string(),
+                 post_operation::<device::DeviceRegistration, producer::RegistrationResult>(&mut generator, "Register a producer and a reactor together as one device which share a uuid"));
+    paths.insert("/v1/admin/stats".to_string(),
+                 get_operation::<crate::admin::RegistryStats>(&mut generator, "Count the producers and optionally the rows of their tables. Needs the admin token as a bearer token", &[
+                     ("tables", "boolean", false),
+                 ]));
+    paths.insert("/v1/admin/reconcile".to_string(), json!({
+        "post": {
+            "summary": "Adopt or drop orphan tables and create the missing tables of producers again. Needs the admin token as a bearer token",
+            "parameters": [
+                { "name": "orphans", "in": "query", "required": false, "schema": { "type": "string", "enum": ["keep", "adopt", "drop"] } },
+                { "name": "recreate_missing", "in": "query", "required": false, "schema": { "type": "boolean" } },
+            ],
+            "responses": {
+                "200": {
+                    "description": "The actions which were applied and the ones which failed. The error field is NoError when the reconcile ran.",
+                    "content": content_for::<crate::admin::ReconcileReport>(&mut generator),
+                },
+            },
+        },
+    }));
+    paths.insert("/v1/health/deep".to_string(),
+                 get_operation::<crate::health::DeepHealthReport>(&mut generator, "Check that every producer has a table and every table has a producer", &[]));
+    paths.insert("/v1/version".to_string(),
+                 get_operation::<crate::version::VersionInfo>(&mut generator, "Read the version of the instance, the wire protocol version it speaks and the data types it supports", &[]));
+    generator.subschema_for::<crate::error::ConductorError>();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Conductor",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": generator.definitions(),
+        },
+    })
+}
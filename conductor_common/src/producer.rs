@@ -3,18 +3,99 @@ use url::{Url};
 
 #[cfg(feature = "async")]
 use async_trait::async_trait;
+#[cfg(feature = "async")]
+use futures::stream::{LocalBoxStream, Stream, StreamExt};
+#[cfg(feature = "async")]
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Formatter;
+use std::time::SystemTime;
 use crate::schema;
+use crate::device;
+use crate::reactor;
+use crate::version;
 use crate::error;
+use crate::format;
+use crate::idempotency;
+use crate::http;
+use crate::dead_letter::{self, DeadLetter, DeadLetterSink, Delivery};
+
 
+/// The name of the column used to mark rows as deleted when soft deletes are enabled without a custom column name.
+pub const DEFAULT_TOMBSTONE_COLUMN: &str = "_deleted";
+
+/// The most rows a batch emit can have unless the Conductor instance is configured with a different `max_batch_rows`.
+pub const DEFAULT_MAX_BATCH_ROWS: usize = 10_000;
+
+/// The most columns a producer schema can have. This is the QuestDB limit of `i32::MAX` columns per table.
+pub const MAX_COLUMNS: usize = i32::MAX as usize;
+
+///
+/// Checks a schema with `columns` columns fits within `MAX_COLUMNS`.
+///
+/// # Errors
+/// * `TooManyColumns`: There are more than `MAX_COLUMNS` columns.
+///
+pub fn validate_column_count(columns: usize) -> Result<(), error::ConductorError> {
+    if columns > MAX_COLUMNS {
+        return Err(error::ConductorError::TooManyColumns(format!("Producer schema registration had {} columns which is more than the maximum QuestDB supports of {}.", columns, MAX_COLUMNS)));
+    }
+    Ok(())
+}
 
 /// Contains the information required to register a producer with a Conductor server.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct Registration {
     name: String,
     schema: schema::Schema,
     use_custom_id: Option<String>, // this is to support devices without persistent storage such as an arduino. They can have a custom id
+    #[serde(default)]
+    tombstone_column: Option<String>,
+    #[serde(default)]
+    strict_float_precision: bool,
+    #[serde(default)]
+    column_defaults: schema::ColumnDefaults,
+    #[serde(default)]
+    required_columns: schema::RequiredColumns,
+    #[serde(default = "strict_columns_default")]
+    strict_columns: bool,
+    #[serde(default)]
+    row_quota: Option<u64>,
+    #[serde(default)]
+    coerce_numeric_strings: bool,
+    #[serde(default)]
+    lenient_bool: bool,
+    #[serde(default, skip_serializing_if = "schema::ColumnMetadataMap::is_empty")]
+    column_metadata: schema::ColumnMetadataMap,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    column_order: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_seconds: Option<u64>,
+    #[serde(default)]
+    timestamp_precision: TimestampPrecision,
+    #[serde(default = "time_series_default")]
+    time_series: bool,
+    #[serde(default = "protocol_version_default")]
+    protocol_version: u32,
+    #[serde(default, skip_serializing_if = "schema::IndexedColumns::is_empty")]
+    indexed_columns: schema::IndexedColumns,
+}
+
+const fn strict_columns_default() -> bool {
+    true
+}
+
+const fn time_series_default() -> bool {
+    true
+}
+
+const fn protocol_version_default() -> u32 {
+    version::MIN_PROTOCOL_VERSION
 }
 
 impl Registration {
@@ -24,6 +105,21 @@ impl Registration {
             name,
             schema,
             use_custom_id: custom_id,
+            tombstone_column: None,
+            strict_float_precision: false,
+            column_defaults: schema::ColumnDefaults::new(),
+            required_columns: schema::RequiredColumns::new(),
+            strict_columns: true,
+            row_quota: None,
+            coerce_numeric_strings: false,
+            lenient_bool: false,
+            column_metadata: schema::ColumnMetadataMap::new(),
+            column_order: None,
+            ttl_seconds: None,
+            timestamp_precision: TimestampPrecision::Micros,
+            time_series: true,
+            protocol_version: version::PROTOCOL_VERSION,
+            indexed_columns: schema::IndexedColumns::new(),
         }
     }
 
@@ -34,9 +130,265 @@ impl Registration {
             name,
             schema: std::collections::HashMap::default(),
             use_custom_id: custom_id,
+            tombstone_column: None,
+            strict_float_precision: false,
+            column_defaults: schema::ColumnDefaults::new(),
+            required_columns: schema::RequiredColumns::new(),
+            strict_columns: true,
+            row_quota: None,
+            coerce_numeric_strings: false,
+            lenient_bool: false,
+            column_metadata: schema::ColumnMetadataMap::new(),
+            column_order: None,
+            ttl_seconds: None,
+            timestamp_precision: TimestampPrecision::Micros,
+            time_series: true,
+            protocol_version: version::PROTOCOL_VERSION,
+            indexed_columns: schema::IndexedColumns::new(),
+        }
+    }
+
+    /// Enables soft deletes for the producer using the default tombstone column name `_deleted`.
+    #[must_use]
+    pub fn with_soft_delete(self) -> Self {
+        self.with_tombstone_column(DEFAULT_TOMBSTONE_COLUMN.to_string())
+    }
+
+    /// Enables soft deletes for the producer. The server adds a boolean column with the given name
+    /// to the data table of the producer which marks rows as deleted. Deleted rows are hidden from
+    /// queries but remain in the database. The name is reserved and can't be used in the schema.
+    #[must_use]
+    pub fn with_tombstone_column(mut self, column_name: String) -> Self {
+        self.tombstone_column = Some(column_name);
+        self
+    }
+
+    /// Get the name of the column used to mark rows as deleted if soft deletes are enabled.
+    #[must_use]
+    pub fn get_tombstone_column(&self) -> Option<&str> {
+        self.tombstone_column.as_deref()
+    }
+
+    /// Makes the server reject values emitted to `Float` columns which would lose precision when stored
+    /// as an f32. By default these values are silently rounded to the nearest f32.
+    #[must_use]
+    pub const fn with_strict_float_precision(mut self) -> Self {
+        self.strict_float_precision = true;
+        self
+    }
+
+    /// returns true if values which don't fit in an f32 without losing precision are rejected.
+    #[must_use]
+    pub const fn has_strict_float_precision(&self) -> bool {
+        self.strict_float_precision
+    }
+
+    /// Makes the server drop columns which aren't in the schema from emits and store the rest. By
+    /// default an emit with an unknown column is rejected.
+    #[must_use]
+    pub const fn with_lenient_columns(mut self) -> Self {
+        self.strict_columns = false;
+        self
+    }
+
+    /// returns true if emits with columns which aren't in the schema are rejected.
+    #[must_use]
+    pub const fn has_strict_columns(&self) -> bool {
+        self.strict_columns
+    }
+
+    /// Makes the server parse strings such as `"42"` emitted to `Int`, `Float` and `Double` columns
+    /// as numbers. Strings which aren't numbers are still rejected. By default only json numbers are
+    /// accepted for these columns so that type errors aren't masked.
+    #[must_use]
+    pub const fn with_numeric_string_coercion(mut self) -> Self {
+        self.coerce_numeric_strings = true;
+        self
+    }
+
+    /// returns true if numeric strings are parsed for `Int`, `Float` and `Double` columns.
+    #[must_use]
+    pub const fn coerces_numeric_strings(&self) -> bool {
+        self.coerce_numeric_strings
+    }
+
+    /// Makes the server accept `0` and `1` as well as the strings `"true"` and `"false"` for `Bool`
+    /// columns, which suits devices that send flags as integers to save bytes. Other integers are
+    /// still rejected. By default only json booleans are accepted.
+    #[must_use]
+    pub const fn with_lenient_bool(mut self) -> Self {
+        self.lenient_bool = true;
+        self
+    }
+
+    /// returns true if `0`, `1`, `"true"` and `"false"` are accepted for `Bool` columns.
+    #[must_use]
+    pub const fn has_lenient_bool(&self) -> bool {
+        self.lenient_bool
+    }
+
+    /// Limits the number of rows the producer can write in each quota period. Emits which would take
+    /// the producer over the quota are rejected with `QuotaExceeded` until the period resets. The length
+    /// of the period is configured on the server and is 30 days by default.
+    #[must_use]
+    pub const fn with_row_quota(mut self, rows: u64) -> Self {
+        self.row_quota = Some(rows);
+        self
+    }
+
+    /// The number of rows the producer can write in each quota period. None if it's unlimited.
+    #[must_use]
+    pub const fn get_row_quota(&self) -> Option<u64> {
+        self.row_quota
+    }
+
+    /// Deletes the producer once it hasn't emitted for this many seconds. A producer which never emits
+    /// expires this long after it was registered. Its table and registration are removed by the sweeper
+    /// of the server, which only runs when `ttl_sweep_interval` is configured. Only time series can have
+    /// a ttl. By default producers never expire.
+    #[must_use]
+    pub const fn with_ttl(mut self, seconds: u64) -> Self {
+        self.ttl_seconds = Some(seconds);
+        self
+    }
+
+    /// The number of seconds the producer can go without emitting before it's deleted. None if it never expires.
+    #[must_use]
+    pub const fn get_ttl(&self) -> Option<u64> {
+        self.ttl_seconds
+    }
+
+    /// Sets the unit of the timestamps the producer emits with. The server converts them to microseconds
+    /// to store them and returns the timestamps it assigns in the same unit. Microseconds by default.
+    #[must_use]
+    pub const fn with_timestamp_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_timestamp_precision(&self) -> TimestampPrecision {
+        self.timestamp_precision
+    }
+
+    /// Registers the producer with a plain table instead of a time series. Its table doesn't get the
+    /// designated timestamp column `ts`, which suits configuration snapshots and lookup data, and
+    /// `ts` can be used as an ordinary column. Emits for it can't have a timestamp.
+    #[must_use]
+    pub const fn without_time_series(mut self) -> Self {
+        self.time_series = false;
+        self
+    }
+
+    /// returns true if the table of the producer has the designated timestamp column `ts`. True by default.
+    #[must_use]
+    pub const fn is_time_series(&self) -> bool {
+        self.time_series
+    }
+
+    /// Sets the wire protocol version the registration is made with. It's `version::PROTOCOL_VERSION`
+    /// unless it's set, which is what the client speaks, so this is only needed to pin an older version.
+    #[must_use]
+    pub const fn with_protocol_version(mut self, protocol_version: u32) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// The wire protocol version the registration was made with. Registrations from clients which
+    /// predate it are `version::MIN_PROTOCOL_VERSION`.
+    #[must_use]
+    pub const fn get_protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// Sets the value which is stored in a column when it's left out of an emit. Without a default the
+    /// column is left empty (null). The default must be convertible to the type of the column otherwise
+    /// the registration is rejected.
+    #[must_use]
+    pub fn with_column_default(mut self, column_name: String, value: serde_json::Value) -> Self {
+        self.column_defaults.insert(column_name, value);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_column_defaults(&self) -> &schema::ColumnDefaults {
+        &self.column_defaults
+    }
+
+    /// Registers the producer with the unit and description of a column already set. They can be
+    /// changed later with `update_metadata`.
+    #[must_use]
+    pub fn with_column_metadata(mut self, column_name: String, metadata: schema::ColumnMetadata) -> Self {
+        self.column_metadata.insert(column_name, metadata);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_column_metadata(&self) -> &schema::ColumnMetadataMap {
+        &self.column_metadata
+    }
+
+    ///
+    /// Sets the order the columns are created in when the table of the producer is created. The
+    /// order has to list every column of the schema exactly once. Without it the columns are created
+    /// in the iteration order of the schema, which is unspecified. The timestamp column always comes
+    /// first and the tombstone column last.
+    ///
+    #[must_use]
+    pub fn with_column_order(mut self, column_order: Vec<String>) -> Self {
+        self.column_order = Some(column_order);
+        self
+    }
+
+    #[must_use]
+    pub fn get_column_order(&self) -> Option<&[String]> {
+        self.column_order.as_deref()
+    }
+
+    /// The columns of the schema in the order they're created in. See `with_column_order`.
+    #[must_use]
+    pub fn ordered_columns(&self) -> Vec<(&str, schema::DataTypes)> {
+        match &self.column_order {
+            Some(column_order) => column_order.iter()
+                .filter_map(|column| self.schema.get_key_value(column))
+                .map(|(column, data_type)| (column.as_str(), *data_type))
+                .collect(),
+            None => self.schema.iter().map(|(column, data_type)| (column.as_str(), *data_type)).collect(),
         }
     }
 
+    /// Marks a column as required. Emits which don't give the column a value are rejected unless
+    /// the column has a default. Columns are optional (nullable) unless they're marked as required.
+    #[must_use]
+    pub fn with_required_column(mut self, column_name: String) -> Self {
+        self.required_columns.insert(column_name);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_required_columns(&self) -> &schema::RequiredColumns {
+        &self.required_columns
+    }
+
+    /// Indexes a column so queries which filter on it are faster. Only `String` columns can be indexed
+    /// and they're stored as QuestDB symbols when they are, which suits columns with few distinct values.
+    #[must_use]
+    pub fn with_indexed_column(mut self, column_name: String) -> Self {
+        self.indexed_columns.insert(column_name);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_indexed_columns(&self) -> &schema::IndexedColumns {
+        &self.indexed_columns
+    }
+
+    /// returns true if the column is indexed.
+    #[must_use]
+    pub fn is_indexed(&self, column_name: &str) -> bool {
+        self.indexed_columns.contains(column_name)
+    }
+
     /// Get the name of the producer
     #[must_use]
     pub fn get_name(&self) -> &str {
@@ -58,6 +410,16 @@ impl Registration {
         None
     }
 
+    /// Where the uuid of the producer will come from once it's registered.
+    #[must_use]
+    pub const fn get_id_source(&self) -> IdSource {
+        if self.has_custom_id() {
+            IdSource::Custom
+        } else {
+            IdSource::Generated
+        }
+    }
+
     #[must_use]
     pub fn contains_column(&self, column_name: &str) -> bool {
         self.schema.contains_key(column_name)
@@ -72,21 +434,110 @@ impl Registration {
     pub const fn get_schema(&self) -> &schema::Schema {
         &self.schema
     }
+
+    ///
+    /// Checks the registration against the rules the server applies before accepting it so that an
+    /// invalid registration can be caught without a round trip.
+    ///
+    /// # Errors
+    /// * `NameInvalid`: The name of the producer is empty.
+    /// * `InvalidUuid`: The custom id isn't a valid uuid. See `validate_uuid`.
+    /// * `TimestampDefined`: The schema of a time series contains the reserved column `ts`.
+    /// * `InvalidColumnNames`: A column or the tombstone column has an illegal name, a default,
+    ///   required, indexed column or column with metadata isn't in the schema or the column order doesn't
+    ///   list every column of the schema exactly once.
+    /// * `NoMembers`: The schema has no columns.
+    /// * `InvalidData`: A column default can't be stored in its column or a producer which isn't a time
+    ///   series has a ttl.
+    /// * `InvalidSchema`: An indexed column has a type which can't be indexed.
+    /// * `TooManyColumns`: The schema has more than `MAX_COLUMNS` columns.
+    /// * `UnsupportedProtocol`: The registration was made with a protocol version the server doesn't support.
+    ///
+    pub fn validate(&self) -> Result<(), error::ConductorError> {
+        version::check_protocol_version(self.protocol_version)?;
+        if self.name.is_empty() {
+            return Err(error::ConductorError::NameInvalid("Producer registration failed. Producer name is empty.".to_string()));
+        }
+        if let Some(custom_id) = self.get_custom_id() {
+            validate_uuid(custom_id)?;
+        }
+        if !self.time_series && self.ttl_seconds.is_some() {
+            return Err(error::ConductorError::InvalidData("Producer registration failed. A ttl is measured from the latest row so the producer has to be a time series.".to_string()));
+        }
+        if self.time_series && self.contains_column("ts") {
+            return Err(error::ConductorError::TimestampDefined("Producer registration failed. column with name ts. This is a reserved name.".to_string()));
+        }
+        if let Some(tombstone_column) = self.get_tombstone_column() {
+            if tombstone_column.is_empty() || (self.time_series && tombstone_column == "ts") || tombstone_column.contains('.') || tombstone_column.contains('\"') || self.contains_column(tombstone_column) {
+                return Err(error::ConductorError::InvalidColumnNames(format!("Producer registration failed. Tombstone column {} is invalid or is already a column in the schema.", tombstone_column)));
+            }
+        }
+        if self.schema.is_empty() {
+            return Err(error::ConductorError::NoMembers("Producer registration failed. No columns in schema.".to_string()));
+        }
+        if let Some(col) = self.schema.keys().find(|col| col.contains('.') || col.contains('\"')) {
+            return Err(error::ConductorError::InvalidColumnNames(format!("Producer registration failed. Column with name {} is invalid as it contains a '.' or a '\"'.", col)));
+        }
+        schema::validate_column_defaults(&self.schema, &self.column_defaults)?;
+        schema::validate_required_columns(&self.schema, &self.required_columns)?;
+        schema::validate_indexed_columns(&self.schema, &self.indexed_columns)?;
+        if let Some(column) = self.column_metadata.keys().find(|column| !self.schema.contains_key(*column)) {
+            return Err(error::ConductorError::InvalidColumnNames(format!("Producer registration failed. Metadata was given for {} which isn't a column of the producer.", column)));
+        }
+        if let Some(column_order) = &self.column_order {
+            let ordered: HashSet<&String> = column_order.iter().collect();
+            if ordered.len() != column_order.len() || ordered.len() != self.schema.len() || !self.schema.keys().all(|column| ordered.contains(column)) {
+                return Err(error::ConductorError::InvalidColumnNames("Producer registration failed. The column order has to list every column of the schema exactly once.".to_string()));
+            }
+        }
+        validate_column_count(self.schema.len())
+    }
 }
 
 ///The response from the Conductor instance after a registration attempt
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct RegistrationResult {
     pub error: error::ConductorError,
     pub uuid: Option<String>,
 }
 
+///The response from the Conductor instance after registering several producers. Contains the result
+/// of each registration in the order they were sent.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct RegisterBatchResult {
+    pub error: error::ConductorError,
+    pub results: Vec<RegistrationResult>,
+}
+
+/// The most registrations the server sends in a single page of an export.
+pub const EXPORT_PAGE_SIZE: u32 = 500;
+
+///The response from the Conductor instance to a page of an export of the registry. Each registration
+/// uses the uuid of its producer as the custom id so registering it again recreates the same producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ExportResult {
+    pub error: error::ConductorError,
+    pub registrations: Vec<Registration>,
+    /// The offset of the next page. None once the last page has been sent.
+    pub next: Option<u64>,
+}
+
 /// A new data packet to be sent to the Conductor instance
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct Emit<'a, T> {
     uuid: &'a str,
     timestamp: Option<u64>,
     data: T,
+    /// Merge the data into the latest row of the producer rather than inserting it as is
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    merge: bool,
+    /// Registers the producer when the emit is for a uuid which isn't registered yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    registration: Option<Registration>,
 }
 
 impl<'a, T> Emit<'a, T> {
@@ -96,9 +547,33 @@ impl<'a, T> Emit<'a, T> {
             uuid,
             timestamp,
             data,
+            merge: false,
+            registration: None,
         }
     }
 
+    ///
+    /// Merges the data into the latest row of the producer. Columns which aren't in the data keep
+    /// the value they have in the latest row and the merged row is inserted with a new timestamp.
+    ///
+    /// The latest row is read and the merged row written in separate steps so concurrent emits for
+    /// the same producer can race. A merge may then be based on a row which has already been
+    /// superseded and overwrite the values of another emit.
+    ///
+    #[must_use]
+    pub const fn with_merge(mut self) -> Self {
+        self.merge = true;
+        self
+    }
+
+    /// Create a new emit with a timestamp taken from a `SystemTime`.
+    ///
+    /// # Errors
+    /// * `InvalidTimestamp`: Produced when the time is before the unix epoch or too large to be represented.
+    pub fn new_at(uuid: &'a str, time: SystemTime, data: T) -> Result<Self, Error> {
+        Ok(Self::new(uuid, Some(timestamp_from_system_time(time)?), data))
+    }
+
     #[must_use]
     pub const fn get_uuid(&self) -> &str {
         self.uuid
@@ -113,428 +588,4093 @@ impl<'a, T> Emit<'a, T> {
     pub const fn get_data(&self) -> &T {
         &self.data
     }
-}
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct EmitResult {
-    pub error: error::ConductorError,
-}
+    #[must_use]
+    pub const fn is_merge(&self) -> bool {
+        self.merge
+    }
 
-/// All the errors that can be produced by a producer
-#[derive(Debug)]
-pub enum Error {
-    /// The domain given for the conductor instance is invalid in some way
-    InvalidConductorDomain(String),
-    /// Indicates a failure to serialize a struct to message pack. Contains rmp_serde encoding error
-    MsgPackSerialisationFailure(rmp_serde::encode::Error),
-    /// Indicates a failure to serialize a struct to json. Contains serde_json error type
-    JsonSerialisationFailure(serde_json::Error),
-    /// Indicates a failure to serialize a struct. Contains the error given by the serializer.
-    GenericSerialisationFailure(Box<dyn std::error::Error>),
-    /// Indicates an error which was emitted from the Conductor server (Internal Server Error)
-    ConductorError(error::ConductorError),
-    /// Indicates an issue with the network layer. Contains the reqwest error type
-    NetworkError(reqwest::Error),
-    /// Indicates a failure to deserialize a struct from message pack. Contains rmp_serde decoding error
-    MsgPackDeserializationFailure(rmp_serde::decode::Error),
-    /// Indicates a failure to deserialize a struct from json. Contains serde_json error type
-    JsonDeserializationFailure(serde_json::Error),
-    /// Indicates a failure to deserialize a struct. Contains the error given by the serializer.
-    GenericDeserializationFailure(Box<dyn std::error::Error>),
+    ///
+    /// Carries a registration with the emit. If the uuid isn't registered yet the server registers the
+    /// producer and creates its table before storing the data. Once the producer is registered the
+    /// registration is ignored so stateless devices can send it with every emit. Emits without a
+    /// registration never create tables.
+    ///
+    /// The registration must use the uuid of the emit as its custom id. A registration without a custom
+    /// id can be sent with an empty uuid instead and the server assigns the producer a uuid, which it
+    /// returns in `EmitResult::registration`. Two emits which both register the same producer at the
+    /// same time can race and register it twice.
+    ///
+    #[must_use]
+    pub fn with_registration(mut self, registration: Registration) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_registration(&self) -> Option<&Registration> {
+        self.registration.as_ref()
+    }
 
+    ///
+    /// Returns the registration carried by the emit after checking that it can be used to register the
+    /// producer of the emit.
+    ///
+    /// A registration without a custom id is only accepted with an empty uuid, in which case the
+    /// server assigns the producer its uuid.
+    ///
+    /// # Errors
+    /// * `InvalidUuid`: The custom id of the registration isn't the uuid of the emit.
+    /// * Any error produced by `Registration::validate`.
+    ///
+    pub fn checked_registration(&self) -> Result<Option<&Registration>, error::ConductorError> {
+        let registration = match &self.registration {
+            Some(registration) => registration,
+            None => return Ok(None),
+        };
+        match registration.get_custom_id() {
+            Some(custom_id) if custom_id == self.uuid => {}
+            None if self.uuid.is_empty() => {}
+            _ => return Err(error::ConductorError::InvalidUuid(format!("The registration sent with an emit for {} must use it as the custom id", self.uuid))),
+        }
+        registration.validate()?;
+        Ok(Some(registration))
+    }
 }
 
+/// The data of an emit as column and value pairs. Unlike a map it keeps the order the columns were
+/// given in, which the server follows when it writes the columns of the insert.
+pub type OrderedData = Vec<(String, serde_json::Value)>;
 
-impl std::error::Error for Error {}
+/// An ordered emit split by `Emit::into_keyed` into a keyed emit and the order its columns were given in.
+pub type KeyedEmit<'a> = (Emit<'a, HashMap<String, serde_json::Value>>, Vec<String>);
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Error::InvalidConductorDomain(message) => write!(f, "InvalidConductorDomain: {}", message),
-            Error::MsgPackSerialisationFailure(encode_error) => write!(f, "MsgPackSerialisationFailure: {}", encode_error),
-            Error::ConductorError(ce) => write!(f, "ConductorError: {}", ce),
-            Error::NetworkError(re) => write!(f, "NetworkError: {}", re),
-            Error::MsgPackDeserializationFailure(decode_error) => write!(f, "MsgPackDeserializationFailure: {}", decode_error),
-            Error::JsonSerialisationFailure(encode_error) => write!(f, "JsonSerialisationFailure: {}", encode_error),
-            Error::GenericSerialisationFailure(encode_error) => write!(f, "GenericSerialisationFailure: {}", encode_error),
-            Error::JsonDeserializationFailure(decode_error) => write!(f, "JsonDeserializationFailure: {}", decode_error),
-            Error::GenericDeserializationFailure(decode_error) => write!(f, "GenericDeserializationFailure: {}", decode_error),
+impl<'a> Emit<'a, OrderedData> {
+    ///
+    /// Splits an ordered emit into an emit of a map of its columns and the order the columns were
+    /// given in.
+    ///
+    /// # Errors
+    /// * `InvalidColumnNames`: A column is given more than once
+    ///
+    pub fn into_keyed(self) -> Result<KeyedEmit<'a>, error::ConductorError> {
+        let mut order = Vec::with_capacity(self.data.len());
+        let mut data = HashMap::with_capacity(self.data.len());
+        for (column, value) in self.data {
+            if data.insert(column.clone(), value).is_some() {
+                return Err(error::ConductorError::InvalidColumnNames(format!("The column {} was emitted more than once", column)));
+            }
+            order.push(column);
         }
+        let keyed = Emit {
+            uuid: self.uuid,
+            timestamp: self.timestamp,
+            data,
+            merge: self.merge,
+            registration: self.registration,
+        };
+        Ok((keyed, order))
     }
 }
 
-///
-/// Provides functionality that is shared between both the async and blocking versions of the Producer trait.
-/// Prepares and processes conductor requests and responses.
-///
-pub trait Base: Serialize + Clone + crate::schema::ConductorSchema {
-    ///
-    /// Prepares a payload for emitting data. This function doesn't send the payload.
-    ///
-    /// # Arguments
+impl<'a> Emit<'a, HashMap<String, serde_json::Value>> {
     ///
-    /// * `uuid`: The unique ID of this producer.
-    /// * `conductor_domain`: The url of the conductor instance.
+    /// Creates an emit from a json object such as one received from another API. Each field of the
+    /// object is a column. The emit doesn't have a timestamp so the server assigns one.
     ///
     /// # Errors
+    /// * `SchemaMismatch`: The value isn't a json object.
     ///
-    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `SerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
-    /// due to a difficulty serialising Self using serde.
-    /// # Example
-    ///
-    /// ```
-    /// use std::collections::HashMap;
-    /// use conductor_common::producer::Base;
-    /// use conductor_common::schema;
-    /// #[derive(Clone, Serialize)]
-    /// struct Measurement {
-    ///     data_point:u8
-    /// }
-    /// impl schema::ConductorSchema for Measurement {
-    ///     fn generate_schema() -> HashMap<String, schema::DataTypes> {
-    ///         unimplemented!("Not needed for example/test");
-    ///     }
-    /// }
-    /// impl Base for Measurement {}
-    /// let m = Measurement {
-    ///     data_point: 10
+    pub fn from_json_object(uuid: &'a str, value: serde_json::Value) -> Result<Self, Error> {
+        let kind = match value {
+            serde_json::Value::Object(fields) => return Ok(Self::new(uuid, None, fields.into_iter().collect())),
+            serde_json::Value::Array(_) => "an array",
+            serde_json::Value::String(_) => "a string",
+            serde_json::Value::Number(_) => "a number",
+            serde_json::Value::Bool(_) => "a bool",
+            serde_json::Value::Null => "null",
+        };
+        Err(Error::SchemaMismatch(format!("Expected the emit data to be a json object but got {}", kind)))
+    }
+
+    /// The names of the columns the emit has a value for. They're in no particular order.
+    pub fn column_names(&self) -> impl Iterator<Item = &String> {
+        self.data.keys()
+    }
+
+    /// The value emitted for a column. None if the emit doesn't have the column.
+    #[must_use]
+    pub fn get_column(&self, name: &str) -> Option<&serde_json::Value> {
+        self.data.get(name)
+    }
+}
+
+/// Serialises an emit and builds the url it's sent to.
+fn encode_emit(emit: &Emit<'_, HashMap<String, serde_json::Value>>, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+    let url = match conductor_domain.join("/v1/producer/emit") {
+        Ok(u) => u,
+        Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+    };
+    let payload = match rmp_serde::to_vec_named(emit) {
+        Ok(p) => p,
+        Err(err) => {
+            return Err(Error::MsgPackSerialisationFailure(err));
+        }
+    };
+    Ok((payload, url))
+}
+
+/// Takes the producer a registering emit was stored for from its result.
+fn emit_registration(result: EmitResult) -> Result<EmitRegistration, Error> {
+    if result.error != error::ConductorError::NoError {
+        return Err(Error::ConductorError(result.error));
+    }
+    result.registration.ok_or_else(|| Error::ConductorError(error::ConductorError::VersionMismatch(
+        "The server didn't report the producer the emit was stored for".to_string()
+    )))
+}
+
+/// Converts a value into the data map sent to Conductor and checks every field against the schema.
+/// This catches differences between the data and the registered schema before anything is sent.
+/// Fields which aren't part of the schema (such as fields skipped with `#[producer_skip_field]`) and
+/// fields which are null are left out of the map.
+///
+/// # Errors
+/// * `JsonSerialisationFailure`: The value couldn't be serialised to json.
+/// * `SchemaMismatch`: The value isn't serialised to an object or a field can't be stored as the type
+/// the schema gives for it.
+pub fn to_checked_map<T: Serialize>(value: &T, schema: &schema::Schema) -> Result<HashMap<String, serde_json::Value>, Error> {
+    let fields = match serde_json::to_value(value) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        Ok(other) => return Err(Error::SchemaMismatch(format!("Expected the value to serialise to an object but got {}", other))),
+        Err(err) => return Err(Error::JsonSerialisationFailure(err)),
+    };
+    let mut data = HashMap::with_capacity(fields.len());
+    for (name, field) in fields {
+        let data_type = match schema.get(&name) {
+            Some(data_type) => data_type,
+            None => continue,
+        };
+        if field.is_null() {
+            continue;
+        }
+        if !data_type.accepts(&field) {
+            return Err(Error::SchemaMismatch(format!("Field {} with value {} can't be stored as {:?}", name, field, data_type)));
+        }
+        data.insert(name, field);
+    }
+    Ok(data)
+}
+
+/// Converts a `SystemTime` into the number of microseconds since the unix epoch which is how
+/// Conductor represents timestamps.
+///
+/// # Errors
+/// * `InvalidTimestamp`: Produced when the time is before the unix epoch or too large to be represented.
+pub fn timestamp_from_system_time(time: SystemTime) -> Result<u64, Error> {
+    let since_epoch = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration,
+        Err(err) => return Err(Error::InvalidTimestamp(format!("The time is before the unix epoch. {}", err)))
+    };
+    match u64::try_from(since_epoch.as_micros()) {
+        Ok(micros) => Ok(micros),
+        Err(err) => Err(Error::InvalidTimestamp(format!("The time is too large to be represented in microseconds. {}", err)))
+    }
+}
+
+/// A set of data packets for a single producer to be sent to the Conductor instance in one request.
+/// Either every packet in the batch is stored or none of them are.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EmitBatch<'a, T> {
+    uuid: &'a str,
+    data: Vec<T>,
+}
+
+impl<'a, T> EmitBatch<'a, T> {
+    #[must_use]
+    pub const fn new(uuid: &'a str, data: Vec<T>) -> Self {
+        Self {
+            uuid,
+            data,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_uuid(&self) -> &str {
+        self.uuid
+    }
+
+    #[must_use]
+    pub fn get_data(&self) -> &[T] {
+        &self.data
+    }
+
+    ///
+    /// Checks the batch has no more than `max_rows` rows.
+    ///
+    /// # Errors
+    /// * `BatchTooLarge`: The batch has more than `max_rows` rows.
+    ///
+    pub fn check_rows(&self, max_rows: usize) -> Result<(), error::ConductorError> {
+        if self.data.len() > max_rows {
+            return Err(error::ConductorError::BatchTooLarge(format!("The batch for producer {} has {} rows which is more than the maximum of {}.", self.uuid, self.data.len(), max_rows)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EmitResult {
+    pub error: error::ConductorError,
+    /// The timestamp the row was stored with in the timestamp precision of the producer, which is
+    /// microseconds since the unix epoch by default. This is the timestamp of the emit or the time it was stored if it didn't have one. Only single emits
+    /// which were stored successfully have it.
+    #[serde(default)]
+    pub assigned_timestamp: Option<u64>,
+    /// The producer an emit which carried a registration was stored for. Devices which emit
+    /// without a uuid learn the uuid the server assigned them from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration: Option<EmitRegistration>,
+}
+
+/// The response from the Conductor instance to the emits of a session. Contains the result of each
+/// emit in the order they were sent.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EmitSessionResult {
+    pub error: error::ConductorError,
+    pub results: Vec<EmitResult>,
+}
+
+/// The producer a registering emit was stored for. See `Emit::with_registration`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EmitRegistration {
+    pub uuid: String,
+    /// True when the emit registered the producer and false when it was already registered.
+    pub created: bool,
+}
+
+///The response from the Conductor instance to a request to explain an emit. The emit isn't stored.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ExplainEmitResult {
+    pub error: error::ConductorError,
+    /// The INSERT sql the emit would be stored with. Values are replaced by placeholders.
+    pub sql: String,
+    /// The type each column of the sql was resolved to including `ts` and columns filled with their default.
+    pub columns: schema::Schema,
+    /// Whether each field converts to the type of its column, sorted by column. It's filled in whenever
+    /// the producer is registered, including when a field doesn't convert, so every bad field is reported.
+    #[serde(default)]
+    pub fields: Vec<FieldConversion>,
+}
+
+/// Whether an emitted value can be stored in its column. See `ExplainEmitResult::fields`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct FieldConversion {
+    pub column: String,
+    pub value: serde_json::Value,
+    /// The type of the column in the registered schema. None if the column isn't in the schema.
+    pub expected: Option<schema::DataTypes>,
+    /// Why the value can't be stored in the column. None if it converts cleanly.
+    pub error: Option<String>,
+}
+
+impl FieldConversion {
+    /// returns true if the value converts to the type of its column.
+    #[must_use]
+    pub const fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// How the server would store an emit. See `explain_emit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitExplanation {
+    pub sql: String,
+    pub columns: schema::Schema,
+    /// How each field converts to the type of its column. See `ExplainEmitResult::fields`.
+    pub fields: Vec<FieldConversion>,
+}
+
+/// A request to mark rows of a producer as deleted without removing them from the database.
+/// Only producers which were registered with a tombstone column support soft deletes.
+///
+/// Rows with a timestamp between `from` and `to` (inclusive, in the timestamp precision of the producer)
+/// are marked as deleted. Leaving a bound empty leaves that side of the range open.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct SoftDelete<'a> {
+    uuid: &'a str,
+    from: Option<u64>,
+    to: Option<u64>,
+}
+
+impl<'a> SoftDelete<'a> {
+    #[must_use]
+    pub const fn new(uuid: &'a str, from: Option<u64>, to: Option<u64>) -> Self {
+        Self {
+            uuid,
+            from,
+            to,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_uuid(&self) -> &str {
+        self.uuid
+    }
+
+    #[must_use]
+    pub const fn get_from(&self) -> Option<u64> {
+        self.from
+    }
+
+    #[must_use]
+    pub const fn get_to(&self) -> Option<u64> {
+        self.to
+    }
+}
+
+/// A request to remove every row stored for a producer. Unlike deregistering the producer this keeps its
+/// registration so that it can continue to emit.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct Truncate<'a> {
+    uuid: &'a str,
+}
+
+impl<'a> Truncate<'a> {
+    #[must_use]
+    pub const fn new(uuid: &'a str) -> Self {
+        Self {
+            uuid,
+        }
+    }
+
+    #[must_use]
+    pub const fn get_uuid(&self) -> &str {
+        self.uuid
+    }
+}
+
+/// A request to remove several producers along with their registration and all of their data. Producers
+/// are selected by uuid, by the start of their name, or both.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct DeleteBatch {
+    uuids: Vec<String>,
+    #[serde(default)]
+    name_prefix: Option<String>,
+}
+
+impl DeleteBatch {
+    #[must_use]
+    pub const fn new(uuids: Vec<String>) -> Self {
+        Self {
+            uuids,
+            name_prefix: None,
+        }
+    }
+
+    /// Also deletes every producer whose name starts with `name_prefix`. An empty prefix is ignored
+    /// so that a batch can't delete every producer by accident.
+    #[must_use]
+    pub fn with_name_prefix(mut self, name_prefix: String) -> Self {
+        self.name_prefix = Some(name_prefix);
+        self
+    }
+
+    #[must_use]
+    pub fn get_uuids(&self) -> &[String] {
+        &self.uuids
+    }
+
+    #[must_use]
+    pub fn get_name_prefix(&self) -> Option<&str> {
+        self.name_prefix.as_deref()
+    }
+
+    /// Returns true if the registered producer with this uuid and name is selected by the batch.
+    #[must_use]
+    pub fn selects(&self, uuid: &str, name: &str) -> bool {
+        self.uuids.iter().any(|selected| selected == uuid)
+            || matches!(self.get_name_prefix(), Some(prefix) if !prefix.is_empty() && name.starts_with(prefix))
+    }
+}
+
+/// The column added to every row of a union query which holds the uuid of the producer the row came from.
+pub const SOURCE_UUID_COLUMN: &str = "source_uuid";
+
+/// A request to read the rows of several producers with identical schemas together. Each row is tagged
+/// with the uuid of its producer in `source_uuid`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct QueryUnion {
+    uuids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<u64>,
+}
+
+impl QueryUnion {
+    #[must_use]
+    pub const fn new(uuids: Vec<String>) -> Self {
+        Self {
+            uuids,
+            limit: None,
+        }
+    }
+
+    /// Returns at most this many rows across all of the producers.
+    #[must_use]
+    pub const fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    #[must_use]
+    pub fn get_uuids(&self) -> &[String] {
+        &self.uuids
+    }
+
+    #[must_use]
+    pub const fn get_limit(&self) -> Option<u64> {
+        self.limit
+    }
+}
+
+/// Checks that a uuid could name a producer's table. It can't be empty or contain a '.' or a quote.
+///
+/// # Errors
+/// * `InvalidUuid`: The uuid is empty or contains an illegal character.
+pub fn validate_uuid(uuid: &str) -> Result<(), error::ConductorError> {
+    if uuid.is_empty() || uuid.contains('.') || uuid.contains('\"') || uuid.contains('\'') {
+        return Err(error::ConductorError::InvalidUuid(format!("The uuid {} is empty or has illegal chars", uuid)));
+    }
+    Ok(())
+}
+
+/// The outcome of deleting a single producer as part of a `DeleteBatch`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct DeleteOutcome {
+    pub uuid: String,
+    pub error: error::ConductorError,
+}
+
+///The response from the Conductor instance after a batch delete. Contains the outcome for each selected producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct DeleteBatchResult {
+    pub error: error::ConductorError,
+    pub results: Vec<DeleteOutcome>,
+}
+
+///The response from the Conductor instance after checking the registration of several producers.
+/// Holds whether each uuid is registered in the order they were sent.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct CheckBatchResult {
+    pub error: error::ConductorError,
+    pub registered: Vec<bool>,
+}
+
+///The response from the Conductor instance after a truncate.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct TruncateResult {
+    pub error: error::ConductorError,
+}
+
+///The response from the Conductor instance after pausing or resuming ingestion for a producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PauseResult {
+    pub error: error::ConductorError,
+    /// Whether ingestion is paused for the producer after the request
+    pub paused: bool,
+}
+
+/// Builds the url which pauses or resumes ingestion for a producer.
+fn pause_url(uuid: &str, paused: bool, conductor_domain: &Url) -> Result<Url, Error> {
+    let action = if paused { "pause" } else { "resume" };
+    match conductor_domain.join(&format!("/v1/producer/{}/{}", uuid, action)) {
+        Ok(url) => Ok(url),
+        Err(err) => Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err))),
+    }
+}
+
+///The response from the Conductor instance when retrieving the registered schema of a producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct SchemaResult {
+    pub error: error::ConductorError,
+    pub schema: HashMap<String, schema::SchemaEntry>,
+    /// Where the uuid of the producer came from. None if the producer was registered before this was recorded.
+    #[serde(default)]
+    pub id_source: Option<IdSource>,
+    /// The name and column metadata of the producer. None if the schema couldn't be retrieved.
+    #[serde(default)]
+    pub metadata: Option<ProducerMetadata>,
+    /// True when the registration of the producer was missing and the schema was read from its data
+    /// table instead. Columns read from the table aren't required.
+    #[serde(default)]
+    pub recovered: bool,
+}
+
+/// The descriptive information about a producer which can be changed after it's registered.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ProducerMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub column_metadata: schema::ColumnMetadataMap,
+}
+
+/// A change to the name and/or column metadata of a producer. It never changes the schema of the producer.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ProducerUpdate {
+    #[serde(default)]
+    name: Option<String>,
+    /// Fields which aren't set are left unchanged.
+    #[serde(default)]
+    column_metadata: schema::ColumnMetadataMap,
+}
+
+impl ProducerUpdate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    #[must_use]
+    pub fn with_column_unit(mut self, column_name: String, unit: String) -> Self {
+        self.column_metadata.entry(column_name).or_default().unit = Some(unit);
+        self
+    }
+
+    #[must_use]
+    pub fn with_column_description(mut self, column_name: String, description: String) -> Self {
+        self.column_metadata.entry(column_name).or_default().description = Some(description);
+        self
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    #[must_use]
+    pub const fn get_column_metadata(&self) -> &schema::ColumnMetadataMap {
+        &self.column_metadata
+    }
+
+    ///
+    /// Checks that the update can be applied to a producer with the given schema.
+    ///
+    /// # Errors
+    /// * `NameInvalid`: The new name is empty.
+    /// * `InvalidColumnNames`: Metadata was given for a column which isn't in the schema.
+    ///
+    pub fn validate(&self, schema: &schema::Schema) -> Result<(), error::ConductorError> {
+        if matches!(&self.name, Some(name) if name.is_empty()) {
+            return Err(error::ConductorError::NameInvalid("Producer update failed. The new name is empty.".to_string()));
+        }
+        match self.column_metadata.keys().find(|column| !schema.contains_key(*column)) {
+            Some(column) => Err(error::ConductorError::InvalidColumnNames(format!("Producer update failed. Metadata was given for {} which isn't a column of the producer.", column))),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the update to the current metadata of a producer.
+    pub fn apply(self, metadata: &mut ProducerMetadata) {
+        if let Some(name) = self.name {
+            metadata.name = name;
+        }
+        for (column, update) in self.column_metadata {
+            metadata.column_metadata.entry(column).or_default().merge(update);
+        }
+    }
+}
+
+///The response from the Conductor instance after updating a producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ProducerUpdateResult {
+    pub error: error::ConductorError,
+    /// The metadata of the producer after the update
+    pub metadata: Option<ProducerMetadata>,
+}
+
+///
+/// A compare and swap of the schema of a producer. The new schema is only applied when the hash of
+/// the schema the producer has at the time (see `schema::schema_hash`) is the expected hash, so
+/// migrations coordinated across a fleet can't overwrite each other. Columns can be added and
+/// dropped but their types can't be changed.
+///
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct SchemaSwap {
+    expected_schema_hash: String,
+    new_schema: schema::Schema,
+}
+
+impl SchemaSwap {
+    #[must_use]
+    pub fn new(expected_schema_hash: String, new_schema: schema::Schema) -> Self {
+        Self { expected_schema_hash, new_schema }
+    }
+
+    #[must_use]
+    pub fn get_expected_schema_hash(&self) -> &str {
+        &self.expected_schema_hash
+    }
+
+    #[must_use]
+    pub const fn get_new_schema(&self) -> &schema::Schema {
+        &self.new_schema
+    }
+}
+
+///The response from the Conductor instance after swapping the schema of a producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct SchemaSwapResult {
+    /// `SchemaConflict` with the columns the current and new schema differ in if the current schema didn't have the expected hash
+    pub error: error::ConductorError,
+    /// The hash of the schema of the producer after the request. This is the hash of the new schema
+    /// if it was swapped and the hash of the current schema if there was a conflict.
+    pub schema_hash: Option<String>,
+}
+
+/// What a Conductor instance does with an emit for a producer whose data table was dropped outside of
+/// Conductor while its registration remains. Read from the `missing_tables` config value.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingTablePolicy {
+    /// The emit is rejected with `TableMissing` so the producer knows to register again.
+    #[default]
+    Reject,
+    /// The table is created again from the registered schema and the emit is stored in it. Any rows
+    /// which were in the dropped table are gone.
+    Recreate,
+}
+
+/// The unit of the timestamps a producer emits with and is sent back. Timestamps count from the unix epoch.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub enum TimestampPrecision {
+    Seconds,
+    Millis,
+    #[default]
+    Micros,
+}
+
+impl TimestampPrecision {
+    const fn micros_per_unit(self) -> u64 {
+        match self {
+            TimestampPrecision::Seconds => 1_000_000,
+            TimestampPrecision::Millis => 1_000,
+            TimestampPrecision::Micros => 1,
+        }
+    }
+
+    /// Converts a timestamp in this unit to microseconds. None if it's too large to be represented.
+    #[must_use]
+    pub const fn to_micros(self, timestamp: u64) -> Option<u64> {
+        timestamp.checked_mul(self.micros_per_unit())
+    }
+
+    /// Converts a timestamp in microseconds to this unit. Anything finer than the unit is truncated.
+    #[must_use]
+    pub const fn from_micros(self, micros: u64) -> u64 {
+        micros / self.micros_per_unit()
+    }
+
+    /// Converts the enum to the string stored in the producers table.
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        match self {
+            TimestampPrecision::Seconds => "seconds",
+            TimestampPrecision::Millis => "millis",
+            TimestampPrecision::Micros => "micros",
+        }
+    }
+
+    /// Converts a string stored in the producers table back into the enum. This is the inverse of `as_str`.
+    #[must_use]
+    pub fn from_db_str(precision: &str) -> Option<Self> {
+        match precision {
+            "seconds" => Some(TimestampPrecision::Seconds),
+            "millis" => Some(TimestampPrecision::Millis),
+            "micros" => Some(TimestampPrecision::Micros),
+            _ => None,
+        }
+    }
+}
+
+/// Where the uuid of a producer came from.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub enum IdSource {
+    /// The producer chose its own id when it registered.
+    Custom,
+    /// The id was generated by Conductor.
+    Generated,
+}
+
+impl IdSource {
+    /// Converts the enum to the string stored in the producers table.
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        match self {
+            IdSource::Custom => "custom",
+            IdSource::Generated => "generated",
+        }
+    }
+
+    /// Converts a string stored in the producers table back into the enum. This is the inverse of `as_str`.
+    #[must_use]
+    pub fn from_db_str(id_source: &str) -> Option<Self> {
+        match id_source {
+            "custom" => Some(IdSource::Custom),
+            "generated" => Some(IdSource::Generated),
+            _ => None,
+        }
+    }
+}
+
+///The response from the Conductor instance after a soft delete. Contains the number of rows that were marked as deleted.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct SoftDeleteResult {
+    pub error: error::ConductorError,
+    pub deleted: u64,
+}
+
+///The response from the Conductor instance when retrieving the column names of a producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ColumnsResult {
+    pub error: error::ConductorError,
+    pub columns: Vec<String>,
+}
+
+///The response from the Conductor instance when retrieving the range of timestamps stored for a producer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct TimeRangeResult {
+    pub error: error::ConductorError,
+    /// The oldest timestamp in the timestamp precision of the producer. None when the producer has no rows.
+    pub min_ts: Option<u64>,
+    /// The newest timestamp in the timestamp precision of the producer. None when the producer has no rows.
+    pub max_ts: Option<u64>,
+    pub count: u64,
+}
+
+/// The range of timestamps stored for a producer and the number of rows within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub min_ts: Option<u64>,
+    pub max_ts: Option<u64>,
+    pub count: u64,
+}
+
+///The response from the Conductor instance to a query. Each row maps column names (including `ts`) to values.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct QueryResult {
+    pub error: error::ConductorError,
+    pub rows: Vec<HashMap<String, serde_json::Value>>,
+    /// The rows in a column oriented layout. Only set when the columnar layout was requested in
+    /// which case `rows` is empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columnar: Option<ColumnarRows>,
+}
+
+/// How the rows of a query are laid out in the response.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub enum Layout {
+    /// A map of column name to value for each row
+    #[default]
+    Rows,
+    /// A list of values for each column. See `ColumnarRows`.
+    Columnar,
+}
+
+impl std::str::FromStr for Layout {
+    type Err = error::ConductorError;
+
+    fn from_str(layout: &str) -> Result<Self, Self::Err> {
+        match layout.to_ascii_lowercase().as_str() {
+            "rows" => Ok(Layout::Rows),
+            "columnar" => Ok(Layout::Columnar),
+            _ => Err(error::ConductorError::InvalidData(format!("{} isn't a supported layout. Use rows or columnar.", layout))),
+        }
+    }
+}
+
+///
+/// Rows stored column by column. This avoids repeating the column names for every row which makes
+/// it cheaper to serialise and easier to hand to analytics tools.
+///
+/// Every column has exactly one value (possibly null) for each entry of `ts`.
+///
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ColumnarRows {
+    /// The timestamp of each row in microseconds since the unix epoch
+    pub ts: Vec<u64>,
+    pub columns: HashMap<String, Vec<serde_json::Value>>,
+}
+
+impl ColumnarRows {
+    /// Appends a row. Columns which the row doesn't have a value for are given a null.
+    pub fn push_row(&mut self, ts: u64, mut row: HashMap<String, serde_json::Value>) {
+        let len = self.ts.len();
+        for (column, values) in &mut self.columns {
+            values.push(row.remove(column).unwrap_or(serde_json::Value::Null));
+        }
+        for (column, value) in row {
+            let mut values = vec![serde_json::Value::Null; len];
+            values.push(value);
+            self.columns.insert(column, values);
+        }
+        self.ts.push(ts);
+    }
+
+    /// The number of rows
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ts.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ts.is_empty()
+    }
+
+    /// Converts the rows into a map of column name to value for each row. The timestamp is included as `ts`.
+    #[must_use]
+    pub fn to_rows(&self) -> Vec<HashMap<String, serde_json::Value>> {
+        self.ts.iter().enumerate().map(|(index, ts)| {
+            let mut row: HashMap<String, serde_json::Value> = self.columns.iter()
+                .map(|(column, values)| (column.clone(), values.get(index).cloned().unwrap_or(serde_json::Value::Null)))
+                .collect();
+            row.insert("ts".to_string(), serde_json::json!(ts));
+            row
+        }).collect()
+    }
+}
+
+/// The aggregate functions that can be used to roll up a numeric column.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub enum Aggregate {
+    Avg,
+    Min,
+    Max,
+    Sum,
+}
+
+impl Aggregate {
+    /// Converts the enum to the name of the matching quest db aggregate function.
+    #[must_use]
+    pub const fn to_sql_str(&self) -> &str {
+        match self {
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Sum => "sum",
+        }
+    }
+}
+
+impl std::str::FromStr for Aggregate {
+    type Err = error::ConductorError;
+
+    fn from_str(aggregate: &str) -> Result<Self, Self::Err> {
+        match aggregate.to_ascii_lowercase().as_str() {
+            "avg" => Ok(Aggregate::Avg),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            "sum" => Ok(Aggregate::Sum),
+            _ => Err(error::ConductorError::InvalidData(format!("{} isn't a supported aggregate. Use one of avg, min, max or sum.", aggregate))),
+        }
+    }
+}
+
+/// Checks that a rollup interval is a positive whole number followed by one of the quest db
+/// units `T` (milliseconds), `s`, `m`, `h`, `d`, `M` (months) or `y`. For example `15m`.
+///
+/// # Errors
+/// * `InvalidData`: The interval isn't in the expected format.
+pub fn validate_rollup_interval(interval: &str) -> Result<(), error::ConductorError> {
+    let (amount, unit) = interval.split_at(interval.len().saturating_sub(1));
+    let valid_amount = !amount.is_empty() && amount.bytes().all(|c| c.is_ascii_digit()) && matches!(amount.parse::<u64>(), Ok(amount) if amount > 0);
+    if valid_amount && matches!(unit, "T" | "s" | "m" | "h" | "d" | "M" | "y") {
+        Ok(())
+    } else {
+        Err(error::ConductorError::InvalidData(format!("{} isn't a valid rollup interval. Expected a number followed by a unit such as 15m.", interval)))
+    }
+}
+
+/// A single time bucket of a rollup.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct RollupBucket {
+    /// The start of the bucket in microseconds since the unix epoch
+    pub ts: i64,
+    /// The aggregated value. None if the bucket doesn't have a value, for example when every value
+    /// in it was null or the result wasn't a number.
+    pub value: Option<f64>,
+}
+
+///The response from the Conductor instance to a rollup. The buckets are ordered by time.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct RollupResult {
+    pub error: error::ConductorError,
+    pub buckets: Vec<RollupBucket>,
+}
+
+/// All the errors that can be produced by a producer
+#[derive(Debug)]
+pub enum Error {
+    /// The domain given for the conductor instance is invalid in some way
+    InvalidConductorDomain(String),
+    /// Indicates a failure to serialize a struct to message pack. Contains rmp_serde encoding error
+    MsgPackSerialisationFailure(rmp_serde::encode::Error),
+    /// Indicates a failure to serialize a struct to json. Contains serde_json error type
+    JsonSerialisationFailure(serde_json::Error),
+    /// Indicates a failure to serialize a struct. Contains the error given by the serializer.
+    GenericSerialisationFailure(Box<dyn std::error::Error>),
+    /// Indicates an error which was emitted from the Conductor server (Internal Server Error)
+    ConductorError(error::ConductorError),
+    /// Indicates an issue with the network layer. Contains the reqwest error type
+    NetworkError(reqwest::Error),
+    /// Indicates a failure to deserialize a struct from message pack. Contains rmp_serde decoding error
+    MsgPackDeserializationFailure(rmp_serde::decode::Error),
+    /// Indicates a failure to deserialize a struct from json. Contains serde_json error type
+    JsonDeserializationFailure(serde_json::Error),
+    /// Indicates a failure to deserialize a struct. Contains the error given by the serializer.
+    GenericDeserializationFailure(Box<dyn std::error::Error>),
+    /// Indicates that the Conductor server responded with a http status that wasn't expected. Contains the status code.
+    UnexpectedStatus(reqwest::StatusCode),
+    /// Indicates that a time couldn't be converted to a Conductor timestamp
+    InvalidTimestamp(String),
+    /// Indicates that data doesn't match the schema it was checked against
+    SchemaMismatch(String),
+    /// Indicates that a dead letter couldn't be stored or read. Contains the reason.
+    DeadLetterFailure(String),
+    /// Indicates that a connection to the Conductor server couldn't be opened. Contains the reason.
+    Unreachable(String),
+}
+
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidConductorDomain(message) => write!(f, "InvalidConductorDomain: {}", message),
+            Error::MsgPackSerialisationFailure(encode_error) => write!(f, "MsgPackSerialisationFailure: {}", encode_error),
+            Error::ConductorError(ce) => write!(f, "ConductorError: {}", ce),
+            Error::NetworkError(re) => write!(f, "NetworkError: {}", re),
+            Error::MsgPackDeserializationFailure(decode_error) => write!(f, "MsgPackDeserializationFailure: {}", decode_error),
+            Error::JsonSerialisationFailure(encode_error) => write!(f, "JsonSerialisationFailure: {}", encode_error),
+            Error::GenericSerialisationFailure(encode_error) => write!(f, "GenericSerialisationFailure: {}", encode_error),
+            Error::JsonDeserializationFailure(decode_error) => write!(f, "JsonDeserializationFailure: {}", decode_error),
+            Error::GenericDeserializationFailure(decode_error) => write!(f, "GenericDeserializationFailure: {}", decode_error),
+            Error::UnexpectedStatus(status) => write!(f, "UnexpectedStatus: {}", status),
+            Error::InvalidTimestamp(message) => write!(f, "InvalidTimestamp: {}", message),
+            Error::SchemaMismatch(message) => write!(f, "SchemaMismatch: {}", message),
+            Error::DeadLetterFailure(message) => write!(f, "DeadLetterFailure: {}", message),
+            Error::Unreachable(message) => write!(f, "Unreachable: {}", message),
+        }
+    }
+}
+
+///
+/// Provides functionality that is shared between both the async and blocking versions of the Producer trait.
+/// Prepares and processes conductor requests and responses.
+///
+pub trait Base: Serialize + Clone + crate::schema::ConductorSchema {
+    ///
+    /// Converts this value into the data packet that is sent to Conductor when it's emitted. The keys
+    /// of the map are exactly the columns returned by `generate_schema`. The derive macro generates
+    /// this from the fields of the struct. The default implementation serialises the whole value and
+    /// keeps the fields that are part of the schema.
+    ///
+    /// # Errors
+    ///
+    /// * `JsonSerialisationFailure`: Produced when a field couldn't be serialised.
+    /// * `SchemaMismatch`: Produced when a field doesn't match the type in the schema.
+    ///
+    fn to_data_map(&self) -> Result<HashMap<String, serde_json::Value>, Error> {
+        to_checked_map(self, &Self::generate_schema())
+    }
+
+    ///
+    /// Builds the registration of this type with the generated schema and the columns listed in
+    /// `INDEXED_COLUMNS` indexed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer.
+    /// * `uuid`: An optional unique ID which will be used to identify this producer.
+    ///
+    fn generate_registration(name: &str, uuid: Option<String>) -> Registration {
+        Self::INDEXED_COLUMNS.iter().fold(
+            Registration::new(name.to_string(), Self::generate_schema(), uuid),
+            |registration, column| registration.with_indexed_column((*column).to_string()),
+        )
+    }
+
+    ///
+    /// Prepares a payload for emitting data. This function doesn't send the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique ID of this producer.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `SerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
+    /// due to a difficulty serialising Self using serde.
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use conductor_common::producer::Base;
+    /// use conductor_common::schema;
+    /// #[derive(Clone, Serialize)]
+    /// struct Measurement {
+    ///     data_point:u8
+    /// }
+    /// impl schema::ConductorSchema for Measurement {
+    ///     fn generate_schema() -> HashMap<String, schema::DataTypes> {
+    ///         unimplemented!("Not needed for example/test");
+    ///     }
+    /// }
+    /// impl Base for Measurement {}
+    /// let m = Measurement {
+    ///     data_point: 10
+    /// };
+    /// let expected:Vec<u8> = vec![3,4,5];
+    /// assert_eq!(m, expected);
+    /// ```
+    fn generate_emit_data(&self, uuid: &str, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+        encode_emit(&Emit::new(uuid, None, self.to_data_map()?), conductor_domain)
+    }
+
+    ///
+    /// Prepares a payload for emitting data which registers the producer if it isn't registered yet.
+    /// See `Emit::with_registration`. This function doesn't send the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer which is used if it's registered.
+    /// * `uuid`: The unique ID of this producer. It's also the custom id of the registration.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    ///
+    fn generate_registering_emit_data(&self, name: &str, uuid: &str, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+        let registration = Self::generate_registration(name, Some(uuid.to_string()));
+        encode_emit(&Emit::new(uuid, None, self.to_data_map()?).with_registration(registration), conductor_domain)
+    }
+
+    ///
+    /// Prepares a payload for emitting data which registers the producer if it isn't registered yet
+    /// and asks the server to assign a uuid when none is given. This function doesn't send the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer which is used if it's registered.
+    /// * `uuid`: The unique ID of this producer if it has one. It's also the custom id of the registration.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    ///
+    fn generate_assigning_emit_data(&self, name: &str, uuid: Option<&str>, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+        let registration = Self::generate_registration(name, uuid.map(str::to_string));
+        encode_emit(&Emit::new(uuid.unwrap_or_default(), None, self.to_data_map()?).with_registration(registration), conductor_domain)
+    }
+
+    ///
+    /// Prepares a payload for merging the named fields into the latest row of the producer.
+    /// See `Emit::with_merge`. This function doesn't send the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique ID of this producer.
+    /// * `fields`: The names of the fields to include in the payload.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `SchemaMismatch`: Produced when a field isn't part of the schema.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    ///
+    fn generate_merge_emit_data(&self, uuid: &str, fields: &[&str], conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+        let schema = Self::generate_schema();
+        let (_, unknown) = schema::diff_emit_columns(fields, &schema);
+        if !unknown.is_empty() {
+            return Err(Error::SchemaMismatch(format!("The fields {} aren't part of the schema", unknown.join(", "))));
+        }
+        let mut data = self.to_data_map()?;
+        data.retain(|column, _| fields.contains(&column.as_str()));
+        encode_emit(&Emit::new(uuid, None, data).with_merge(), conductor_domain)
+    }
+
+    ///
+    /// Prepares a payload for emitting a batch of data packets. This function doesn't send the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique ID of this producer.
+    /// * `data`: The data packets to include in the batch. Each one maps column names to values.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the batch payload cannot be serialised to the message pack format.
+    ///
+    fn generate_emit_batch_data(uuid: &str, data: Vec<HashMap<String, serde_json::Value>>, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+        let url = match conductor_domain.join("/v1/producer/emit_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let batch = EmitBatch::new(uuid, data);
+        let payload = match rmp_serde::to_vec_named(&batch) {
+            Ok(p) => p,
+            Err(err) => {
+                return Err(Error::MsgPackSerialisationFailure(err));
+            }
+        };
+        Ok((payload, url))
+    }
+
+    ///
+    /// Prepares the payload used for registration. Registration is not done by this function.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the producer.
+    /// This doesn't need to be unique in a Conductor network although it may be helpful to you if it is.
+    /// * `uuid`: The unique ID string to identify this producer. If it's none one will be generated by the
+    /// Conductor server and returned to us. Most of the time you'll want to leave this as None.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    ///# Errors
+    ///
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use conductor_common::producer::Base;
+    /// use conductor_common::schema;
+    /// #[derive(Clone, Serialize)]
+    /// struct Measurement {
+    ///     data_point:u8
+    /// }
+    /// impl schema::ConductorSchema for Measurement {
+    ///     fn generate_schema() -> HashMap<String, schema::DataTypes> {
+    ///         unimplemented!("Not needed for example/test");
+    ///     }
+    /// }
+    /// impl Base for Measurement {}
+    /// let m = Measurement {
+    ///     data_point: 10
     /// };
     /// let expected:Vec<u8> = vec![3,4,5];
     /// assert_eq!(m, expected);
     /// ```
-    fn generate_emit_data(&self, uuid: &str, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
-        let url = match conductor_domain.join("/v1/producer/emit") {
+    ///
+    fn prepare_registration_data(name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
+        let url = match conductor_domain.join("/v1/producer/register") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+
+        let reg = Self::generate_registration(name, uuid);
+        let payload = match rmp_serde::to_vec_named(&reg) {
+            Ok(m) => m,
+            Err(err) => {
+                return Err(Error::MsgPackSerialisationFailure(err));
+            }
+        };
+        Ok((payload, url))
+    }
+
+    ///
+    /// The number of bytes the registration `register` sends takes up in the given format, without
+    /// sending it. Devices on links with a small MTU can use it to decide whether to compress or
+    /// split the registration. Msgpack is the format `register` uses.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer.
+    /// * `uuid`: An optional unique ID which will be used to identify this producer.
+    /// * `format`: The format the registration would be sent in.
+    ///
+    /// # Errors
+    ///
+    /// * `JsonSerialisationFailure`: Produced when the registration cannot be serialised to json.
+    /// * `MsgPackSerialisationFailure`: Produced when the registration cannot be serialised to the message pack format.
+    ///
+    fn registration_payload_size(name: &str, uuid: Option<String>, format: format::WireFormat) -> Result<usize, Error> {
+        format.encode(&Self::generate_registration(name, uuid)).map(|payload| payload.len())
+    }
+    ///
+    /// Serialises the schema generated for this type to json. The columns are sorted by name so
+    /// the same schema always produces the same json.
+    ///
+    /// # Errors
+    ///
+    /// * `JsonSerialisationFailure`: Produced when the schema couldn't be serialised to json.
+    ///
+    fn schema_json() -> Result<String, Error> {
+        let schema: BTreeMap<String, schema::DataTypes> = Self::generate_schema().into_iter().collect();
+        serde_json::to_string(&schema).map_err(Error::JsonSerialisationFailure)
+    }
+}
+
+///
+/// Provides functions to add Conductor interactions to a type. Turns the implementing type into
+/// a Conductor Producer. This version of the trait provides a Asynchronous version of the functions.
+/// Refer to `conductor::producer::Producer` for the blocking version.
+///
+/// This should not be implemented directly in most cases.
+/// Instead use the `#[derive(conductor::Producer)]` macro to generate everything for you.
+///
+#[cfg(feature = "async")]
+#[async_trait]
+#[allow(clippy::module_name_repetitions)]
+pub trait AsyncProducer: Base {
+    /// Async send a new data packet to the conductor server.
+    /// Messagepack is used as the format over the wire.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
+    /// due to a difficulty serialising Self using serde.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn emit(&self, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        self.emit_returning_timestamp(uuid, conductor_domain).await.map(|_| ())
+    }
+
+    ///
+    /// Sends a new data packet like `emit` and returns the timestamp the row was stored with in
+    /// microseconds since the unix epoch. When the emit has no timestamp this is the time the server
+    /// stored it. It's None if the server is too old to report it.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`.
+    ///
+    async fn emit_returning_timestamp(&self, uuid: &str, conductor_domain: Url) -> Result<Option<u64>, Error>
+    {
+        self.emit_returning_timestamp_with_client(http::async_client(), uuid, conductor_domain).await
+    }
+
+    ///
+    /// Sends a new data packet like `emit` using an existing client. Sharing one client between many
+    /// producers lets them reuse its connection pool and configuration such as proxies and TLS.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: The client used to send the request.
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`.
+    ///
+    async fn emit_with_client(&self, client: &reqwest::Client, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        self.emit_returning_timestamp_with_client(client, uuid, conductor_domain).await.map(|_| ())
+    }
+
+    ///
+    /// The same as `emit_returning_timestamp` except the request is sent with an existing client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: The client used to send the request.
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`.
+    ///
+    async fn emit_returning_timestamp_with_client(&self, client: &reqwest::Client, uuid: &str, conductor_domain: Url) -> Result<Option<u64>, Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+
+        //start async specific
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        //end async specific code
+        if result.error == error::ConductorError::NoError {
+            return Ok(result.assigned_timestamp);
+        }
+        Err(Error::ConductorError(result.error))
+    }
+
+    ///
+    /// Sends a new data packet like `emit` along with an idempotency key. Retrying with the same key
+    /// is safe. The server answers attempts whose key it has already seen for this producer with the
+    /// result of the first one rather than storing the data again. Keys are remembered for a limited
+    /// time (10 minutes by default) so a new key should be used for every logical emit.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `key`: Identifies this emit across retries. A random uuid works well.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`. `GenericSerialisationFailure` if the key can't be sent as a header.
+    ///
+    async fn emit_idempotent(&self, uuid: &str, key: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+        let key = match reqwest::header::HeaderValue::from_str(key) {
+            Ok(k) => k,
+            Err(err) => return Err(Error::GenericSerialisationFailure(Box::new(err)))
+        };
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .header(idempotency::IDEMPOTENCY_KEY_HEADER, key)
+            .send().await;
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error == error::ConductorError::NoError {
+            return Ok(());
+        }
+        Err(Error::ConductorError(result.error))
+    }
+
+    ///
+    /// Asks the server how it would store `self` without storing it. The explanation holds the
+    /// INSERT sql the emit would be written with, using placeholders rather than values, and the type
+    /// each of its columns was resolved to. The data is converted to those types so conversion
+    /// errors are reported as they would be for an emit. Merges are explained as plain emits.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`. `Unregistered` if the uuid isn't registered.
+    ///
+    async fn explain_emit(&self, uuid: &str, conductor_domain: Url) -> Result<EmitExplanation, Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+        let url = match url.join("/v1/producer/explain_emit") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: ExplainEmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(EmitExplanation { sql: result.sql, columns: result.columns, fields: result.fields })
+    }
+
+    ///
+    /// Merges the named fields of `self` into the latest row of the producer on the server. Every
+    /// other column keeps its value from the latest row. This suits slowly changing state where only
+    /// some fields change at a time. See `Emit::with_merge` for why concurrent merges for the same
+    /// producer can overwrite each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `fields`: The names of the fields which changed.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `SchemaMismatch`: Produced when a field isn't part of the schema.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn emit_merge(&self, uuid: &str, fields: &[&str], conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_merge_emit_data(uuid, fields, conductor_domain)?;
+
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error == error::ConductorError::NoError {
+            return Ok(());
+        }
+        Err(Error::ConductorError(result.error))
+    }
+
+    ///
+    /// Sends a new data packet along with a registration for this producer. If the uuid isn't
+    /// registered yet the server registers the producer and creates its table first, otherwise the
+    /// registration is ignored. This suits stateless devices which can't remember whether they've
+    /// registered. See `Emit::with_registration`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer which is used if it's registered.
+    /// * `uuid`: The unique id of this producer. It's also used as the custom id when registering.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `SchemaConflict` if the
+    /// producer had to be registered and another producer already uses the uuid.
+    ///
+    async fn emit_registering(&self, name: &str, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_registering_emit_data(name, uuid, conductor_domain)?;
+
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error == error::ConductorError::NoError {
+            return Ok(());
+        }
+        Err(Error::ConductorError(result.error))
+    }
+
+    ///
+    /// Sends a new data packet along with a registration for this producer and returns the producer
+    /// it was stored for. Without a uuid the server registers the producer on its first emit and
+    /// assigns it a uuid, which a stateless device can keep and send with later emits.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer which is used if it's registered.
+    /// * `uuid`: The unique id of this producer if it already has one.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `VersionMismatch` if the
+    /// server didn't report the producer the emit was stored for.
+    ///
+    async fn emit_assigning(&self, name: &str, uuid: Option<&str>, conductor_domain: Url) -> Result<EmitRegistration, Error>
+    {
+        let (payload, url) = self.generate_assigning_emit_data(name, uuid, conductor_domain)?;
+
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        emit_registration(result)
+    }
+
+    /// Async send several data packets to the conductor server in a single request.
+    /// The server stores the packets atomically so either all of them are written or none are.
+    /// Nothing is sent if `data` is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `data`: The data packets to send. Each one maps column names to values.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the batch payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn emit_iter<I>(&self, uuid: &str, data: I, conductor_domain: Url) -> Result<(), Error>
+        where I: IntoIterator<Item=HashMap<String, serde_json::Value>> + Send
+    {
+        let data: Vec<_> = data.into_iter().collect();
+        if data.is_empty() {
+            return Ok(());
+        }
+        let (payload, url) = Self::generate_emit_batch_data(uuid, data, conductor_domain)?;
+
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error == error::ConductorError::NoError {
+            return Ok(());
+        }
+        Err(Error::ConductorError(result.error))
+    }
+
+
+    /// Generates the schema for this struct and register it with conductor asynchronously.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer. This isn't important to conductor and doesn't have to be unique.
+    /// It's stored in the DB and can be useful to identify the producer. And empty string is valid but not recommended.
+    /// * `uuid`: An optional unique ID which will be used to identify this producer. If this is set to None one is generated automatically by
+    /// Conductor. It's recommended to leave this as null and let the server generate the ID.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
+    /// due to a difficulty serialising Self using serde.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `SchemaConflict` if the
+    /// custom id is already registered with a different schema.
+    ///
+    async fn register(name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<String, Error>
+    {
+        Self::register_with_client(http::async_client(), name, uuid, conductor_domain).await
+    }
+
+    ///
+    /// The same as `register` except the request is sent with an existing client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: The client used to send the request.
+    /// * `name`: A human friendly name for this producer.
+    /// * `uuid`: An optional unique ID which will be used to identify this producer.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `register`.
+    ///
+    async fn register_with_client(client: &reqwest::Client, name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<String, Error>
+    {
+        //TODO handle errors correctly
+        let (payload, url) = Self::prepare_registration_data(name, uuid, conductor_domain)?;
+
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: RegistrationResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.uuid.unwrap())
+    }
+
+    ///
+    /// Generates the schema for this struct and registers it asynchronously together with the actions it can
+    /// perform as one device. The producer and reactor halves share the returned uuid so the data it
+    /// emits and the actions queued for it are found under the same id.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for the device. Unlike a producer a device must have a name.
+    /// * `uuid`: An optional unique ID which will be used to identify the device. If this is set to None one is generated automatically by
+    /// Conductor.
+    /// * `actions`: The actions the device can perform. There must be at least one.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the registration cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `ConductorError`: Produced when there was an error on the server. `SchemaConflict` if the custom id is
+    /// already registered with a different schema or different actions.
+    ///
+    async fn register_device(name: &str, uuid: Option<String>, actions: Vec<reactor::ActionRegistration>, conductor_domain: Url) -> Result<String, Error>
+    {
+        let registration = actions.into_iter().fold(
+            device::DeviceRegistration::new(Self::generate_registration(name, uuid)),
+            device::DeviceRegistration::with_action,
+        );
+        let (payload, url) = device::generate_registration_data(&registration, &conductor_domain)?;
+        let response = http::async_client().post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await
+            .map_err(Error::NetworkError)?;
+        let body = response.bytes().await.map_err(Error::NetworkError)?;
+        device::decode_registration(&body)
+    }
+
+    ///
+    /// Asynchronously checks to see if the UUID has been registered with Conductor.
+    /// This does not verify that the schema registered with the server is correct.
+    /// Returns `Ok(false)` only when the server reports that the uuid isn't registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `UnexpectedStatus`: Produced when the server responds with anything other than 200 or 404. This
+    /// usually indicates an error on the server such as the database being unavailable.
+    ///
+    async fn is_registered(uuid: &str, conductor_domain: Url) -> Result<bool, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/check") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::async_client();
+        match client.get(url).query(&params).send().await {
+            Ok(response) => match response.status() {
+                reqwest::StatusCode::OK => Ok(true),
+                reqwest::StatusCode::NOT_FOUND => Ok(false),
+                status => Err(Error::UnexpectedStatus(status)),
+            },
+            Err(err) => Err(Error::NetworkError(err))
+        }
+    }
+
+    ///
+    /// Asynchronously checks which of several uuids have been registered with Conductor in a single request.
+    /// The result holds one entry per uuid in the order they were given. Malformed uuids are reported
+    /// as not registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuids`: The unique ids to check.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the uuids cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn are_registered(uuids: &[&str], conductor_domain: Url) -> Result<Vec<bool>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/check_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(uuids) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: CheckBatchResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.registered)
+    }
+
+    ///
+    /// Asynchronously reads the rows of several producers with identical schemas together. Each row
+    /// has a `source_uuid` column holding the uuid of the producer it came from.
+    ///
+    /// # Arguments
+    /// * `query` : The producers to read and how many rows to return
+    /// * `conductor_domain` : The url of the conductor instance
+    ///
+    /// # Errors
+    /// * `ConductorError::SchemaConflict` : The schemas of the producers aren't the same. Lists the columns which differ
+    /// * `ConductorError::Unregistered` : One of the producers isn't registered
+    ///
+    async fn query_union(query: &QueryUnion, conductor_domain: Url) -> Result<Vec<HashMap<String, serde_json::Value>>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/query_union") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(query) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: QueryResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.rows)
+    }
+
+    ///
+    /// Asynchronously retrieves the names of the columns of a registered producer. This includes the
+    /// `ts` timestamp column. It's cheaper than retrieving the full schema when only the names are needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    async fn columns(uuid: &str, conductor_domain: Url) -> Result<Vec<String>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/columns") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::async_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: ColumnsResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.columns)
+    }
+
+    ///
+    /// Asynchronously retrieves the range of timestamps stored for a registered producer along with the number
+    /// of rows. Rows which have been soft deleted aren't counted. The timestamps are `None` when the
+    /// producer has no rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    async fn time_range(uuid: &str, conductor_domain: Url) -> Result<TimeRange, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/range") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::async_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: TimeRangeResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(TimeRange { min_ts: result.min_ts, max_ts: result.max_ts, count: result.count })
+    }
+
+    ///
+    /// Starts a transaction which buffers emits for this producer until it's committed.
+    /// Dropping the transaction without committing it discards the buffered emits.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    fn transaction<'a>(&'a self, uuid: &'a str, conductor_domain: Url) -> AsyncTransaction<'a, Self>
+        where Self: Sync
+    {
+        AsyncTransaction::new(self, uuid, conductor_domain)
+    }
+
+    ///
+    /// Asynchronously removes every row that has been stored for a producer. The registration of the
+    /// producer is kept so it can continue to emit data.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the truncate payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    async fn truncate(uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/truncate") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(&Truncate::new(uuid)) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: TruncateResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Asynchronously pauses or resumes ingestion for a producer. While it's paused its emits are rejected with
+    /// `Paused` and the server answers them with 503 Service Unavailable. Nothing is deleted and it
+    /// stays registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `paused`: True to pause ingestion and false to resume it.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    async fn set_paused(uuid: &str, paused: bool, conductor_domain: Url) -> Result<(), Error>
+    {
+        let url = pause_url(uuid, paused, &conductor_domain)?;
+        let client = http::async_client();
+        let request = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: PauseResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Asynchronously changes the name and/or column metadata of a producer in a single request. The
+    /// schema of the producer isn't changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `update`: The new name and the column metadata to change.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the update cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http patch fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when the update was rejected. See `ProducerUpdate::validate`.
+    ///
+    async fn update_metadata(uuid: &str, update: &ProducerUpdate, conductor_domain: Url) -> Result<ProducerMetadata, Error>
+    {
+        let url = match conductor_domain.join(&format!("/v1/producer/{}", uuid)) {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(update) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.patch(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: ProducerUpdateResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.metadata.unwrap_or_default())
+    }
+
+    ///
+    /// Asynchronously swaps the schema of a producer for a new one if its current schema has the expected hash.
+    /// The table of the producer is migrated by adding and dropping columns. Returns the hash of the
+    /// new schema which is the expected hash of the next swap. See `SchemaSwap`.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `swap`: The hash the current schema is expected to have and the schema to replace it with.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the swap cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when the swap was rejected. `SchemaConflict` if the current schema
+    /// doesn't have the expected hash and `InvalidSchema` if the type of a column would change.
+    ///
+    async fn swap_schema(uuid: &str, swap: &SchemaSwap, conductor_domain: Url) -> Result<String, Error>
+    {
+        let url = match conductor_domain.join(&format!("/v1/producer/{}/schema", uuid)) {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(swap) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: SchemaSwapResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.schema_hash.unwrap_or_default())
+    }
+
+    ///
+    /// Asynchronously retrieves the schema a producer was registered with. Unlike `generate_schema`
+    /// this includes whether each column is required.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    async fn registered_schema(uuid: &str, conductor_domain: Url) -> Result<HashMap<String, schema::SchemaEntry>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/schema") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::async_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: SchemaResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.schema)
+    }
+
+    ///
+    /// Async send this data packet to the conductor server. If it can't be delivered because of a
+    /// failure that may go away (such as the server being unreachable) it's stored in the sink instead
+    /// so that it can be replayed later with `replay_dead_letters`.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    /// * `sink`: Where to store the data packet if it can't be delivered.
+    ///
+    /// # Errors
+    /// * Any error produced by `emit` which won't go away by trying again.
+    /// * `SchemaMismatch`: Produced when the data packet doesn't match the schema and couldn't be stored.
+    /// * `DeadLetterFailure`: Produced when the sink couldn't store the data packet.
+    /// * `InvalidTimestamp`: Produced when the system clock is set before the unix epoch.
+    ///
+    async fn emit_or_dead_letter(&self, uuid: &str, conductor_domain: Url, sink: &mut (dyn DeadLetterSink + Send)) -> Result<Delivery, Error>
+    {
+        let timestamp = timestamp_from_system_time(SystemTime::now())?;
+        match self.emit(uuid, conductor_domain).await {
+            Ok(()) => Ok(Delivery::Sent),
+            Err(err) if dead_letter::is_transient(&err) => {
+                let data = to_checked_map(self, &Self::generate_schema())?;
+                sink.store(DeadLetter { uuid: uuid.to_string(), timestamp: Some(timestamp), data })?;
+                Ok(Delivery::DeadLettered)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    ///
+    /// Async send every dead letter in the sink to the conductor server in the order they were stored.
+    /// Each one is sent as its own emit with the timestamp of the emit that failed. Replaying stops at
+    /// the first dead letter that can't be delivered. It and every dead letter after it are kept in
+    /// the sink while the ones that were delivered are removed.
+    ///
+    /// Returns the number of dead letters that were delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink`: The sink containing the dead letters.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * Any error produced by `emit` for the first dead letter that couldn't be delivered.
+    /// * `DeadLetterFailure`: Produced when the sink couldn't be read or written. If it can't be
+    /// written the dead letters that were delivered are still in the sink and will be sent again.
+    ///
+    async fn replay_dead_letters(&self, sink: &mut (dyn DeadLetterSink + Send), conductor_domain: Url) -> Result<usize, Error>
+    {
+        let mut dead_letters = sink.load()?;
+        let client = http::async_client();
+        let mut replayed = 0;
+        let mut failure = None;
+        for dead_letter in &dead_letters {
+            let emit = Emit::new(&dead_letter.uuid, dead_letter.timestamp, dead_letter.data.clone());
+            let (payload, url) = encode_emit(&emit, conductor_domain.clone())?;
+            let request_resp = client.post(url)
+                .body(payload)
+                .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                .send().await;
+            let result = match request_resp {
+                Ok(response) => format::decode_response::<EmitResult>(response.bytes().await.unwrap().as_ref()),
+                Err(err) => Err(Error::NetworkError(err))
+            };
+            match result {
+                Ok(result) if result.error == error::ConductorError::NoError => replayed += 1,
+                Ok(result) => {
+                    failure = Some(Error::ConductorError(result.error));
+                    break;
+                }
+                Err(err) => {
+                    failure = Some(err);
+                    break;
+                }
+            }
+        }
+        sink.replace(dead_letters.split_off(replayed))?;
+        failure.map_or(Ok(replayed), Err)
+    }
+
+    ///
+    /// Asynchronously aggregates a numeric column of this producer into time buckets on the conductor server.
+    ///
+    /// # Arguments
+    /// * `uuid`: The uuid of the producer
+    /// * `column`: The numeric column to aggregate
+    /// * `aggregate`: The aggregate function applied to each bucket
+    /// * `interval`: The size of each bucket such as `15m`. See `validate_rollup_interval`.
+    /// * `conductor_domain`: The domain of the conductor instance
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: The domain given for the conductor instance is invalid
+    /// * `NetworkError`: There was an issue communicating with the conductor instance
+    /// * `MsgPackDeserializationFailure`: Couldn't decode the response from the server
+    /// * `ConductorError`: The server couldn't roll up the column. `InvalidData` if the column isn't numeric.
+    async fn rollup(uuid: &str, column: &str, aggregate: Aggregate, interval: &str, conductor_domain: Url) -> Result<Vec<RollupBucket>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/rollup") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid), ("column", column), ("fn", aggregate.to_sql_str()), ("interval", interval)];
+        let client = http::async_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: RollupResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.buckets)
+    }
+
+    ///
+    /// Emits every item of a stream as it arrives. Returns a stream which yields the outcome of each
+    /// item in the same order as the input. An item which fails doesn't stop the remaining items from
+    /// being sent unless `options` says so. The result stream isn't `Send` because `Error` isn't.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `stream`: The data packets to send. Each one maps column names to values.
+    /// * `conductor_domain`: The url of the conductor instance.
+    /// * `options`: How many items can be sent at once and whether to stop at the first failure.
+    ///
+    /// # Errors
+    /// Each item can produce the same errors as `emit_iter`.
+    ///
+    fn emit_stream<S>(self: Arc<Self>, uuid: String, stream: S, conductor_domain: Url, options: StreamOptions) -> LocalBoxStream<'static, Result<(), Error>>
+        where S: Stream<Item=HashMap<String, serde_json::Value>> + Send + 'static,
+              Self: Sync + Send + 'static
+    {
+        let stop_on_error = options.should_stop_on_error();
+        let failed = Arc::new(AtomicBool::new(false));
+        let stop_sending = Arc::clone(&failed);
+        stream
+            //once an item has failed no new items are sent. Items which are already in flight still finish.
+            .take_while(move |_| futures::future::ready(!stop_sending.load(Ordering::SeqCst)))
+            .map(move |data| {
+                let producer = Arc::clone(&self);
+                let uuid = uuid.clone();
+                let conductor_domain = conductor_domain.clone();
+                async move { producer.emit_iter(&uuid, std::iter::once(data), conductor_domain).await }
+            })
+            .buffered(options.get_concurrency().max(1))
+            .scan(false, move |done, result| {
+                if *done {
+                    return futures::future::ready(None);
+                }
+                if stop_on_error && result.is_err() {
+                    *done = true;
+                    failed.store(true, Ordering::SeqCst);
+                }
+                futures::future::ready(Some(result))
+            })
+            .boxed_local()
+    }
+
+    ///
+    /// Asynchronously deletes several producers along with their data tables. This can't be undone.
+    /// Returns the outcome for each selected producer. A producer which couldn't be deleted has an error
+    /// in its outcome but doesn't stop the others from being deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `request`: The uuids and/or name prefix of the producers to delete.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the request cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when the batch was rejected. `InvalidUuid` if any of the uuids are malformed in
+    /// which case nothing is deleted.
+    ///
+    async fn delete_batch(request: &DeleteBatch, conductor_domain: Url) -> Result<Vec<DeleteOutcome>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/delete_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(request) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: DeleteBatchResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.results)
+    }
+
+    ///
+    /// Asynchronously exports the registrations of every producer so they can be recreated on another
+    /// Conductor instance with `register_batch`. Each registration uses the uuid of its producer as its
+    /// custom id. The registry is fetched in pages of `EXPORT_PAGE_SIZE` so large exports don't have
+    /// to fit in a single response. Only the registry is exported, not the data of the producers.
+    ///
+    /// # Arguments
+    ///
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn export_registrations(conductor_domain: Url) -> Result<Vec<Registration>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/export") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let client = http::async_client();
+        let mut registrations = Vec::new();
+        let mut offset = 0;
+        loop {
+            let params = [("offset", offset.to_string()), ("limit", EXPORT_PAGE_SIZE.to_string())];
+            let request = client.get(url.clone())
+                .query(&params)
+                .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                .send().await;
+            let response = match request {
+                Ok(r) => r,
+                Err(err) => return Err(Error::NetworkError(err))
+            };
+            let result: ExportResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+            if result.error != error::ConductorError::NoError {
+                return Err(Error::ConductorError(result.error));
+            }
+            registrations.extend(result.registrations);
+            offset = match result.next {
+                Some(next) => next,
+                None => return Ok(registrations),
+            };
+        }
+    }
+
+    ///
+    /// Asynchronously registers several producers in one request such as those returned by
+    /// `export_registrations`. Returns the result of each registration in order. A registration which
+    /// fails doesn't stop the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `registrations`: The producers to register.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the registrations cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when the batch was rejected as a whole.
+    ///
+    async fn register_batch(registrations: &[Registration], conductor_domain: Url) -> Result<Vec<RegistrationResult>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/register_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(registrations) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::async_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: RegisterBatchResult = format::decode_response(response.bytes().await.unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.results)
+    }
+
+    ///
+    /// Async send a new data packet to the conductor server without waiting for the response.
+    /// This returns as soon as a connection to the server could be opened. The request is then sent by a
+    /// background task which nothing waits for, so a packet which the server rejects (or which fails
+    /// after the connection check) is lost. Those failures, including the error of the emit result, are
+    /// only logged at warn level.
+    /// Only use this when occasionally losing data is acceptable, such as for high rate telemetry.
+    ///
+    /// This must be called from within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `Unreachable`: Produced when a connection to the conductor instance couldn't be opened. For example when the
+    /// connection is refused.
+    ///
+    async fn emit_nowait(&self, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+        let address = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => (host.to_string(), port),
+            _ => return Err(Error::InvalidConductorDomain(format!("The conductor domain {} doesn't have a host and port", url)))
+        };
+        if let Err(err) = tokio::net::TcpStream::connect(address).await {
+            return Err(Error::Unreachable(format!("Couldn't connect to {}. {}", url, err)));
+        }
+
+        tokio::spawn(async move {
+            let client = http::async_client();
+            let request_resp = client.post(url)
+                .body(payload)
+                .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                .send().await;
+            let response = match request_resp {
+                Ok(response) => response,
+                Err(err) => {
+                    log::warn!("An emit sent without waiting failed. {}", err);
+                    return;
+                }
+            };
+            let status = response.status();
+            let body = match response.bytes().await {
+                Ok(body) => body,
+                Err(err) => {
+                    log::warn!("The response to an emit sent without waiting couldn't be read. {}", err);
+                    return;
+                }
+            };
+            match format::decode_response::<EmitResult>(body.as_ref()) {
+                Ok(result) if result.error == error::ConductorError::NoError => {}
+                Ok(result) => log::warn!("An emit sent without waiting was rejected with status {}. {}", status, result.error),
+                Err(err) => log::warn!("An emit sent without waiting was rejected with status {}. {}", status, err),
+            }
+        });
+        Ok(())
+    }
+}
+
+///
+/// Provides functions to add Conductor interactions to a type. Turns the implementing type into
+/// a Conductor Producer. This version of the trait provides a blocking version of the functions.
+/// Refer to `conductor::producer::AsyncProducer` for the Asynchronous version.
+///
+/// This should not be implemented directly in most cases.
+/// Instead use the `#[derive(conductor::Producer)]` macro to generate everything for you.
+///
+pub trait Producer: Base {
+    /// Send a new data packet to the conductor server.
+    /// Messagepack is used as the format over the wire.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
+    /// due to a difficulty serialising Self using serde.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    fn emit(&self, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        self.emit_returning_timestamp(uuid, conductor_domain).map(|_| ())
+    }
+
+    ///
+    /// Sends a new data packet like `emit` and returns the timestamp the row was stored with in
+    /// microseconds since the unix epoch. When the emit has no timestamp this is the time the server
+    /// stored it. It's None if the server is too old to report it.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`.
+    ///
+    fn emit_returning_timestamp(&self, uuid: &str, conductor_domain: Url) -> Result<Option<u64>, Error>
+    {
+        self.emit_returning_timestamp_with_client(http::blocking_client(), uuid, conductor_domain)
+    }
+
+    ///
+    /// Sends a new data packet like `emit` using an existing client. Sharing one client between many
+    /// producers lets them reuse its connection pool and configuration such as proxies and TLS.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: The client used to send the request.
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`.
+    ///
+    fn emit_with_client(&self, client: &reqwest::blocking::Client, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        self.emit_returning_timestamp_with_client(client, uuid, conductor_domain).map(|_| ())
+    }
+
+    ///
+    /// The same as `emit_returning_timestamp` except the request is sent with an existing client.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: The client used to send the request.
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`.
+    ///
+    fn emit_returning_timestamp_with_client(&self, client: &reqwest::blocking::Client, uuid: &str, conductor_domain: Url) -> Result<Option<u64>, Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+
+        //start blocking specific
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        //end blocking specific code
+        match &result.error {
+            error::ConductorError::NoError => Ok(result.assigned_timestamp),
+            _ => Err(Error::ConductorError(result.error))
+        }
+    }
+
+    ///
+    /// Sends a new data packet like `emit` along with an idempotency key. Retrying with the same key
+    /// is safe. The server answers attempts whose key it has already seen for this producer with the
+    /// result of the first one rather than storing the data again. Keys are remembered for a limited
+    /// time (10 minutes by default) so a new key should be used for every logical emit.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `key`: Identifies this emit across retries. A random uuid works well.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`. `GenericSerialisationFailure` if the key can't be sent as a header.
+    ///
+    fn emit_idempotent(&self, uuid: &str, key: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+        let key = match reqwest::header::HeaderValue::from_str(key) {
+            Ok(k) => k,
+            Err(err) => return Err(Error::GenericSerialisationFailure(Box::new(err)))
+        };
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .header(idempotency::IDEMPOTENCY_KEY_HEADER, key)
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        match &result.error {
+            error::ConductorError::NoError => Ok(()),
+            _ => Err(Error::ConductorError(result.error))
+        }
+    }
+
+    ///
+    /// Asks the server how it would store `self` without storing it. The explanation holds the
+    /// INSERT sql the emit would be written with, using placeholders rather than values, and the type
+    /// each of its columns was resolved to. The data is converted to those types so conversion
+    /// errors are reported as they would be for an emit. Merges are explained as plain emits.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `emit`. `Unregistered` if the uuid isn't registered.
+    ///
+    fn explain_emit(&self, uuid: &str, conductor_domain: Url) -> Result<EmitExplanation, Error>
+    {
+        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
+        let url = match url.join("/v1/producer/explain_emit") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: ExplainEmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(EmitExplanation { sql: result.sql, columns: result.columns, fields: result.fields })
+    }
+
+    ///
+    /// Merges the named fields of `self` into the latest row of the producer on the server. Every
+    /// other column keeps its value from the latest row. This suits slowly changing state where only
+    /// some fields change at a time. See `Emit::with_merge` for why concurrent merges for the same
+    /// producer can overwrite each other.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `fields`: The names of the fields which changed.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `SchemaMismatch`: Produced when a field isn't part of the schema.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    fn emit_merge(&self, uuid: &str, fields: &[&str], conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_merge_emit_data(uuid, fields, conductor_domain)?;
+
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        match &result.error {
+            error::ConductorError::NoError => Ok(()),
+            _ => Err(Error::ConductorError(result.error))
+        }
+    }
+
+    ///
+    /// Sends a new data packet along with a registration for this producer. If the uuid isn't
+    /// registered yet the server registers the producer and creates its table first, otherwise the
+    /// registration is ignored. This suits stateless devices which can't remember whether they've
+    /// registered. See `Emit::with_registration`.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer which is used if it's registered.
+    /// * `uuid`: The unique id of this producer. It's also used as the custom id when registering.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `SchemaConflict` if the
+    /// producer had to be registered and another producer already uses the uuid.
+    ///
+    fn emit_registering(&self, name: &str, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let (payload, url) = self.generate_registering_emit_data(name, uuid, conductor_domain)?;
+
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        match &result.error {
+            error::ConductorError::NoError => Ok(()),
+            _ => Err(Error::ConductorError(result.error))
+        }
+    }
+
+    ///
+    /// Sends a new data packet along with a registration for this producer and returns the producer
+    /// it was stored for. Without a uuid the server registers the producer on its first emit and
+    /// assigns it a uuid, which a stateless device can keep and send with later emits.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer which is used if it's registered.
+    /// * `uuid`: The unique id of this producer if it already has one.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `VersionMismatch` if the
+    /// server didn't report the producer the emit was stored for.
+    ///
+    fn emit_assigning(&self, name: &str, uuid: Option<&str>, conductor_domain: Url) -> Result<EmitRegistration, Error>
+    {
+        let (payload, url) = self.generate_assigning_emit_data(name, uuid, conductor_domain)?;
+
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        emit_registration(result)
+    }
+
+    /// Send several data packets to the conductor server in a single request.
+    /// The server stores the packets atomically so either all of them are written or none are.
+    /// Nothing is sent if `data` is empty.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `data`: The data packets to send. Each one maps column names to values.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the batch payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    fn emit_iter<I>(&self, uuid: &str, data: I, conductor_domain: Url) -> Result<(), Error>
+        where I: IntoIterator<Item=HashMap<String, serde_json::Value>>
+    {
+        let data: Vec<_> = data.into_iter().collect();
+        if data.is_empty() {
+            return Ok(());
+        }
+        let (payload, url) = Self::generate_emit_batch_data(uuid, data, conductor_domain)?;
+
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: EmitResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        match &result.error {
+            error::ConductorError::NoError => Ok(()),
+            _ => Err(Error::ConductorError(result.error))
+        }
+    }
+
+    /// Generates the schema for this struct and register it with conductor.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for this producer. This isn't important to conductor and doesn't have to be unique.
+    /// It's stored in the DB and can be useful to identify the producer. And empty string is valid but not recommended.
+    /// * `uuid`: An optional unique ID which will be used to identify this producer. If this is set to None one is generated automatically by
+    /// Conductor. It's recommended to leave this as null and let the server generate the ID.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
+    /// due to a difficulty serialising Self using serde.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `SchemaConflict` if the
+    /// custom id is already registered with a different schema.
+    ///
+    fn register(name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<String, Error>
+    {
+        Self::register_with_client(http::blocking_client(), name, uuid, conductor_domain)
+    }
+
+    ///
+    /// The same as `register` except the request is sent with an existing client.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `client`: The client used to send the request.
+    /// * `name`: A human friendly name for this producer.
+    /// * `uuid`: An optional unique ID which will be used to identify this producer.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// The same as `register`.
+    ///
+    fn register_with_client(client: &reqwest::blocking::Client, name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<String, Error>
+    {
+        //TODO handle errors correctly
+        let (payload, url) = Self::prepare_registration_data(name, uuid, conductor_domain)?;
+
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: RegistrationResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.uuid.unwrap())
+    }
+
+    ///
+    /// Generates the schema for this struct and registers it together with the actions it can
+    /// perform as one device. The producer and reactor halves share the returned uuid so the data it
+    /// emits and the actions queued for it are found under the same id.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: A human friendly name for the device. Unlike a producer a device must have a name.
+    /// * `uuid`: An optional unique ID which will be used to identify the device. If this is set to None one is generated automatically by
+    /// Conductor.
+    /// * `actions`: The actions the device can perform. There must be at least one.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the registration cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `ConductorError`: Produced when there was an error on the server. `SchemaConflict` if the custom id is
+    /// already registered with a different schema or different actions.
+    ///
+    fn register_device(name: &str, uuid: Option<String>, actions: Vec<reactor::ActionRegistration>, conductor_domain: Url) -> Result<String, Error>
+    {
+        let registration = actions.into_iter().fold(
+            device::DeviceRegistration::new(Self::generate_registration(name, uuid)),
+            device::DeviceRegistration::with_action,
+        );
+        let (payload, url) = device::generate_registration_data(&registration, &conductor_domain)?;
+        let response = http::blocking_client().post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send()
+            .map_err(Error::NetworkError)?;
+        let body = response.bytes().map_err(Error::NetworkError)?;
+        device::decode_registration(&body)
+    }
+
+    ///
+    /// Checks to see if the UUID has been registered with Conductor.
+    /// This does not verify that the schema registered with the server is correct.
+    /// Returns `Ok(false)` only when the server reports that the uuid isn't registered.
+    /// This function blocks
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `UnexpectedStatus`: Produced when the server responds with anything other than 200 or 404. This
+    /// usually indicates an error on the server such as the database being unavailable.
+    ///
+    fn is_registered(uuid: &str, conductor_domain: Url) -> Result<bool, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/check") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::blocking_client();
+        match client.get(url).query(&params).send() {
+            Ok(response) => match response.status() {
+                reqwest::StatusCode::OK => Ok(true),
+                reqwest::StatusCode::NOT_FOUND => Ok(false),
+                status => Err(Error::UnexpectedStatus(status)),
+            },
+            Err(err) => Err(Error::NetworkError(err))
+        }
+    }
+
+    ///
+    /// Checks which of several uuids have been registered with Conductor in a single request.
+    /// The result holds one entry per uuid in the order they were given. Malformed uuids are reported
+    /// as not registered.
+    /// This function blocks
+    ///
+    /// # Arguments
+    ///
+    /// * `uuids`: The unique ids to check.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the uuids cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    fn are_registered(uuids: &[&str], conductor_domain: Url) -> Result<Vec<bool>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/check_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(uuids) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::blocking_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: CheckBatchResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.registered)
+    }
+
+    ///
+    /// Reads the rows of several producers with identical schemas together. Each row has a
+    /// `source_uuid` column holding the uuid of the producer it came from.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    /// * `query` : The producers to read and how many rows to return
+    /// * `conductor_domain` : The url of the conductor instance
+    ///
+    /// # Errors
+    /// * `ConductorError::SchemaConflict` : The schemas of the producers aren't the same. Lists the columns which differ
+    /// * `ConductorError::Unregistered` : One of the producers isn't registered
+    ///
+    fn query_union(query: &QueryUnion, conductor_domain: Url) -> Result<Vec<HashMap<String, serde_json::Value>>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/query_union") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(query) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::blocking_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: QueryResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.rows)
+    }
+
+    ///
+    /// Reads the rows of a producer as a stream of length prefixed msgpack rows. The rows are decoded
+    /// one at a time as they're read from the response so large results aren't held in memory at once.
+    /// This function blocks while the request is sent and each time the next row is read.
+    ///
+    /// # Arguments
+    /// * `uuid` : The unique id of the producer which was registered with conductor
+    /// * `from` : Only rows at or after this timestamp are returned
+    /// * `to` : Only rows at or before this timestamp are returned
+    /// * `limit` : The maximum number of rows returned
+    /// * `conductor_domain` : The url of the conductor instance
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `ConductorError`: Produced when the server refuses the query, e.g. `Unregistered`.
+    /// * `UnexpectedStatus`: Produced when the server responds with an error status and no error.
+    ///
+    /// Each row read from the stream is a `Result` which holds a read or decode error instead when the
+    /// stream is cut short or malformed.
+    ///
+    fn query_stream(uuid: &str, from: Option<u64>, to: Option<u64>, limit: Option<u64>, conductor_domain: Url) -> Result<format::MsgPackFrames<reqwest::blocking::Response, HashMap<String, serde_json::Value>>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/query") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let mut params = vec![("uuid", uuid.to_string())];
+        for (name, value) in [("from", from), ("to", to), ("limit", limit)] {
+            if let Some(value) = value {
+                params.push((name, value.to_string()));
+            }
+        }
+        let client = http::blocking_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static(format::MSGPACK_STREAM_MEDIA_TYPE))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let status = response.status();
+        if status.is_success() {
+            return Ok(format::MsgPackFrames::new(response));
+        }
+        let body = match response.bytes() {
+            Ok(body) => body,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        match format::decode_response::<QueryResult>(body.as_ref()) {
+            Ok(result) if result.error != error::ConductorError::NoError => Err(Error::ConductorError(result.error)),
+            Err(Error::ConductorError(error)) => Err(Error::ConductorError(error)),
+            _ => Err(Error::UnexpectedStatus(status)),
+        }
+    }
+
+    ///
+    /// Retrieves the names of the columns of a registered producer. This includes the
+    /// `ts` timestamp column. It's cheaper than retrieving the full schema when only the names are needed.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    fn columns(uuid: &str, conductor_domain: Url) -> Result<Vec<String>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/columns") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::blocking_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: ColumnsResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.columns)
+    }
+
+    ///
+    /// Retrieves the range of timestamps stored for a registered producer along with the number
+    /// of rows. Rows which have been soft deleted aren't counted. The timestamps are `None` when the
+    /// producer has no rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    fn time_range(uuid: &str, conductor_domain: Url) -> Result<TimeRange, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/range") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::blocking_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: TimeRangeResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(TimeRange { min_ts: result.min_ts, max_ts: result.max_ts, count: result.count })
+    }
+
+    ///
+    /// Starts a transaction which buffers emits for this producer until it's committed.
+    /// Dropping the transaction without committing it discards the buffered emits.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    fn transaction<'a>(&'a self, uuid: &'a str, conductor_domain: Url) -> Transaction<'a, Self>
+    {
+        Transaction::new(self, uuid, conductor_domain)
+    }
+
+    ///
+    /// Removes every row that has been stored for a producer. The registration of the
+    /// producer is kept so it can continue to emit data. This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the truncate payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    fn truncate(uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/truncate") {
             Ok(u) => u,
             Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
         };
-        let emit: Emit<Self> = Emit {
-            uuid,
-            timestamp: None,
-            data: self.clone(),
-        };
-        let payload = match rmp_serde::to_vec_named(&emit) {
+        let payload = match rmp_serde::to_vec_named(&Truncate::new(uuid)) {
             Ok(p) => p,
-            Err(err) => {
-                return Err(Error::MsgPackSerialisationFailure(err));
-            }
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
         };
-        Ok((payload, url))
+        let client = http::blocking_client();
+        let request = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: TruncateResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(())
     }
 
     ///
-    /// Prepares the payload used for registration. Registration is not done by this function.
+    /// Pauses or resumes ingestion for a producer. While it's paused its emits are rejected with
+    /// `Paused` and the server answers them with 503 Service Unavailable. Nothing is deleted and it
+    /// stays registered. This function blocks.
     ///
     /// # Arguments
     ///
-    /// * `name`: The name of the producer.
-    /// This doesn't need to be unique in a Conductor network although it may be helpful to you if it is.
-    /// * `uuid`: The unique ID string to identify this producer. If it's none one will be generated by the
-    /// Conductor server and returned to us. Most of the time you'll want to leave this as None.
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `paused`: True to pause ingestion and false to resume it.
     /// * `conductor_domain`: The url of the conductor instance.
     ///
-    ///# Errors
-    ///
+    /// # Errors
     /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
     ///
-    /// # Example
+    fn set_paused(uuid: &str, paused: bool, conductor_domain: Url) -> Result<(), Error>
+    {
+        let url = pause_url(uuid, paused, &conductor_domain)?;
+        let client = http::blocking_client();
+        let request = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: PauseResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(())
+    }
+
     ///
-    /// ```
-    /// use std::collections::HashMap;
-    /// use conductor_common::producer::Base;
-    /// use conductor_common::schema;
-    /// #[derive(Clone, Serialize)]
-    /// struct Measurement {
-    ///     data_point:u8
-    /// }
-    /// impl schema::ConductorSchema for Measurement {
-    ///     fn generate_schema() -> HashMap<String, schema::DataTypes> {
-    ///         unimplemented!("Not needed for example/test");
-    ///     }
-    /// }
-    /// impl Base for Measurement {}
-    /// let m = Measurement {
-    ///     data_point: 10
-    /// };
-    /// let expected:Vec<u8> = vec![3,4,5];
-    /// assert_eq!(m, expected);
-    /// ```
+    /// Changes the name and/or column metadata of a producer in a single request. The
+    /// schema of the producer isn't changed.
     ///
-    fn prepare_registration_data(name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<(Vec<u8>, Url), Error> {
-        let url = match conductor_domain.join("/v1/producer/register") {
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `update`: The new name and the column metadata to change.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the update cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http patch fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when the update was rejected. See `ProducerUpdate::validate`.
+    ///
+    fn update_metadata(uuid: &str, update: &ProducerUpdate, conductor_domain: Url) -> Result<ProducerMetadata, Error>
+    {
+        let url = match conductor_domain.join(&format!("/v1/producer/{}", uuid)) {
             Ok(u) => u,
             Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
         };
-
-        let reg = Registration {
-            name: name.to_string(),
-            schema: Self::generate_schema(),
-            use_custom_id: uuid,
+        let payload = match rmp_serde::to_vec_named(update) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
         };
-        let payload = match rmp_serde::to_vec_named(&reg) {
-            Ok(m) => m,
-            Err(err) => {
-                return Err(Error::MsgPackSerialisationFailure(err));
-            }
+        let client = http::blocking_client();
+        let request = client.patch(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
         };
-        Ok((payload, url))
+        let result: ProducerUpdateResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.metadata.unwrap_or_default())
     }
-}
 
-///
-/// Provides functions to add Conductor interactions to a type. Turns the implementing type into
-/// a Conductor Producer. This version of the trait provides a Asynchronous version of the functions.
-/// Refer to `conductor::producer::Producer` for the blocking version.
-///
-/// This should not be implemented directly in most cases.
-/// Instead use the `#[derive(conductor::Producer)]` macro to generate everything for you.
-///
-#[cfg(feature = "async")]
-#[async_trait]
-#[allow(clippy::module_name_repetitions)]
-pub trait AsyncProducer: Base {
-    /// Async send a new data packet to the conductor server.
-    /// Messagepack is used as the format over the wire.
+    ///
+    /// Swaps the schema of a producer for a new one if its current schema has the expected hash.
+    /// The table of the producer is migrated by adding and dropping columns. Returns the hash of the
+    /// new schema which is the expected hash of the next swap. See `SchemaSwap`.
     ///
     /// # Arguments
     ///
-    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `swap`: The hash the current schema is expected to have and the schema to replace it with.
     /// * `conductor_domain`: The url of the conductor instance.
     ///
     /// # Errors
     /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
-    /// due to a difficulty serialising Self using serde.
+    /// * `MsgPackSerialisationFailure`: Produced when the swap cannot be serialised to the message pack format.
     /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
-    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
     /// rmp_serde Error struct.
-    /// * `ConductorError`: Produced when there was an error on the server.
+    /// * `ConductorError`: Produced when the swap was rejected. `SchemaConflict` if the current schema
+    /// doesn't have the expected hash and `InvalidSchema` if the type of a column would change.
     ///
-    async fn emit(&self, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    fn swap_schema(uuid: &str, swap: &SchemaSwap, conductor_domain: Url) -> Result<String, Error>
     {
-        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
-
-        //start async specific
-        let client = reqwest::Client::new();
-        let request_resp = client.post(url)
+        let url = match conductor_domain.join(&format!("/v1/producer/{}/schema", uuid)) {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(swap) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::blocking_client();
+        let request = client.post(url)
             .body(payload)
             .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
-            .send().await;
-
-        let response = match request_resp {
+            .send();
+        let response = match request {
             Ok(r) => r,
             Err(err) => return Err(Error::NetworkError(err))
         };
-        let result: EmitResult = match rmp_serde::from_read_ref(response.bytes().await.unwrap().as_ref()) {
+        let result: SchemaSwapResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.schema_hash.unwrap_or_default())
+    }
+
+    ///
+    /// Retrieves the schema a producer was registered with. Unlike `generate_schema`
+    /// this includes whether each column is required. This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server. `Unregistered` if the uuid isn't registered.
+    ///
+    fn registered_schema(uuid: &str, conductor_domain: Url) -> Result<HashMap<String, schema::SchemaEntry>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/schema") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid)];
+        let client = http::blocking_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
             Ok(r) => r,
-            Err(err) => return Err(Error::MsgPackDeserializationFailure(err))
+            Err(err) => return Err(Error::NetworkError(err))
         };
-        //end async specific code
-        if result.error == error::ConductorError::NoError {
-            return Ok(());
+        let result: SchemaResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.schema)
+    }
+
+    ///
+    /// Send this data packet to the conductor server. If it can't be delivered because of a
+    /// failure that may go away (such as the server being unreachable) it's stored in the sink instead
+    /// so that it can be replayed later with `replay_dead_letters`.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    /// * `sink`: Where to store the data packet if it can't be delivered.
+    ///
+    /// # Errors
+    /// * Any error produced by `emit` which won't go away by trying again.
+    /// * `SchemaMismatch`: Produced when the data packet doesn't match the schema and couldn't be stored.
+    /// * `DeadLetterFailure`: Produced when the sink couldn't store the data packet.
+    /// * `InvalidTimestamp`: Produced when the system clock is set before the unix epoch.
+    ///
+    fn emit_or_dead_letter(&self, uuid: &str, conductor_domain: Url, sink: &mut dyn DeadLetterSink) -> Result<Delivery, Error>
+    {
+        let timestamp = timestamp_from_system_time(SystemTime::now())?;
+        match self.emit(uuid, conductor_domain) {
+            Ok(()) => Ok(Delivery::Sent),
+            Err(err) if dead_letter::is_transient(&err) => {
+                let data = to_checked_map(self, &Self::generate_schema())?;
+                sink.store(DeadLetter { uuid: uuid.to_string(), timestamp: Some(timestamp), data })?;
+                Ok(Delivery::DeadLettered)
+            }
+            Err(err) => Err(err),
         }
-        Err(Error::ConductorError(result.error))
     }
 
+    ///
+    /// Send every dead letter in the sink to the conductor server in the order they were stored.
+    /// Each one is sent as its own emit with the timestamp of the emit that failed. Replaying stops at
+    /// the first dead letter that can't be delivered. It and every dead letter after it are kept in
+    /// the sink while the ones that were delivered are removed.
+    ///
+    /// Returns the number of dead letters that were delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink`: The sink containing the dead letters.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * Any error produced by `emit` for the first dead letter that couldn't be delivered.
+    /// * `DeadLetterFailure`: Produced when the sink couldn't be read or written. If it can't be
+    /// written the dead letters that were delivered are still in the sink and will be sent again.
+    ///
+    fn replay_dead_letters(&self, sink: &mut dyn DeadLetterSink, conductor_domain: Url) -> Result<usize, Error>
+    {
+        let mut dead_letters = sink.load()?;
+        let client = http::blocking_client();
+        let mut replayed = 0;
+        let mut failure = None;
+        for dead_letter in &dead_letters {
+            let emit = Emit::new(&dead_letter.uuid, dead_letter.timestamp, dead_letter.data.clone());
+            let (payload, url) = encode_emit(&emit, conductor_domain.clone())?;
+            let request_resp = client.post(url)
+                .body(payload)
+                .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                .send();
+            let result = match request_resp {
+                Ok(response) => format::decode_response::<EmitResult>(response.bytes().unwrap().as_ref()),
+                Err(err) => Err(Error::NetworkError(err))
+            };
+            match result {
+                Ok(result) if result.error == error::ConductorError::NoError => replayed += 1,
+                Ok(result) => {
+                    failure = Some(Error::ConductorError(result.error));
+                    break;
+                }
+                Err(err) => {
+                    failure = Some(err);
+                    break;
+                }
+            }
+        }
+        sink.replace(dead_letters.split_off(replayed))?;
+        failure.map_or(Ok(replayed), Err)
+    }
 
-    /// Generates the schema for this struct and register it with conductor asynchronously.
+    ///
+    /// Aggregates a numeric column of this producer into time buckets on the conductor server.
     ///
     /// # Arguments
+    /// * `uuid`: The uuid of the producer
+    /// * `column`: The numeric column to aggregate
+    /// * `aggregate`: The aggregate function applied to each bucket
+    /// * `interval`: The size of each bucket such as `15m`. See `validate_rollup_interval`.
+    /// * `conductor_domain`: The domain of the conductor instance
     ///
-    /// * `name`: A human friendly name for this producer. This isn't important to conductor and doesn't have to be unique.
-    /// It's stored in the DB and can be useful to identify the producer. And empty string is valid but not recommended.
-    /// * `uuid`: An optional unique ID which will be used to identify this producer. If this is set to None one is generated automatically by
-    /// Conductor. It's recommended to leave this as null and let the server generate the ID.
+    /// # Errors
+    /// * `InvalidConductorDomain`: The domain given for the conductor instance is invalid
+    /// * `NetworkError`: There was an issue communicating with the conductor instance
+    /// * `MsgPackDeserializationFailure`: Couldn't decode the response from the server
+    /// * `ConductorError`: The server couldn't roll up the column. `InvalidData` if the column isn't numeric.
+    fn rollup(uuid: &str, column: &str, aggregate: Aggregate, interval: &str, conductor_domain: Url) -> Result<Vec<RollupBucket>, Error>
+    {
+        let url = match conductor_domain.join("/v1/producer/rollup") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let params = [("uuid", uuid), ("column", column), ("fn", aggregate.to_sql_str()), ("interval", interval)];
+        let client = http::blocking_client();
+        let request = client.get(url)
+            .query(&params)
+            .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        let result: RollupResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
+        }
+        Ok(result.buckets)
+    }
+
+    ///
+    /// Deletes several producers along with their data tables. This can't be undone.
+    /// Returns the outcome for each selected producer. A producer which couldn't be deleted has an error
+    /// in its outcome but doesn't stop the others from being deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `request`: The uuids and/or name prefix of the producers to delete.
     /// * `conductor_domain`: The url of the conductor instance.
     ///
     /// # Errors
     /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
-    /// due to a difficulty serialising Self using serde.
+    /// * `MsgPackSerialisationFailure`: Produced when the request cannot be serialised to the message pack format.
     /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
-    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
     /// rmp_serde Error struct.
-    /// * `ConductorError`: Produced when there was an error on the server.
+    /// * `ConductorError`: Produced when the batch was rejected. `InvalidUuid` if any of the uuids are malformed in
+    /// which case nothing is deleted.
     ///
-    async fn register(name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<String, Error>
+    fn delete_batch(request: &DeleteBatch, conductor_domain: Url) -> Result<Vec<DeleteOutcome>, Error>
     {
-        //TODO handle errors correctly
-        let (payload, url) = Self::prepare_registration_data(name, uuid, conductor_domain)?;
-
-        let client = reqwest::Client::new();
+        let url = match conductor_domain.join("/v1/producer/delete_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(request) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::blocking_client();
         let request = client.post(url)
             .body(payload)
             .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
-            .send().await;
+            .send();
         let response = match request {
             Ok(r) => r,
             Err(err) => return Err(Error::NetworkError(err))
         };
-        let result: RegistrationResult = match rmp_serde::from_read_ref(response.bytes().await.unwrap().as_ref()) {
-            Ok(r) => r,
-            Err(err) => return Err(Error::MsgPackDeserializationFailure(err))
-        };
+        let result: DeleteBatchResult = format::decode_response(response.bytes().unwrap().as_ref())?;
         if result.error != error::ConductorError::NoError {
             return Err(Error::ConductorError(result.error));
         }
-        Ok(result.uuid.unwrap())
+        Ok(result.results)
     }
 
     ///
-    /// Asynchronously checks to see if the UUID has been registered with Conductor.
-    /// This does not verify that the schema registered with the server is correct.
+    /// Exports the registrations of every producer so they can be recreated on another
+    /// Conductor instance with `register_batch`. Each registration uses the uuid of its producer as its
+    /// custom id. The registry is fetched in pages of `EXPORT_PAGE_SIZE` so large exports don't have
+    /// to fit in a single response. Only the registry is exported, not the data of the producers. This function blocks.
     ///
     /// # Arguments
     ///
-    /// * `uuid`: The unique id of this producer which was registered with conductor.
     /// * `conductor_domain`: The url of the conductor instance.
     ///
     /// # Errors
     /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
     /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
+    /// rmp_serde Error struct.
+    /// * `ConductorError`: Produced when there was an error on the server.
     ///
-    async fn is_registered(uuid: &str, conductor_domain: Url) -> Result<bool, Error>
+    fn export_registrations(conductor_domain: Url) -> Result<Vec<Registration>, Error>
     {
-        let url = match conductor_domain.join("/v1/producer/check") {
+        let url = match conductor_domain.join("/v1/producer/export") {
             Ok(u) => u,
             Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
         };
-        let params = [("uuid", uuid)];
-        let client = reqwest::Client::new();
-        match client.get(url).query(&params).send().await {
-            Ok(response) => {
-                Ok(response.status().is_success())
+        let client = http::blocking_client();
+        let mut registrations = Vec::new();
+        let mut offset = 0;
+        loop {
+            let params = [("offset", offset.to_string()), ("limit", EXPORT_PAGE_SIZE.to_string())];
+            let request = client.get(url.clone())
+                .query(&params)
+                .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                .send();
+            let response = match request {
+                Ok(r) => r,
+                Err(err) => return Err(Error::NetworkError(err))
+            };
+            let result: ExportResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+            if result.error != error::ConductorError::NoError {
+                return Err(Error::ConductorError(result.error));
             }
-            Err(err) => Err(Error::NetworkError(err))
+            registrations.extend(result.registrations);
+            offset = match result.next {
+                Some(next) => next,
+                None => return Ok(registrations),
+            };
         }
     }
-}
 
-///
-/// Provides functions to add Conductor interactions to a type. Turns the implementing type into
-/// a Conductor Producer. This version of the trait provides a blocking version of the functions.
-/// Refer to `conductor::producer::AsyncProducer` for the Asynchronous version.
-///
-/// This should not be implemented directly in most cases.
-/// Instead use the `#[derive(conductor::Producer)]` macro to generate everything for you.
-///
-pub trait Producer: Base {
-    /// Send a new data packet to the conductor server.
-    /// Messagepack is used as the format over the wire.
-    /// This function blocks.
+    ///
+    /// Registers several producers in one request such as those returned by
+    /// `export_registrations`. Returns the result of each registration in order. A registration which
+    /// fails doesn't stop the others. This function blocks.
     ///
     /// # Arguments
     ///
-    /// * `uuid`: The unique id of this producer which was registered with conductor.
+    /// * `registrations`: The producers to register.
     /// * `conductor_domain`: The url of the conductor instance.
     ///
     /// # Errors
     /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
-    /// due to a difficulty serialising Self using serde.
+    /// * `MsgPackSerialisationFailure`: Produced when the registrations cannot be serialised to the message pack format.
     /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
-    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack. Holds the
     /// rmp_serde Error struct.
-    /// * `ConductorError`: Produced when there was an error on the server.
+    /// * `ConductorError`: Produced when the batch was rejected as a whole.
     ///
-    fn emit(&self, uuid: &str, conductor_domain: Url) -> Result<(), Error>
+    fn register_batch(registrations: &[Registration], conductor_domain: Url) -> Result<Vec<RegistrationResult>, Error>
     {
-        let (payload, url) = self.generate_emit_data(uuid, conductor_domain)?;
-
-        //start blocking specific
-        let client = reqwest::blocking::Client::new();
-        let request_resp = client.post(url)
+        let url = match conductor_domain.join("/v1/producer/register_batch") {
+            Ok(u) => u,
+            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+        };
+        let payload = match rmp_serde::to_vec_named(registrations) {
+            Ok(p) => p,
+            Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+        };
+        let client = http::blocking_client();
+        let request = client.post(url)
             .body(payload)
             .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
             .send();
-        let response = match request_resp {
+        let response = match request {
             Ok(r) => r,
             Err(err) => return Err(Error::NetworkError(err))
         };
-        let result: EmitResult = match rmp_serde::from_read_ref(response.bytes().unwrap().as_ref()) {
-            Ok(r) => r,
-            Err(err) => return Err(Error::MsgPackDeserializationFailure(err))
-        };
-        //end blocking specific code
-        match &result.error {
-            error::ConductorError::NoError => Ok(()),
-            _ => Err(Error::ConductorError(result.error))
+        let result: RegisterBatchResult = format::decode_response(response.bytes().unwrap().as_ref())?;
+        if result.error != error::ConductorError::NoError {
+            return Err(Error::ConductorError(result.error));
         }
+        Ok(result.results)
     }
+}
 
-    /// Generates the schema for this struct and register it with conductor.
+
+/// The emits a transaction has buffered for producers other than its own, grouped by producer.
+type OtherBatches = Vec<(String, Vec<HashMap<String, serde_json::Value>>)>;
+
+fn add_to_batches(batches: &mut OtherBatches, uuid: &str, data: HashMap<String, serde_json::Value>) {
+    match batches.iter_mut().find(|(batch_uuid, _)| batch_uuid == uuid) {
+        Some((_, batch)) => batch.push(data),
+        None => batches.push((uuid.to_string(), vec![data])),
+    }
+}
+
+fn generate_transaction_data(uuid: &str, data: &[HashMap<String, serde_json::Value>], others: &[(String, Vec<HashMap<String, serde_json::Value>>)], conductor_domain: &Url) -> Result<(Vec<u8>, Url), Error> {
+    let url = match conductor_domain.join("/v1/producer/emit_transaction") {
+        Ok(u) => u,
+        Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+    };
+    let mut batches = Vec::with_capacity(others.len() + 1);
+    if !data.is_empty() {
+        batches.push(EmitBatch::new(uuid, data.iter().collect()));
+    }
+    batches.extend(others.iter().map(|(uuid, data)| EmitBatch::new(uuid, data.iter().collect())));
+    let payload = match rmp_serde::to_vec_named(&batches) {
+        Ok(p) => p,
+        Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+    };
+    Ok((payload, url))
+}
+
+fn decode_transaction(body: &[u8]) -> Result<(), Error> {
+    let result: EmitResult = format::decode_response(body)?;
+    match result.error {
+        error::ConductorError::NoError => Ok(()),
+        error => Err(Error::ConductorError(error)),
+    }
+}
+
+///
+/// Buffers emits so that they can be sent to Conductor together. The emits are for the transaction's
+/// own producer unless they're added with `emit_for`. The buffered emits are written atomically when
+/// the transaction is committed, even when they're for several producers. If the transaction is
+/// dropped without being committed nothing is sent to the server.
+/// Refer to `conductor::producer::AsyncTransaction` for the async version.
+///
+pub struct Transaction<'a, T> {
+    producer: &'a T,
+    uuid: &'a str,
+    conductor_domain: Url,
+    data: Vec<HashMap<String, serde_json::Value>>,
+    others: OtherBatches,
+}
+
+impl<'a, T: Producer> Transaction<'a, T> {
+    #[must_use]
+    pub const fn new(producer: &'a T, uuid: &'a str, conductor_domain: Url) -> Self {
+        Self {
+            producer,
+            uuid,
+            conductor_domain,
+            data: Vec::new(),
+            others: Vec::new(),
+        }
+    }
+
+    /// Adds a data packet to the transaction. Nothing is sent until the transaction is committed.
+    pub fn emit(&mut self, data: HashMap<String, serde_json::Value>) {
+        self.data.push(data);
+    }
+
+    /// Adds a data packet for the producer registered as `uuid` so that it's stored or rejected
+    /// together with the rest of the transaction. Nothing is sent until the transaction is committed.
+    pub fn emit_for(&mut self, uuid: &str, data: HashMap<String, serde_json::Value>) {
+        if uuid == self.uuid {
+            self.data.push(data);
+        } else {
+            add_to_batches(&mut self.others, uuid, data);
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len() + self.others.iter().map(|(_, data)| data.len()).sum::<usize>()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// Sends every buffered data packet to the conductor server in a single request. Either all of
+    /// them are stored or none are. A transaction for only its own producer is sent as a batch.
+    /// Committing an empty transaction doesn't send anything.
     /// This function blocks.
     ///
-    /// # Arguments
+    /// # Errors
+    /// The same errors as `Producer::emit_iter`.
     ///
-    /// * `name`: A human friendly name for this producer. This isn't important to conductor and doesn't have to be unique.
-    /// It's stored in the DB and can be useful to identify the producer. And empty string is valid but not recommended.
-    /// * `uuid`: An optional unique ID which will be used to identify this producer. If this is set to None one is generated automatically by
-    /// Conductor. It's recommended to leave this as null and let the server generate the ID.
-    /// * `conductor_domain`: The url of the conductor instance.
+    pub fn commit(self) -> Result<(), Error> {
+        if self.others.is_empty() {
+            return self.producer.emit_iter(self.uuid, self.data, self.conductor_domain);
+        }
+        let (payload, url) = generate_transaction_data(self.uuid, &self.data, &self.others, &self.conductor_domain)?;
+        let response = http::blocking_client().post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send()
+            .map_err(Error::NetworkError)?;
+        let body = response.bytes().map_err(Error::NetworkError)?;
+        decode_transaction(&body)
+    }
+}
+
+///
+/// The async version of `conductor::producer::Transaction`. Buffers emits so that they can be sent to
+/// Conductor together and written atomically. If the transaction is dropped without being committed
+/// nothing is sent to the server.
+///
+#[cfg(feature = "async")]
+pub struct AsyncTransaction<'a, T> {
+    producer: &'a T,
+    uuid: &'a str,
+    conductor_domain: Url,
+    data: Vec<HashMap<String, serde_json::Value>>,
+    others: OtherBatches,
+}
+
+#[cfg(feature = "async")]
+impl<'a, T: AsyncProducer + Sync> AsyncTransaction<'a, T> {
+    #[must_use]
+    pub const fn new(producer: &'a T, uuid: &'a str, conductor_domain: Url) -> Self {
+        Self {
+            producer,
+            uuid,
+            conductor_domain,
+            data: Vec::new(),
+            others: Vec::new(),
+        }
+    }
+
+    /// Adds a data packet to the transaction. Nothing is sent until the transaction is committed.
+    pub fn emit(&mut self, data: HashMap<String, serde_json::Value>) {
+        self.data.push(data);
+    }
+
+    /// Adds a data packet for the producer registered as `uuid`. See `Transaction::emit_for`.
+    pub fn emit_for(&mut self, uuid: &str, data: HashMap<String, serde_json::Value>) {
+        if uuid == self.uuid {
+            self.data.push(data);
+        } else {
+            add_to_batches(&mut self.others, uuid, data);
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len() + self.others.iter().map(|(_, data)| data.len()).sum::<usize>()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// Async send every buffered data packet to the conductor server in a single request. Either all
+    /// of them are stored or none are. Committing an empty transaction doesn't send anything.
     ///
     /// # Errors
-    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `MsgPackSerialisationFailure`: Produced when the emit payload cannot be serialised to the message pack format. This is most likely
-    /// due to a difficulty serialising Self using serde.
-    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
-    /// * `MsgPackDeserializationFailure`: Produced when the emit response couldn't be deserialized from message pack. Holds the
-    /// rmp_serde Error struct.
-    /// * `ConductorError`: Produced when there was an error on the server.
+    /// The same errors as `AsyncProducer::emit_iter`.
     ///
-    fn register(name: &str, uuid: Option<String>, conductor_domain: Url) -> Result<String, Error>
-    {
-        //TODO handle errors correctly
-        let (payload, url) = Self::prepare_registration_data(name, uuid, conductor_domain)?;
-
-        let client = reqwest::blocking::Client::new();
-        let request = client.post(url)
+    pub async fn commit(self) -> Result<(), Error> {
+        if self.others.is_empty() {
+            return self.producer.emit_iter(self.uuid, self.data, self.conductor_domain).await;
+        }
+        let (payload, url) = generate_transaction_data(self.uuid, &self.data, &self.others, &self.conductor_domain)?;
+        let response = http::async_client().post(url)
             .body(payload)
             .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
-            .send();
-        let response = match request {
-            Ok(r) => r,
-            Err(err) => return Err(Error::NetworkError(err))
-        };
-        let result: RegistrationResult = match rmp_serde::from_read_ref(response.bytes().unwrap().as_ref()) {
-            Ok(r) => r,
-            Err(err) => return Err(Error::MsgPackDeserializationFailure(err))
-        };
-        if result.error != error::ConductorError::NoError {
-            return Err(Error::ConductorError(result.error));
+            .send().await
+            .map_err(Error::NetworkError)?;
+        let body = response.bytes().await.map_err(Error::NetworkError)?;
+        decode_transaction(&body)
+    }
+}
+
+/// A data packet buffered by a session along with the producer it's for.
+#[derive(Debug, Clone)]
+struct SessionEmit {
+    uuid: String,
+    timestamp: Option<u64>,
+    data: HashMap<String, serde_json::Value>,
+}
+
+fn generate_session_data(emits: &[SessionEmit], conductor_domain: &Url) -> Result<(Vec<u8>, Url), Error> {
+    let url = match conductor_domain.join("/v1/producer/emit_session") {
+        Ok(u) => u,
+        Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+    };
+    let emits: Vec<Emit<'_, &HashMap<String, serde_json::Value>>> = emits.iter()
+        .map(|emit| Emit::new(&emit.uuid, emit.timestamp, &emit.data))
+        .collect();
+    let payload = match rmp_serde::to_vec_named(&emits) {
+        Ok(p) => p,
+        Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+    };
+    Ok((payload, url))
+}
+
+fn decode_session(body: &[u8]) -> Result<Vec<EmitResult>, Error> {
+    let result: EmitSessionResult = format::decode_response(body)?;
+    if result.error != error::ConductorError::NoError {
+        return Err(Error::ConductorError(result.error));
+    }
+    Ok(result.results)
+}
+
+///
+/// Buffers emits for any number of producers so that they're sent to Conductor in one request. The
+/// server stores them one after another. Unlike a `Transaction` each emit is stored or rejected on
+/// its own. If the session is dropped without being committed nothing is sent to the server.
+/// Refer to `conductor::producer::AsyncSession` for the async version.
+///
+#[derive(Debug, Clone)]
+pub struct Session {
+    conductor_domain: Url,
+    emits: Vec<SessionEmit>,
+}
+
+impl Session {
+    #[must_use]
+    pub const fn new(conductor_domain: Url) -> Self {
+        Self {
+            conductor_domain,
+            emits: Vec::new(),
         }
-        Ok(result.uuid.unwrap())
+    }
+
+    /// Adds a data packet for the producer registered as `uuid`. Nothing is sent until the session is committed.
+    pub fn emit(&mut self, uuid: &str, data: HashMap<String, serde_json::Value>) {
+        self.emit_at(uuid, None, data);
+    }
+
+    /// The same as `emit` except the data is stored with `timestamp` in the timestamp precision of the producer.
+    pub fn emit_at(&mut self, uuid: &str, timestamp: Option<u64>, data: HashMap<String, serde_json::Value>) {
+        self.emits.push(SessionEmit { uuid: uuid.to_string(), timestamp, data });
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.emits.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.emits.is_empty()
     }
 
     ///
-    /// Checks to see if the UUID has been registered with Conductor.
-    /// This does not verify that the schema registered with the server is correct.
-    /// This function blocks
+    /// Sends every buffered emit to the conductor server in one request. Returns the result of each
+    /// emit in the order they were added. Committing an empty session doesn't send anything.
+    /// This function blocks.
     ///
-    /// # Arguments
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the emits cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `ConductorError`: Produced when the session was rejected as a whole. `BatchTooLarge` if it has
+    /// more emits than the server's `max_batch_rows`.
     ///
-    /// * `uuid`: The unique id of this producer which was registered with conductor.
-    /// * `conductor_domain`: The url of the conductor instance.
+    pub fn commit(self) -> Result<Vec<EmitResult>, Error> {
+        if self.emits.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (payload, url) = generate_session_data(&self.emits, &self.conductor_domain)?;
+        let response = http::blocking_client().post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send()
+            .map_err(Error::NetworkError)?;
+        let body = response.bytes().map_err(Error::NetworkError)?;
+        decode_session(&body)
+    }
+}
+
+///
+/// The async version of `conductor::producer::Session`. Buffers emits for any number of producers so
+/// that they're sent to Conductor in one request and stored on a single database connection.
+///
+#[cfg(feature = "async")]
+#[derive(Debug, Clone)]
+pub struct AsyncSession {
+    conductor_domain: Url,
+    emits: Vec<SessionEmit>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncSession {
+    #[must_use]
+    pub const fn new(conductor_domain: Url) -> Self {
+        Self {
+            conductor_domain,
+            emits: Vec::new(),
+        }
+    }
+
+    /// Adds a data packet for the producer registered as `uuid`. Nothing is sent until the session is committed.
+    pub fn emit(&mut self, uuid: &str, data: HashMap<String, serde_json::Value>) {
+        self.emit_at(uuid, None, data);
+    }
+
+    /// The same as `emit` except the data is stored with `timestamp` in the timestamp precision of the producer.
+    pub fn emit_at(&mut self, uuid: &str, timestamp: Option<u64>, data: HashMap<String, serde_json::Value>) {
+        self.emits.push(SessionEmit { uuid: uuid.to_string(), timestamp, data });
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.emits.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.emits.is_empty()
+    }
+
+    ///
+    /// Async send every buffered emit to the conductor server in one request. Returns the result of
+    /// each emit in the order they were added. Committing an empty session doesn't send anything.
     ///
     /// # Errors
-    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
-    /// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+    /// The same errors as `Session::commit`.
     ///
-    fn is_registered(uuid: &str, conductor_domain: Url) -> Result<bool, Error>
-    {
-        let url = match conductor_domain.join("/v1/producer/check") {
-            Ok(u) => u,
-            Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
-        };
-        let params = [("uuid", uuid)];
-        let client = reqwest::blocking::Client::new();
-        match client.get(url).query(&params).send() {
-            Ok(response) => {
-                Ok(response.status().is_success())
-            }
-            Err(err) => Err(Error::NetworkError(err))
+    pub async fn commit(self) -> Result<Vec<EmitResult>, Error> {
+        if self.emits.is_empty() {
+            return Ok(Vec::new());
         }
+        let (payload, url) = generate_session_data(&self.emits, &self.conductor_domain)?;
+        let response = http::async_client().post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await
+            .map_err(Error::NetworkError)?;
+        let body = response.bytes().await.map_err(Error::NetworkError)?;
+        decode_session(&body)
+    }
+}
+
+/// Controls how `AsyncProducer::emit_stream` sends the items of a stream.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamOptions {
+    concurrency: usize,
+    stop_on_error: bool,
+}
+
+#[cfg(feature = "async")]
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+#[cfg(feature = "async")]
+impl StreamOptions {
+    /// Sends one item at a time and keeps going after an item fails.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            concurrency: 1,
+            stop_on_error: false,
+        }
+    }
+
+    /// Sets how many items can be in flight at once. Results are still produced in the order of the
+    /// stream. A concurrency of 0 is treated as 1.
+    #[must_use]
+    pub const fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Ends the result stream after the first item which couldn't be emitted.
+    #[must_use]
+    pub const fn with_stop_on_error(mut self) -> Self {
+        self.stop_on_error = true;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    #[must_use]
+    pub const fn should_stop_on_error(&self) -> bool {
+        self.stop_on_error
+    }
+}
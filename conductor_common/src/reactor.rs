@@ -1,3 +1,644 @@
-// pub struct Test {
-//     value:usize
-// }
\ No newline at end of file
+//! The client half of the reactor automation loop. Reactors poll the Conductor instance for actions
+//! which are waiting for them, perform them and report the result back.
+//!
+//! Polling uses long polls. Each poll asks the server to hold the request open for up to
+//! `PollOptions::get_wait` until an action is queued for the reactor. Polls which end without any
+//! actions are repeated straight away. Polls which fail with an error which could go away on its own
+//! (see `should_retry`) are repeated after a backoff which starts at `PollOptions::get_initial_backoff`
+//! and doubles after each consecutive failure up to `PollOptions::get_max_backoff`. Any other error is
+//! returned to the caller.
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::error;
+use crate::dead_letter;
+use crate::format;
+use crate::http;
+use crate::producer::{self, Error};
+use crate::schema;
+
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+
+/// The time a reactor gives the server to answer a poll beyond the long poll wait.
+const POLL_TIMEOUT_MARGIN: Duration = Duration::from_secs(10);
+
+/// An action which a reactor can perform along with the parameters it takes.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ActionRegistration {
+    name: String,
+    input_schema: schema::Schema,
+    #[serde(default)]
+    output_schema: schema::Schema,
+    #[serde(default)]
+    version: Option<u32>,
+}
+
+impl ActionRegistration {
+    /// Creates an action which takes parameters matching `input_schema`. Every parameter must be
+    /// given when the action is queued.
+    #[must_use]
+    pub fn new(name: String, input_schema: schema::Schema) -> Self {
+        Self {
+            name,
+            input_schema,
+            output_schema: schema::Schema::new(),
+            version: None,
+        }
+    }
+
+    /// Describes the values the reactor reports once it has performed the action.
+    #[must_use]
+    pub fn with_output_schema(mut self, output_schema: schema::Schema) -> Self {
+        self.output_schema = output_schema;
+        self
+    }
+
+    /// Sets the version of the action. Callers which queue the action can give the version they
+    /// expect so they're rejected rather than silently misunderstood once the action changes.
+    #[must_use]
+    pub const fn with_version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn get_version(&self) -> Option<u32> {
+        self.version
+    }
+
+    #[must_use]
+    pub const fn get_input_schema(&self) -> &schema::Schema {
+        &self.input_schema
+    }
+
+    #[must_use]
+    pub const fn get_output_schema(&self) -> &schema::Schema {
+        &self.output_schema
+    }
+
+    ///
+    /// Checks the parameters of a request to perform this action against its input schema.
+    ///
+    /// # Errors
+    /// * `InvalidSchema`: A parameter isn't part of the input schema or a parameter of the schema is missing.
+    /// * `InvalidData`: A parameter can't be stored as the type the input schema gives for it.
+    ///
+    pub fn validate_input(&self, parameters: &HashMap<String, serde_json::Value>) -> Result<(), error::ConductorError> {
+        if let Some(parameter) = parameters.keys().find(|parameter| !self.input_schema.contains_key(*parameter)) {
+            return Err(error::ConductorError::InvalidSchema(format!("The action {} doesn't take the parameter {}", self.name, parameter)));
+        }
+        for (parameter, data_type) in &self.input_schema {
+            match parameters.get(parameter) {
+                Some(value) if data_type.accepts(value) => {}
+                Some(value) => {
+                    return Err(error::ConductorError::InvalidData(format!("The parameter {} of the action {} can't be a {:?}. Value: {}", parameter, self.name, data_type, value)));
+                }
+                None => {
+                    return Err(error::ConductorError::InvalidSchema(format!("The parameter {} of the action {} is missing", parameter, self.name)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Checks the version a caller expects against the version the action was registered with. Callers
+    /// which don't expect a version accept any.
+    ///
+    /// # Errors
+    /// * `VersionMismatch`: The caller expects a version and the action was registered with a different
+    /// version or without one.
+    ///
+    pub fn check_version(&self, expected: Option<u32>) -> Result<(), error::ConductorError> {
+        match expected {
+            Some(expected) if self.version != Some(expected) => {
+                let registered = self.version.map_or_else(|| String::from("no version"), |version| format!("version {}", version));
+                Err(error::ConductorError::VersionMismatch(format!("The action {} was expected to be version {} but it was registered with {}", self.name, expected, registered)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Contains the information required to register a reactor with a Conductor server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ReactorRegistration {
+    name: String,
+    use_custom_id: Option<String>,
+    actions: Vec<ActionRegistration>,
+}
+
+impl ReactorRegistration {
+    #[must_use]
+    pub const fn new(name: String, custom_id: Option<String>) -> Self {
+        Self {
+            name,
+            use_custom_id: custom_id,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Adds an action which the reactor can perform.
+    #[must_use]
+    pub fn with_action(mut self, action: ActionRegistration) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn get_custom_id(&self) -> Option<&str> {
+        self.use_custom_id.as_deref()
+    }
+
+    #[must_use]
+    pub fn get_actions(&self) -> &[ActionRegistration] {
+        &self.actions
+    }
+
+    /// Finds the action with the given name.
+    #[must_use]
+    pub fn get_action(&self, name: &str) -> Option<&ActionRegistration> {
+        self.actions.iter().find(|action| action.name == name)
+    }
+
+    ///
+    /// Checks the registration the same way the server does before it's stored.
+    ///
+    /// # Errors
+    /// * `NameInvalid`: The name of the reactor or of an action is empty.
+    /// * `InvalidUuid`: The custom id is empty or has illegal chars.
+    /// * `NoMembers`: The reactor doesn't have any actions.
+    /// * `InvalidSchema`: Two actions have the same name.
+    ///
+    pub fn validate(&self) -> Result<(), error::ConductorError> {
+        if self.name.is_empty() {
+            return Err(error::ConductorError::NameInvalid("Reactor registration failed. Reactor name is empty.".to_string()));
+        }
+        if let Some(custom_id) = self.get_custom_id() {
+            producer::validate_uuid(custom_id)?;
+        }
+        if self.actions.is_empty() {
+            return Err(error::ConductorError::NoMembers("Reactor registration failed. The reactor has no actions.".to_string()));
+        }
+        for (index, action) in self.actions.iter().enumerate() {
+            if action.name.is_empty() {
+                return Err(error::ConductorError::NameInvalid("Reactor registration failed. An action name is empty.".to_string()));
+            }
+            if self.actions[..index].iter().any(|other| other.name == action.name) {
+                return Err(error::ConductorError::InvalidSchema(format!("Reactor registration failed. The action {} is registered more than once.", action.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A request to queue an action for a reactor
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EnqueueAction {
+    /// The uuid of the reactor which should perform the action
+    pub reactor: String,
+    /// The name of the action to perform
+    pub action: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    /// The version of the action the caller expects. The action isn't queued if the reactor registered
+    /// a different version.
+    #[serde(default)]
+    pub expected_version: Option<u32>,
+}
+
+///The response from the Conductor instance after an action was queued. Contains the id of the queued action.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct EnqueueResult {
+    pub error: error::ConductorError,
+    pub id: Option<String>,
+}
+
+/// An action which the Conductor instance wants a reactor to perform.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct Action {
+    /// Identifies this request to perform the action. Used to report the result.
+    pub id: String,
+    /// The name of the action to perform
+    pub action: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    /// The version the action was registered with
+    #[serde(default)]
+    pub version: Option<u32>,
+}
+
+///The response from the Conductor instance to a poll for pending actions
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct PendingActionsResult {
+    pub error: error::ConductorError,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+}
+
+/// The outcome of performing an action.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ActionResult {
+    pub succeeded: bool,
+    /// A human readable description of the outcome. Usually only set when the action failed.
+    pub message: Option<String>,
+}
+
+impl ActionResult {
+    #[must_use]
+    pub const fn success() -> Self {
+        Self { succeeded: true, message: None }
+    }
+
+    #[must_use]
+    pub const fn failure(message: String) -> Self {
+        Self { succeeded: false, message: Some(message) }
+    }
+}
+
+/// Reports the outcome of an action to the Conductor instance
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ActionReport<'a> {
+    pub action_id: &'a str,
+    pub result: ActionResult,
+}
+
+///The response from the Conductor instance after an action result was reported
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ActionReportResult {
+    pub error: error::ConductorError,
+}
+
+/// Controls how a reactor polls for pending actions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollOptions {
+    wait: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_retries: Option<u32>,
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PollOptions {
+    /// Long polls for 30 seconds and backs off from 1 second up to 1 minute. Failed polls are retried
+    /// until one succeeds.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            wait: Duration::from_secs(30),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_retries: None,
+        }
+    }
+
+    /// Sets how long the server holds a poll open while there aren't any actions for the reactor.
+    #[must_use]
+    pub const fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Sets the backoff after the first failed poll and the most it can grow to.
+    #[must_use]
+    pub const fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Gives up polling after `retries` consecutive failed polls and returns the last error.
+    #[must_use]
+    pub const fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = Some(retries);
+        self
+    }
+
+    #[must_use]
+    pub const fn get_wait(&self) -> Duration {
+        self.wait
+    }
+
+    #[must_use]
+    pub const fn get_initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    #[must_use]
+    pub const fn get_max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    #[must_use]
+    pub const fn get_max_retries(&self) -> Option<u32> {
+        self.max_retries
+    }
+
+    /// The time to wait before the next poll after `failures` consecutive failed polls.
+    #[must_use]
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff.checked_mul(factor).unwrap_or(self.max_backoff).min(self.max_backoff)
+    }
+
+    /// Returns true if another poll should be made after `failures` consecutive failed polls.
+    #[must_use]
+    pub fn can_retry(&self, failures: u32) -> bool {
+        match self.max_retries {
+            Some(retries) => failures <= retries,
+            None => true,
+        }
+    }
+}
+
+/// Returns true if a poll which failed with this error is worth repeating. A 404 means the Conductor
+/// instance doesn't support reactors so polling again won't help.
+#[must_use]
+pub fn should_retry(error: &Error) -> bool {
+    !matches!(error, Error::UnexpectedStatus(reqwest::StatusCode::NOT_FOUND)) && dead_letter::is_transient(error)
+}
+
+///
+/// Builds the url of a long poll for the pending actions of a reactor.
+///
+/// # Errors
+/// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+///
+pub fn pending_url(uuid: &str, wait: Duration, conductor_domain: &Url) -> Result<Url, Error> {
+    let mut url = match conductor_domain.join("/v1/reactor/pending") {
+        Ok(u) => u,
+        Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+    };
+    url.query_pairs_mut().append_pair("uuid", uuid).append_pair("wait", &wait.as_secs().to_string());
+    Ok(url)
+}
+
+///
+/// Reads the actions out of the response to a poll.
+///
+/// # Errors
+/// * `MsgPackDeserializationFailure`: Produced when a successful response couldn't be deserialized.
+/// * `ConductorError`: Produced when there was an error on the server.
+/// * `UnexpectedStatus`: Produced when the server responded with an error status and no error.
+///
+pub fn decode_pending(status: reqwest::StatusCode, body: &[u8]) -> Result<Vec<Action>, Error> {
+    let result: PendingActionsResult = match format::decode_response(body) {
+        Ok(r) => r,
+        Err(Error::MsgPackDeserializationFailure(_)) if !status.is_success() => return Err(Error::UnexpectedStatus(status)),
+        Err(err) => return Err(err)
+    };
+    match result.error {
+        error::ConductorError::NoError if status.is_success() => Ok(result.actions),
+        error::ConductorError::NoError => Err(Error::UnexpectedStatus(status)),
+        err => Err(Error::ConductorError(err)),
+    }
+}
+
+///
+/// Prepares a payload for reporting the result of an action. This function doesn't send the payload.
+///
+/// # Errors
+/// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+/// * `MsgPackSerialisationFailure`: Produced when the report cannot be serialised to the message pack format.
+///
+pub fn generate_report_data(action_id: &str, result: ActionResult, conductor_domain: &Url) -> Result<(Vec<u8>, Url), Error> {
+    let url = match conductor_domain.join("/v1/reactor/result") {
+        Ok(u) => u,
+        Err(err) => return Err(Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+    };
+    let report = ActionReport { action_id, result };
+    let payload = match rmp_serde::to_vec_named(&report) {
+        Ok(p) => p,
+        Err(err) => return Err(Error::MsgPackSerialisationFailure(err))
+    };
+    Ok((payload, url))
+}
+
+fn decode_report(body: &[u8]) -> Result<(), Error> {
+    let result: ActionReportResult = format::decode_response(body)?;
+    match result.error {
+        error::ConductorError::NoError => Ok(()),
+        err => Err(Error::ConductorError(err)),
+    }
+}
+
+///
+/// Provides functions to turn a type into a Conductor Reactor. This version of the trait provides
+/// blocking versions of the functions. Refer to `conductor::reactor::AsyncReactor` for the
+/// asynchronous version.
+///
+/// Every function has a default implementation so `impl Reactor for MyReactor {}` is enough. Override
+/// `poll_options` to change how the reactor polls.
+///
+pub trait Reactor {
+    /// The options used by `poll_actions`
+    fn poll_options(&self) -> PollOptions {
+        PollOptions::default()
+    }
+
+    /// Waits for actions to be queued for the reactor and returns them. Messagepack is used as the
+    /// format over the wire. This function blocks until at least one action is returned or polling
+    /// gives up. See the module documentation for how polls are repeated.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the reactor which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http request fails and the poll can't be retried. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `UnexpectedStatus`: Produced when the server responds with an error status. A 404 is never retried.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    fn poll_actions(&self, uuid: &str, conductor_domain: Url) -> Result<Vec<Action>, Error> {
+        let options = self.poll_options();
+        let url = pending_url(uuid, options.get_wait(), &conductor_domain)?;
+        let client = http::blocking_client();
+        let timeout = options.get_wait() + POLL_TIMEOUT_MARGIN;
+        let mut failures = 0;
+        loop {
+            let poll_result = client.get(url.clone())
+                .timeout(timeout)
+                .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                .send()
+                .and_then(|response| {
+                    let status = response.status();
+                    response.bytes().map(|body| (status, body))
+                })
+                .map_err(Error::NetworkError)
+                .and_then(|(status, body)| decode_pending(status, body.as_ref()));
+            match poll_result {
+                Ok(actions) if actions.is_empty() => failures = 0,
+                Ok(actions) => return Ok(actions),
+                Err(err) if should_retry(&err) && options.can_retry(failures + 1) => {
+                    failures += 1;
+                    std::thread::sleep(options.backoff(failures));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reports the result of performing an action to the conductor server.
+    /// This function blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `action_id`: The id of the action which was performed.
+    /// * `result`: The outcome of the action.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the report cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    fn report_result(&self, action_id: &str, result: ActionResult, conductor_domain: Url) -> Result<(), Error> {
+        let (payload, url) = generate_report_data(action_id, result, &conductor_domain)?;
+        let client = http::blocking_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send();
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        match response.bytes() {
+            Ok(body) => decode_report(body.as_ref()),
+            Err(err) => Err(Error::NetworkError(err)),
+        }
+    }
+}
+
+///
+/// The asynchronous version of `conductor::reactor::Reactor`. Polls sleep with tokio so this must be
+/// used from within a tokio runtime.
+///
+#[cfg(feature = "async")]
+#[async_trait]
+pub trait AsyncReactor: Sync {
+    /// The options used by `poll_actions`
+    fn poll_options(&self) -> PollOptions {
+        PollOptions::default()
+    }
+
+    /// Async wait for actions to be queued for the reactor and return them. Messagepack is used as
+    /// the format over the wire. Resolves once at least one action is returned or polling gives up.
+    /// See the module documentation for how polls are repeated.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid`: The unique id of the reactor which was registered with conductor.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `NetworkError`: Produced when the http request fails and the poll can't be retried. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `UnexpectedStatus`: Produced when the server responds with an error status. A 404 is never retried.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn poll_actions(&self, uuid: &str, conductor_domain: Url) -> Result<Vec<Action>, Error> {
+        let options = self.poll_options();
+        let url = pending_url(uuid, options.get_wait(), &conductor_domain)?;
+        let client = http::async_client();
+        let timeout = options.get_wait() + POLL_TIMEOUT_MARGIN;
+        let mut failures = 0;
+        loop {
+            //the error isn't Send so it has to be dropped before sleeping
+            let backoff = {
+                let request_resp = client.get(url.clone())
+                    .timeout(timeout)
+                    .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+                    .send().await;
+                let poll_result = match request_resp {
+                    Ok(response) => {
+                        let status = response.status();
+                        match response.bytes().await {
+                            Ok(body) => decode_pending(status, body.as_ref()),
+                            Err(err) => Err(Error::NetworkError(err)),
+                        }
+                    }
+                    Err(err) => Err(Error::NetworkError(err)),
+                };
+                match poll_result {
+                    Ok(actions) if actions.is_empty() => {
+                        failures = 0;
+                        continue;
+                    }
+                    Ok(actions) => return Ok(actions),
+                    Err(err) if should_retry(&err) && options.can_retry(failures + 1) => {
+                        failures += 1;
+                        options.backoff(failures)
+                    }
+                    Err(err) => return Err(err),
+                }
+            };
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// Async report the result of performing an action to the conductor server.
+    ///
+    /// # Arguments
+    ///
+    /// * `action_id`: The id of the action which was performed.
+    /// * `result`: The outcome of the action.
+    /// * `conductor_domain`: The url of the conductor instance.
+    ///
+    /// # Errors
+    /// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+    /// * `MsgPackSerialisationFailure`: Produced when the report cannot be serialised to the message pack format.
+    /// * `NetworkError`: Produced when the http post fails for any reason. Holds the Reqwest Error Struct.
+    /// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+    /// * `ConductorError`: Produced when there was an error on the server.
+    ///
+    async fn report_result(&self, action_id: &str, result: ActionResult, conductor_domain: Url) -> Result<(), Error> {
+        let (payload, url) = generate_report_data(action_id, result, &conductor_domain)?;
+        let client = http::async_client();
+        let request_resp = client.post(url)
+            .body(payload)
+            .header(reqwest::header::CONTENT_TYPE, reqwest::header::HeaderValue::from_static("application/msgpack"))
+            .send().await;
+        let response = match request_resp {
+            Ok(r) => r,
+            Err(err) => return Err(Error::NetworkError(err))
+        };
+        match response.bytes().await {
+            Ok(body) => decode_report(body.as_ref()),
+            Err(err) => Err(Error::NetworkError(err)),
+        }
+    }
+}
@@ -1,10 +1,15 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use duplicate::duplicate;
-use chrono::{DateTime, Utc, NaiveDate, NaiveDateTime};
+use chrono::{DateTime, FixedOffset, Local, Utc, NaiveDate, NaiveDateTime};
+use crate::error::ConductorError;
+/// The Arrow schema types the conversions use. They're the ones `arrow::datatypes` re-exports.
+#[cfg(feature = "arrow")]
+pub use arrow_schema;
 
 /// Data types supported by conductor
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, strum::EnumIter)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub enum DataTypes {
     Int,
     Float,
@@ -16,6 +21,12 @@ pub enum DataTypes {
 }
 
 impl DataTypes {
+    /// Every data type in the order they're declared.
+    #[must_use]
+    pub fn all() -> Vec<Self> {
+        <Self as strum::IntoEnumIterator>::iter().collect()
+    }
+
     /// Converts the enum to a string representation which matches quest db data types.
     #[must_use]
     pub const fn to_quest_type_str(&self) -> &str {
@@ -29,9 +40,109 @@ impl DataTypes {
             DataTypes::Double => "double",
         }
     }
+
+    /// QuestDB only indexes symbol columns so only `String` columns can be indexed. They're stored as
+    /// symbols when they are.
+    #[must_use]
+    pub const fn is_indexable(&self) -> bool {
+        matches!(self, DataTypes::String)
+    }
+
+    /// Roughly how many bytes QuestDB uses to store a value of the type. Strings and binary values vary
+    /// in length so an average is used for them.
+    #[must_use]
+    pub const fn approx_byte_size(&self) -> u64 {
+        match self {
+            DataTypes::Int | DataTypes::Time | DataTypes::Double => 8,
+            DataTypes::Float => 4,
+            DataTypes::Bool => 1,
+            DataTypes::String => 32,
+            DataTypes::Binary => 64,
+        }
+    }
+
+    /// Converts a quest db data type into the matching Conductor data type. This is the inverse of
+    /// `to_quest_type_str` and ignores case. Indexed `String` columns are stored as symbols so `symbol`
+    /// is a `String` too. Returns None for quest db types Conductor doesn't use.
+    #[must_use]
+    pub fn from_quest_type_str(quest_type: &str) -> Option<Self> {
+        match quest_type.to_ascii_lowercase().as_str() {
+            "long" => Some(DataTypes::Int),
+            "float" => Some(DataTypes::Float),
+            "timestamp" => Some(DataTypes::Time),
+            "binary" => Some(DataTypes::Binary),
+            "string" | "symbol" => Some(DataTypes::String),
+            "boolean" => Some(DataTypes::Bool),
+            "double" => Some(DataTypes::Double),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Converts the data type into the Arrow data type which stores the same values. `Time` is a
+    /// timestamp with microsecond precision and no time zone as that's how QuestDB stores it and
+    /// `Binary` is variable length binary with 32 bit offsets.
+    ///
+    #[cfg(feature = "arrow")]
+    #[must_use]
+    pub const fn to_arrow_datatype(&self) -> arrow_schema::DataType {
+        match self {
+            DataTypes::Int => arrow_schema::DataType::Int64,
+            DataTypes::Float => arrow_schema::DataType::Float32,
+            DataTypes::Time => arrow_schema::DataType::Timestamp(arrow_schema::TimeUnit::Microsecond, None),
+            DataTypes::Binary => arrow_schema::DataType::Binary,
+            DataTypes::String => arrow_schema::DataType::Utf8,
+            DataTypes::Bool => arrow_schema::DataType::Boolean,
+            DataTypes::Double => arrow_schema::DataType::Float64,
+        }
+    }
+
+    ///
+    /// Converts an Arrow data type into the matching Conductor data type. This is the inverse of
+    /// `to_arrow_datatype` except that timestamps of any precision and time zone are accepted as
+    /// `Time`. Returns None for Arrow types which Conductor can't store.
+    ///
+    #[cfg(feature = "arrow")]
+    #[must_use]
+    pub const fn from_arrow_datatype(data_type: &arrow_schema::DataType) -> Option<Self> {
+        match data_type {
+            arrow_schema::DataType::Int64 => Some(DataTypes::Int),
+            arrow_schema::DataType::Float32 => Some(DataTypes::Float),
+            arrow_schema::DataType::Timestamp(_, _) => Some(DataTypes::Time),
+            arrow_schema::DataType::Binary => Some(DataTypes::Binary),
+            arrow_schema::DataType::Utf8 => Some(DataTypes::String),
+            arrow_schema::DataType::Boolean => Some(DataTypes::Bool),
+            arrow_schema::DataType::Float64 => Some(DataTypes::Double),
+            _ => None,
+        }
+    }
+
+    /// Returns true if values of this type can be aggregated as numbers.
+    #[must_use]
+    pub const fn is_numeric(&self) -> bool {
+        matches!(self, DataTypes::Int | DataTypes::Float | DataTypes::Double)
+    }
+
+    /// Returns true if the json value can be stored in a column of this type.
+    #[must_use]
+    pub fn accepts(&self, value: &serde_json::Value) -> bool {
+        match self {
+            DataTypes::Int => value.is_i64(),
+            DataTypes::Float | DataTypes::Double => value.is_number(),
+            DataTypes::Time => time_from_json(value).is_some(),
+            DataTypes::Binary => serde_json::from_value::<Vec<u8>>(value.clone()).is_ok(),
+            DataTypes::String => value.is_string(),
+            DataTypes::Bool => value.is_boolean(),
+        }
+    }
 }
 
 /// Provides a function to retrieve conductor data types
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be stored by Conductor because it doesn't implement `ToConductorDataType`",
+    label = "unsupported producer field type",
+    note = "skip the field with `#[producer_skip_field]` or implement `ToConductorDataType` for the type"
+)]
 pub trait ToConductorDataType {
     /// returns the Conductor data type for the implimenting type.
     ///
@@ -97,7 +208,7 @@ impl ToConductorDataType for bool {
 #[duplicate(
 time_type;
 [ NaiveDate ]; [ NaiveDateTime ];
-[ DateTime < Utc > ];
+[ DateTime < Utc > ]; [ DateTime < Local > ]; [ DateTime < FixedOffset > ];
 )]
 impl ToConductorDataType for time_type {
     fn conductor_data_type() -> DataTypes {
@@ -105,12 +216,520 @@ impl ToConductorDataType for time_type {
     }
 }
 
+/// Requires the `time` feature. The field must be serialised as RFC 3339 using
+/// `#[serde(with = "time::serde::rfc3339")]` as the default serialisation of `OffsetDateTime` isn't
+/// understood by Conductor.
+#[cfg(feature = "time")]
+impl ToConductorDataType for time::OffsetDateTime {
+    fn conductor_data_type() -> DataTypes {
+        DataTypes::Time
+    }
+}
+
+/// Reads a timestamp from json. Conductor stores every timestamp in UTC.
+/// * A date time without an offset (such as a serialised `NaiveDateTime`) is assumed to already be in UTC.
+/// * A date time with an offset (such as a serialised `DateTime<Local>` or `DateTime<FixedOffset>`)
+///   is converted to UTC. The offset itself isn't stored.
+///
+/// Returns None if the value isn't a timestamp.
+#[must_use]
+pub fn time_from_json(value: &serde_json::Value) -> Option<NaiveDateTime> {
+    if let Ok(time) = serde_json::from_value::<NaiveDateTime>(value.clone()) {
+        return Some(time);
+    }
+    serde_json::from_value::<DateTime<FixedOffset>>(value.clone()).ok().map(|time| time.naive_utc())
+}
+
 pub trait ConductorSchema {
+    /// The column name and data type of every field in the schema. The derive macro generates it so
+    /// that a column can be looked up without building the schema. Empty for manual implementations.
+    const SCHEMA_FIELDS: &'static [SchemaField] = &[];
+
+    /// The columns which are indexed when the producer is registered. The derive macro generates it
+    /// from the fields annotated with `#[producer_index]`.
+    const INDEXED_COLUMNS: &'static [&'static str] = &[];
+
     fn generate_schema() -> HashMap<String, DataTypes>;
+
+    /// The data type of a single column or None if the schema doesn't have the column. This scans
+    /// `SCHEMA_FIELDS` without allocating. Implementations without `SCHEMA_FIELDS` generate the schema instead.
+    fn data_type_of(column: &str) -> Option<DataTypes> {
+        if Self::SCHEMA_FIELDS.is_empty() {
+            return Self::generate_schema().get(column).copied();
+        }
+        Self::SCHEMA_FIELDS.iter().find(|(name, _)| *name == column).map(|(_, data_type)| data_type())
+    }
+
+    /// Generates the schema and validates it using the same rules the Conductor server applies at
+    /// registration. This lets a producer fail fast without a round trip to the server.
+    ///
+    /// # Errors
+    /// Produces the same errors as `validate_schema`.
+    fn try_generate_schema() -> Result<Schema, ConductorError> {
+        let schema = Self::generate_schema();
+        validate_schema(&schema)?;
+        Ok(schema)
+    }
 }
 
 pub type Schema = HashMap<String, DataTypes>;
 
+/// The name of a column along with the function which returns its data type. See `ConductorSchema::SCHEMA_FIELDS`.
+pub type SchemaField = (&'static str, fn() -> DataTypes);
+
+/// The names of the columns which have to be given a value in every emit.
+pub type RequiredColumns = BTreeSet<String>;
+
+/// The names of the columns which QuestDB keeps an index for to speed up queries filtering on them.
+pub type IndexedColumns = BTreeSet<String>;
+
+/// Describes a column of a registered producer.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct SchemaEntry {
+    pub data_type: DataTypes,
+    /// True if every emit has to give this column a value (either directly or through a default).
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Human readable information about a column. It's only descriptive and doesn't affect how data is stored.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct ColumnMetadata {
+    /// The unit of the values in the column such as `°C`.
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl ColumnMetadata {
+    /// Overwrites the fields which are set in `update`. Fields which aren't set are left unchanged.
+    pub fn merge(&mut self, update: Self) {
+        if update.unit.is_some() {
+            self.unit = update.unit;
+        }
+        if update.description.is_some() {
+            self.description = update.description;
+        }
+    }
+}
+
+/// Maps column names to their metadata.
+pub type ColumnMetadataMap = BTreeMap<String, ColumnMetadata>;
+
+/// Values which are stored in a column when it's left out of an emit. Maps column names to the default value.
+pub type ColumnDefaults = BTreeMap<String, serde_json::Value>;
+
+/// Checks that a schema could be registered with a Conductor server.
+///
+/// # Errors
+/// * `NoMembers`: The schema doesn't have any columns.
+/// * `TimestampDefined`: The schema has a column named ts which is reserved for the timestamp generated by Conductor.
+/// * `InvalidColumnNames`: A column name contains a '.' or a '"'.
+pub fn validate_schema(schema: &Schema) -> Result<(), ConductorError> {
+    if schema.is_empty() {
+        return Err(ConductorError::NoMembers("No columns in schema.".to_string()));
+    }
+    if schema.contains_key("ts") {
+        return Err(ConductorError::TimestampDefined("Column with name ts. This is a reserved name.".to_string()));
+    }
+    for col in schema.keys() {
+        if col.contains('.') || col.contains('\"') {
+            return Err(ConductorError::InvalidColumnNames(format!("Column with name {} is invalid as it contains a '.' or a '\"'.", col)));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a schema stored as json, such as `{"id": "Int", "name": "String"}`, and checks that it could be
+/// registered with a Conductor server. Useful to check schema files before they're deployed.
+///
+/// # Errors
+/// * `InvalidSchema`: The json couldn't be parsed into a schema.
+/// * Any of the errors produced by `validate_schema`.
+pub fn validate_schema_json(schema_json: &str) -> Result<Schema, ConductorError> {
+    let schema: Schema = match serde_json::from_str(schema_json) {
+        Ok(schema) => schema,
+        Err(err) => return Err(ConductorError::InvalidSchema(format!("The schema couldn't be parsed. {}", err))),
+    };
+    validate_schema(&schema)?;
+    Ok(schema)
+}
+
+/// Hashes a schema so that identical schemas can be found without comparing them column by column.
+/// The columns are sorted before hashing so the hash doesn't depend on the order of the map. The hash
+/// is the 64 bit FNV-1a hash of the sorted schema as json written as hex. It's stable between
+/// versions of Rust and Conductor so it can be stored.
+#[must_use]
+pub fn schema_hash(schema: &Schema) -> String {
+    let sorted: BTreeMap<&String, &DataTypes> = schema.iter().collect();
+    let json = serde_json::to_string(&sorted).unwrap_or_default();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in json.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Compares two schemas and returns the sorted names of the columns which are only in one of them
+/// or which have a different type in each.
+#[must_use]
+pub fn schema_differences(registered: &Schema, schema: &Schema) -> Vec<String> {
+    let mut columns: Vec<String> = registered.iter()
+        .filter(|(column, data_type)| schema.get(*column) != Some(*data_type))
+        .map(|(column, _)| column.clone())
+        .chain(schema.keys().filter(|column| !registered.contains_key(*column)).cloned())
+        .collect();
+    columns.sort();
+    columns
+}
+
+/// The columns which have to be added to and dropped from a table to migrate it from one schema to another.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaMigration {
+    /// Sorted by column name
+    pub added: Vec<(String, DataTypes)>,
+    /// Sorted by column name
+    pub dropped: Vec<String>,
+}
+
+impl SchemaMigration {
+    /// True if the schemas are the same so there's nothing to migrate.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.dropped.is_empty()
+    }
+}
+
+///
+/// Works out how to migrate a table from the `current` schema to the `new` one. Columns can be added
+/// and dropped but the type of a column which is in both can't be changed.
+///
+/// # Errors
+/// * `InvalidSchema`: A column has a different type in each schema.
+///
+pub fn plan_migration(current: &Schema, new: &Schema) -> Result<SchemaMigration, ConductorError> {
+    let mut changed: Vec<&String> = current.iter()
+        .filter(|(column, data_type)| matches!(new.get(*column), Some(new_type) if new_type != *data_type))
+        .map(|(column, _)| column)
+        .collect();
+    if !changed.is_empty() {
+        changed.sort();
+        let changed: Vec<&str> = changed.into_iter().map(String::as_str).collect();
+        return Err(ConductorError::InvalidSchema(format!("The type of the columns {} can't be changed", changed.join(", "))));
+    }
+    let mut added: Vec<(String, DataTypes)> = new.iter()
+        .filter(|(column, _)| !current.contains_key(*column))
+        .map(|(column, data_type)| (column.clone(), *data_type))
+        .collect();
+    added.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut dropped: Vec<String> = current.keys().filter(|column| !new.contains_key(*column)).cloned().collect();
+    dropped.sort();
+    Ok(SchemaMigration { added, dropped })
+}
+
+/// Compares the columns of emitted data against a schema. Returns the sorted names of the columns of
+/// the schema which aren't in the data followed by the sorted names of the columns of the data which
+/// aren't in the schema.
+#[must_use]
+pub fn diff_emit_columns<I, K>(data_keys: I, schema: &Schema) -> (Vec<String>, Vec<String>)
+where
+    I: IntoIterator<Item = K>,
+    K: AsRef<str>,
+{
+    let keys: HashSet<String> = data_keys.into_iter().map(|key| key.as_ref().to_string()).collect();
+    let mut missing: Vec<String> = schema.keys().filter(|column| !keys.contains(*column)).cloned().collect();
+    let mut extra: Vec<String> = keys.into_iter().filter(|key| !schema.contains_key(key)).collect();
+    missing.sort();
+    extra.sort();
+    (missing, extra)
+}
+
+///
+/// Renders a schema as a table for terminal output. The columns are sorted by name and listed with
+/// the QuestDB type they're stored as. Every line ends with a newline.
+///
+/// ```text
+/// column | type
+/// -------+-------
+/// id     | long
+/// name   | string
+/// ```
+///
+#[must_use]
+pub fn format_table(schema: &Schema) -> String {
+    const COLUMN_HEADER: &str = "column";
+    const TYPE_HEADER: &str = "type";
+    let mut columns: Vec<(&String, &str)> = schema.iter().map(|(column, data_type)| (column, data_type.to_quest_type_str())).collect();
+    columns.sort();
+    let column_width = columns.iter().map(|(column, _)| column.chars().count()).chain(std::iter::once(COLUMN_HEADER.len())).max().unwrap_or_default();
+    let type_width = columns.iter().map(|(_, quest_type)| quest_type.len()).chain(std::iter::once(TYPE_HEADER.len())).max().unwrap_or_default();
+    let mut table = format!("{:<width$} | {}\n", COLUMN_HEADER, TYPE_HEADER, width = column_width);
+    table += &format!("{}-+-{}\n", "-".repeat(column_width), "-".repeat(type_width));
+    for (column, quest_type) in columns {
+        table += &format!("{:<width$} | {}\n", column, quest_type, width = column_width);
+    }
+    table
+}
+
+/// Orders the columns of emitted data for an insert. Columns named in `order` come first in that order
+/// and the rest follow in the order they're given in. Names in `order` which aren't columns are skipped.
+#[must_use]
+pub fn order_columns<'a, I>(columns: I, order: &[String]) -> Vec<&'a String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut rest: Vec<&'a String> = columns.into_iter().collect();
+    let mut ordered = Vec::with_capacity(rest.len());
+    for column in order {
+        if let Some(index) = rest.iter().position(|candidate| *candidate == column) {
+            ordered.push(rest.remove(index));
+        }
+    }
+    ordered.extend(rest);
+    ordered
+}
+
+///
+/// Rebuilds a schema from the columns of a QuestDB table as they're listed by `table_columns`. Each
+/// column is its name, QuestDB type and whether it's the designated timestamp. The designated
+/// timestamp has to be called ts and is left out of the schema.
+///
+/// # Errors
+/// * `InvalidSchema`: A column has a type Conductor doesn't use or the table doesn't have a designated
+/// timestamp called ts.
+///
+pub fn schema_from_table_columns(table: &str, columns: &[(String, String, bool)]) -> Result<Schema, ConductorError> {
+    let mut schema = Schema::new();
+    let mut has_timestamp = false;
+    for (column, quest_type, designated) in columns {
+        if *designated {
+            has_timestamp = column == "ts";
+            continue;
+        }
+        match DataTypes::from_quest_type_str(quest_type) {
+            Some(data_type) => {
+                schema.insert(column.clone(), data_type);
+            }
+            None => {
+                return Err(ConductorError::InvalidSchema(format!("Column {} of table {} has the unsupported type {}", column, table, quest_type)));
+            }
+        }
+    }
+    if !has_timestamp {
+        return Err(ConductorError::InvalidSchema(format!("Table {} doesn't have a designated timestamp called ts", table)));
+    }
+    Ok(schema)
+}
+
+/// Checks that every default value belongs to a column in the schema and can be stored in that column.
+///
+/// # Errors
+/// * `InvalidColumnNames`: A default was given for a column which isn't in the schema.
+/// * `InvalidData`: A default value can't be stored in the column it was given for.
+pub fn validate_column_defaults(schema: &Schema, defaults: &ColumnDefaults) -> Result<(), ConductorError> {
+    for (column, value) in defaults {
+        match schema.get(column) {
+            Some(data_type) if data_type.accepts(value) => {}
+            Some(data_type) => return Err(ConductorError::InvalidData(format!("Default value {} for column {} can't be stored as {:?}.", value, column, data_type))),
+            None => return Err(ConductorError::InvalidColumnNames(format!("Default value given for column {} which isn't in the schema.", column))),
+        }
+    }
+    Ok(())
+}
+
+///
+/// Builds a JSON Schema (draft 2020-12) document describing the data object of an emit for a
+/// producer with the given schema. Clients can use it to validate emits with any JSON Schema
+/// validator before sending them.
+///
+/// Columns which are required and don't have a default have to be given. Any other column may be
+/// left out. No column may be null since the server rejects null values. Columns which aren't in the
+/// schema are rejected as they are by the server unless the producer was registered with lenient
+/// columns. `Time` columns are strings without a `format` because the server also accepts date
+/// times without an offset which aren't valid RFC 3339 date times. `Binary` columns are arrays of
+/// bytes.
+///
+#[must_use]
+pub fn to_json_schema(schema: &Schema, required: &RequiredColumns, defaults: &ColumnDefaults) -> serde_json::Value {
+    let mut properties = serde_json::Map::new();
+    for (column, data_type) in schema {
+        let json_type = match data_type {
+            DataTypes::Int => "integer",
+            DataTypes::Float | DataTypes::Double => "number",
+            DataTypes::Bool => "boolean",
+            DataTypes::Time | DataTypes::String => "string",
+            DataTypes::Binary => "array",
+        };
+        let mut property = serde_json::json!({ "type": json_type });
+        if *data_type == DataTypes::Binary {
+            property["items"] = serde_json::json!({ "type": "integer", "minimum": 0, "maximum": 255 });
+        }
+        properties.insert(column.clone(), property);
+    }
+    let mut required: Vec<&String> = required.iter()
+        .filter(|column| schema.contains_key(*column) && !defaults.contains_key(*column))
+        .collect();
+    required.sort();
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+///
+/// Builds the Arrow schema of the data table of a producer with the given schema. The `ts` column
+/// comes first followed by the columns of the schema in name order. Every column except `ts` is
+/// nullable. See `DataTypes::to_arrow_datatype` for how each column is mapped.
+///
+#[cfg(feature = "arrow")]
+#[must_use]
+pub fn to_arrow_schema(schema: &Schema) -> arrow_schema::Schema {
+    let mut columns: Vec<(&String, &DataTypes)> = schema.iter().collect();
+    columns.sort_by_key(|(column, _)| *column);
+    let mut fields = Vec::with_capacity(columns.len() + 1);
+    fields.push(arrow_schema::Field::new("ts", DataTypes::Time.to_arrow_datatype(), false));
+    for (column, data_type) in columns {
+        fields.push(arrow_schema::Field::new(column.as_str(), data_type.to_arrow_datatype(), true));
+    }
+    arrow_schema::Schema::new(fields)
+}
+
+/// Checks that every required column is part of the schema.
+///
+/// # Errors
+/// * `InvalidColumnNames`: A column was marked as required but isn't in the schema.
+pub fn validate_required_columns(schema: &Schema, required: &RequiredColumns) -> Result<(), ConductorError> {
+    match required.iter().find(|column| !schema.contains_key(*column)) {
+        Some(column) => Err(ConductorError::InvalidColumnNames(format!("Column {} is required but isn't in the schema.", column))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every indexed column is in the schema and has a type QuestDB can index.
+///
+/// # Errors
+/// * `InvalidColumnNames`: A column was marked as indexed but isn't in the schema.
+/// * `InvalidSchema`: An indexed column has a type which can't be indexed. See `DataTypes::is_indexable`.
+pub fn validate_indexed_columns(schema: &Schema, indexed: &IndexedColumns) -> Result<(), ConductorError> {
+    for column in indexed {
+        match schema.get(column) {
+            Some(data_type) if data_type.is_indexable() => {}
+            Some(data_type) => return Err(ConductorError::InvalidSchema(format!("Column {} is {:?} which can't be indexed. Only String columns can be indexed.", column, data_type))),
+            None => return Err(ConductorError::InvalidColumnNames(format!("Column {} is indexed but isn't in the schema.", column))),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a column is part of the schema and is numeric so that it can be aggregated.
+/// Returns the data type of the column.
+///
+/// # Errors
+/// * `InvalidColumnNames`: The column isn't in the schema.
+/// * `InvalidData`: The column isn't numeric.
+pub fn validate_numeric_column(schema: &Schema, column: &str) -> Result<DataTypes, ConductorError> {
+    match schema.get(column) {
+        Some(data_type) if data_type.is_numeric() => Ok(*data_type),
+        Some(data_type) => Err(ConductorError::InvalidData(format!("Column {} is {:?} which isn't numeric.", column, data_type))),
+        None => Err(ConductorError::InvalidColumnNames(format!("Column {} isn't in the schema.", column))),
+    }
+}
+
+/// Adds the default value of every column which is missing from the data.
+pub fn fill_column_defaults(data: &mut HashMap<String, serde_json::Value>, defaults: &ColumnDefaults) {
+    for (column, value) in defaults {
+        data.entry(column.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+///
+/// Parses a string holding a number into a json number for `Int`, `Float` and `Double` columns.
+/// Returns `None` if the value isn't a string, the column isn't numeric or the string isn't a
+/// number of the column's type. Surrounding whitespace isn't allowed.
+///
+#[must_use]
+pub fn coerce_numeric_string(value: &serde_json::Value, data_type: DataTypes) -> Option<serde_json::Value> {
+    let text = value.as_str()?;
+    match data_type {
+        DataTypes::Int => text.parse::<i64>().ok().map(serde_json::Value::from),
+        DataTypes::Float | DataTypes::Double => text.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map(serde_json::Value::Number),
+        _ => None,
+    }
+}
+
+///
+/// Converts `0`, `1`, `"true"` and `"false"` into a json boolean for `Bool` columns. Returns `None` if
+/// the column isn't a `Bool` or the value is anything else, including other integers.
+///
+#[must_use]
+pub fn coerce_lenient_bool(value: &serde_json::Value, data_type: DataTypes) -> Option<serde_json::Value> {
+    if data_type != DataTypes::Bool {
+        return None;
+    }
+    match value {
+        serde_json::Value::Number(number) => match number.as_u64() {
+            Some(0) => Some(serde_json::Value::Bool(false)),
+            Some(1) => Some(serde_json::Value::Bool(true)),
+            _ => None,
+        },
+        serde_json::Value::String(text) => text.parse::<bool>().ok().map(serde_json::Value::Bool),
+        _ => None,
+    }
+}
+
+/// Removes every column which isn't in the schema from the data. Returns the names of the removed columns.
+pub fn retain_known_columns(data: &mut HashMap<String, serde_json::Value>, schema: &Schema) -> Vec<String> {
+    let unknown: Vec<String> = data.keys().filter(|column| !schema.contains_key(*column)).cloned().collect();
+    for column in &unknown {
+        data.remove(column);
+    }
+    unknown
+}
+
+///
+/// Overlays a partial update onto the latest row of a producer. Columns of the latest row which
+/// aren't in the update are carried over, other than those which aren't in the schema (such as the
+/// timestamp) or are null. Columns in the update replace those of the latest row even when null.
+///
+/// `Float` values read back from the database are widened to an f64 which usually has more digits
+/// than were stored. Carried over floats are rounded to the shortest decimal which reads back as the
+/// same f32 so that they're accepted by producers with strict float precision.
+///
+#[must_use]
+pub fn merge_with_latest(latest: &HashMap<String, serde_json::Value>, update: &HashMap<String, serde_json::Value>, schema: &Schema) -> HashMap<String, serde_json::Value> {
+    let mut merged = update.clone();
+    for (column, value) in latest {
+        let data_type = match schema.get(column) {
+            Some(data_type) if !value.is_null() && !update.contains_key(column) => data_type,
+            _ => continue,
+        };
+        let value = match (data_type, value.as_f64()) {
+            #[allow(clippy::cast_possible_truncation)]
+            (DataTypes::Float, Some(float)) => (float as f32).to_string().parse::<f64>().map_or_else(|_| value.clone(), serde_json::Value::from),
+            _ => value.clone(),
+        };
+        merged.insert(column.clone(), value);
+    }
+    merged
+}
+
+/// Checks that a value can be stored in a `Float` column without losing precision.
+/// The value is accepted if the f32 closest to it has the same shortest decimal representation.
+/// This means that values such as 0.1 are accepted even though neither an f32 or an f64 can hold them exactly.
+#[must_use]
+pub fn fits_in_float(value: f64) -> bool {
+    #[allow(clippy::cast_possible_truncation)]
+    let narrowed = value as f32;
+    narrowed.is_finite() == value.is_finite() && narrowed.to_string().parse::<f64>() == Ok(value)
+}
+
 /// A struct which assists in building a schema.
 /// Most of the time this won't be necessary as the producer derive macro does this for you.
 pub struct Builder {
@@ -144,6 +763,24 @@ impl Builder {
         self
     }
 
+    /// Adds a column with the data type of the rust type `T`. This keeps the schema in step with the
+    /// type that's actually emitted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use conductor_common::schema::{Builder, DataTypes};
+    /// let schema = Builder::new().add::<f64>(String::from("temp")).add::<str>(String::from("room")).build();
+    /// assert_eq!(schema["temp"], DataTypes::Double);
+    /// assert_eq!(schema["room"], DataTypes::String);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    #[must_use]
+    pub fn add<T: ToConductorDataType + ?Sized>(mut self, name: String) -> Self {
+        self.schema.insert(name, T::conductor_data_type());
+        self
+    }
+
     #[must_use]
     pub fn add_int(mut self, name: String) -> Self {
         self.schema.insert(name, DataTypes::Int);
@@ -0,0 +1,131 @@
+//! Describes the version of a Conductor instance so clients can check what it supports before
+//! relying on it. Instances running different versions can be told apart through `/v1/version`.
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+use crate::error::ConductorError;
+use crate::http;
+use crate::producer::Error;
+use crate::schema::DataTypes;
+
+/// The version of Conductor. The client and server crates are released together with the same version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The version of the requests and responses sent over the wire. It's raised whenever a change would
+/// stop an older client or server from understanding them. Version 2 added the protocol version to
+/// registrations.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The oldest wire protocol version which is still accepted. Registrations without a protocol version
+/// come from clients which predate it and are version 1.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+///
+/// Checks a client which speaks `protocol_version` can be served. Clients which are older than
+/// `PROTOCOL_VERSION` but not older than `MIN_PROTOCOL_VERSION` are served the way they expect.
+///
+/// # Errors
+/// * `UnsupportedProtocol`: The version is newer than `PROTOCOL_VERSION` or older than `MIN_PROTOCOL_VERSION`.
+///
+pub fn check_protocol_version(protocol_version: u32) -> Result<(), ConductorError> {
+    if protocol_version > PROTOCOL_VERSION {
+        return Err(ConductorError::UnsupportedProtocol(format!("The client speaks protocol version {} which is newer than the newest this instance supports of {}. Upgrade the Conductor instance.", protocol_version, PROTOCOL_VERSION)));
+    }
+    if protocol_version < MIN_PROTOCOL_VERSION {
+        return Err(ConductorError::UnsupportedProtocol(format!("The client speaks protocol version {} which is older than the oldest this instance supports of {}. Upgrade the client.", protocol_version, MIN_PROTOCOL_VERSION)));
+    }
+    Ok(())
+}
+
+/// The versions a Conductor instance was built with and the data types it supports.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct VersionInfo {
+    /// The version of the Conductor crate the instance was built from
+    pub version: String,
+    /// The newest wire protocol version the instance speaks. See `PROTOCOL_VERSION`.
+    pub protocol_version: u32,
+    /// The oldest wire protocol version the instance still accepts. See `MIN_PROTOCOL_VERSION`.
+    pub min_protocol_version: u32,
+    /// Every data type the instance accepts in a schema
+    pub data_types: Vec<DataTypes>,
+}
+
+impl VersionInfo {
+    /// Describes an instance built from the crate `version` which supports everything this version of
+    /// the protocol does.
+    #[must_use]
+    pub fn new(version: &str) -> Self {
+        Self {
+            version: version.to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            data_types: DataTypes::all(),
+        }
+    }
+
+    /// Returns true if the instance accepts the wire protocol version this client speaks.
+    #[must_use]
+    pub const fn is_compatible(&self) -> bool {
+        self.min_protocol_version <= PROTOCOL_VERSION && PROTOCOL_VERSION <= self.protocol_version
+    }
+
+    /// Returns true if the instance accepts the data type in a schema.
+    #[must_use]
+    pub fn supports(&self, data_type: DataTypes) -> bool {
+        self.data_types.contains(&data_type)
+    }
+}
+
+fn version_url(conductor_domain: &Url) -> Result<Url, Error> {
+    conductor_domain.join("/v1/version")
+        .map_err(|err| Error::InvalidConductorDomain(format!("The conductor domain was invalid. {}", err)))
+}
+
+fn decode_version(status: reqwest::StatusCode, body: &[u8]) -> Result<VersionInfo, Error> {
+    if status != reqwest::StatusCode::OK {
+        return match rmp_serde::from_read_ref(body) {
+            Ok(error) => Err(Error::ConductorError(error)),
+            Err(_) => Err(Error::UnexpectedStatus(status)),
+        };
+    }
+    rmp_serde::from_read_ref(body).map_err(Error::MsgPackDeserializationFailure)
+}
+
+///
+/// Asks a Conductor instance which version it runs. This function blocks.
+///
+/// # Errors
+/// * `InvalidConductorDomain`: Produced when the conductor domain is an invalid url.
+/// * `NetworkError`: Produced when the http get fails for any reason. Holds the Reqwest Error Struct.
+/// * `UnexpectedStatus`: Produced when the server responds with anything other than 200. Instances
+/// which are older than the version route respond with 404.
+/// * `ConductorError`: Produced when the server rejected the request with an error such as `Busy`.
+/// * `MsgPackDeserializationFailure`: Produced when the response couldn't be deserialized from message pack.
+///
+pub fn get_version(conductor_domain: &Url) -> Result<VersionInfo, Error> {
+    let response = http::blocking_client().get(version_url(conductor_domain)?)
+        .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+        .send()
+        .map_err(Error::NetworkError)?;
+    let status = response.status();
+    let body = response.bytes().map_err(Error::NetworkError)?;
+    decode_version(status, body.as_ref())
+}
+
+///
+/// Asynchronously asks a Conductor instance which version it runs.
+///
+/// # Errors
+/// The same as `get_version`.
+///
+#[cfg(feature = "async")]
+pub async fn get_version_async(conductor_domain: &Url) -> Result<VersionInfo, Error> {
+    let response = http::async_client().get(version_url(conductor_domain)?)
+        .header(reqwest::header::ACCEPT, reqwest::header::HeaderValue::from_static("application/msgpack"))
+        .send().await
+        .map_err(Error::NetworkError)?;
+    let status = response.status();
+    let body = response.bytes().await.map_err(Error::NetworkError)?;
+    decode_version(status, body.as_ref())
+}
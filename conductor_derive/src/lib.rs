@@ -3,26 +3,106 @@
 
 extern crate proc_macro;
 use proc_macro::{TokenStream};
-use quote::quote;
+use quote::{quote, quote_spanned};
 
 use syn::{DeriveInput, Fields, Data};
 use syn::spanned::Spanned;
 use quote::TokenStreamExt;
 
 ///
-/// Generates a list of tuples which contain the name, type and any annotations on each named
-/// field on a struct.
+/// Finds the name serde serialises with from a `#[serde(...)]` attribute such as `rename` or
+/// `rename_all`. Both `key = "name"` and `key(serialize = "name")` are supported. Returns None if
+/// none of the attributes set it.
+///
+/// # Errors
+/// * If a serde attribute couldn't be parsed or the value isn't a string then an error is generated.
+///
+fn serde_serialize_name(attrs: &[syn::Attribute], key: &str) -> Result<Option<syn::LitStr>, syn::Error> {
+    let mut name = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("serde")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            _ => continue,
+        };
+        for nested in &list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(value)) if value.path.is_ident(key) => {
+                    name = Some(lit_str(&value.lit)?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(inner)) if inner.path.is_ident(key) => {
+                    for nested in &inner.nested {
+                        if let syn::NestedMeta::Meta(syn::Meta::NameValue(value)) = nested {
+                            if value.path.is_ident("serialize") {
+                                name = Some(lit_str(&value.lit)?);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(name)
+}
+
+fn lit_str(lit: &syn::Lit) -> Result<syn::LitStr, syn::Error> {
+    match lit {
+        syn::Lit::Str(lit) => Ok(lit.clone()),
+        _ => Err(syn::Error::new(lit.span(), "Expected a string")),
+    }
+}
+
+///
+/// Applies a serde `rename_all` rule to the name of a field in the same way serde does.
+///
+/// # Errors
+/// * If the rule isn't one serde supports then an error is generated.
+///
+fn apply_rename_all(rule: &syn::LitStr, field: &str) -> Result<String, syn::Error> {
+    let pascal_case = || field.split('_').map(|word| {
+        let mut chars = word.chars();
+        chars.next().map_or_else(String::new, |first| first.to_ascii_uppercase().to_string() + chars.as_str())
+    }).collect::<String>();
+    Ok(match rule.value().as_str() {
+        "lowercase" | "snake_case" => field.to_string(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => field.to_ascii_uppercase(),
+        "PascalCase" => pascal_case(),
+        "camelCase" => {
+            let pascal = pascal_case();
+            let mut chars = pascal.chars();
+            chars.next().map_or_else(String::new, |first| first.to_ascii_lowercase().to_string() + chars.as_str())
+        }
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.to_ascii_uppercase().replace('_', "-"),
+        _ => return Err(syn::Error::new(rule.span(), format!("Unknown serde rename_all rule {}", rule.value()))),
+    })
+}
+
+/// The identifiers, types and column names of the stored fields of a struct, the column names of the
+/// indexed fields and the name of the struct.
+type StructFields<'a> = (Vec<&'a syn::Ident>, Vec<&'a syn::Type>, Vec<String>, Vec<String>, &'a syn::Ident);
+
+///
+/// Generates a list of tuples which contain the name, type, column name and any annotations on
+/// each named field on a struct. The column name is the name serde serialises the field with so
+/// `#[serde(rename = "...")]` on the field and `#[serde(rename_all = "...")]` on the struct are
+/// respected.
 ///
 /// # Errors
 /// * If the given input is not a struct then an error is generated.
 /// * If the given input doesn't have named fields then an error is generated.
+/// * If every field is skipped (or there are no fields) then an error is generated as the schema
+/// would be empty and the registration would be rejected by the server.
+/// * If a serde rename attribute is invalid then an error is generated.
+/// * If a field is annotated with both `#[producer_index]` and `#[producer_skip_field]` then an error
+/// is generated.
 ///
 /// # Arguments
 ///
 /// * `item`: The input tokens to be processed.
 ///
 
-fn get_fields_types(item:&DeriveInput) -> Result<(Vec<&syn::Ident>, Vec<&syn::Type> , &syn::Ident), TokenStream> {
+fn get_fields_types(item:&DeriveInput) -> Result<StructFields<'_>, TokenStream> {
     let struct_name = &item.ident;
 
     let struct_data = if let Data::Struct(struct_body) = &item.data {
@@ -36,24 +116,45 @@ fn get_fields_types(item:&DeriveInput) -> Result<(Vec<&syn::Ident>, Vec<&syn::Ty
     } else {
         return Err(syn::Error::new(item.span(), "Named fields are missing").to_compile_error().into());
     };
+    let rename_all = serde_serialize_name(&item.attrs, "rename_all").map_err(|err| TokenStream::from(err.to_compile_error()))?;
     let mut fields_vec = Vec::new();
     let mut fields_type_vec = Vec::new();
+    let mut column_names = Vec::new();
+    let mut indexed_columns = Vec::new();
     for field in &fields.named {
-        let mut skip = false;
-        for attr in &field.attrs {
-            if attr.path.is_ident("producer_skip_field") {
-                skip = true;
-                break;
-            }
+        let skip = field.attrs.iter().any(|attr| attr.path.is_ident("producer_skip_field"));
+        let index = field.attrs.iter().find(|attr| attr.path.is_ident("producer_index"));
+        if let (true, Some(index)) = (skip, index) {
+            return Err(syn::Error::new(index.span(), "A field annotated with #[producer_skip_field] isn't stored so it can't be indexed").to_compile_error().into());
         }
         if skip {
             continue;
         }
 
+        let ident = field.ident.as_ref().unwrap();
+        let column_name = match serde_serialize_name(&field.attrs, "rename") {
+            Ok(Some(rename)) => rename.value(),
+            Ok(None) => {
+                // serde drops the r# of raw identifiers
+                let field_name = ident.to_string().trim_start_matches("r#").to_string();
+                match &rename_all {
+                    Some(rule) => apply_rename_all(rule, &field_name).map_err(|err| TokenStream::from(err.to_compile_error()))?,
+                    None => field_name,
+                }
+            }
+            Err(err) => return Err(err.to_compile_error().into()),
+        };
         fields_type_vec.push(&field.ty);
-        fields_vec.push(field.ident.as_ref().unwrap());
+        fields_vec.push(ident);
+        if index.is_some() {
+            indexed_columns.push(column_name.clone());
+        }
+        column_names.push(column_name);
     }
-    Ok((fields_vec, fields_type_vec, struct_name))
+    if fields_vec.is_empty() {
+        return Err(syn::Error::new(struct_name.span(), "Producer has no fields to store. At least one field must not be annotated with #[producer_skip_field]").to_compile_error().into());
+    }
+    Ok((fields_vec, fields_type_vec, column_names, indexed_columns, struct_name))
 }
 
 ///
@@ -67,11 +168,25 @@ fn get_fields_types(item:&DeriveInput) -> Result<(Vec<&syn::Ident>, Vec<&syn::Ty
 ///  `#[producer_skip_field]` annotation. Members with this annotation will be skipped in the schema.
 /// This is useful for storing data such as the conductor UUID in the struct.
 ///
+/// The column names and types are also generated as the `SCHEMA_FIELDS` const so that
+/// `data_type_of` can look up a single column without building the schema.
+///
+/// Fields annotated with `#[producer_index]` are listed in the `INDEXED_COLUMNS` const and are indexed
+/// when the producer is registered. Only `String` columns can be indexed, other types are rejected
+/// by the server.
+///
+/// The columns are named the way serde serialises the fields so `#[serde(rename = "...")]` on a
+/// field and `#[serde(rename_all = "...")]` on the struct change the column names.
+///
+/// Generic structs are supported. The generated impls keep the struct's generics and where clause
+/// and additionally require the struct and each of its fields to be `Serialize`.
+///
 /// # Panics
 /// It will panic if the token stream provided is not able to be passed.
 ///
 /// # Errors
-/// Errors will be produced if the input is not a struct or if it has not got named fields.
+/// Errors will be produced if the input is not a struct, if it has not got named fields, if every
+/// field is skipped or if a skipped field is indexed.
 ///
 /// # Examples
 /// ```
@@ -90,40 +205,70 @@ fn get_fields_types(item:&DeriveInput) -> Result<(Vec<&syn::Ident>, Vec<&syn::Ty
 ///  //ignore skipped fields
 ///  assert_eq!(schema.contains_key("uuid"), false);
 /// ```
-#[proc_macro_derive(Producer, attributes(producer_skip_field))]
+#[proc_macro_derive(Producer, attributes(producer_skip_field, producer_index))]
 pub fn derive_producer(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
 
     let item:DeriveInput = syn::parse(input).expect("Couldn't pass input tokens");
 
-    let (fields_vec, fields_type_vec, struct_name)  = match get_fields_types(&item) {
+    let (fields_vec, fields_type_vec, column_names, indexed_columns, struct_name)  = match get_fields_types(&item) {
         Ok(sd) => sd,
         Err(err) => return err
     };
 
+    // Generic structs only implement Serialize and Clone when their parameters do so the bounds
+    // required by Base are added to the generated impls.
+    let mut generics = item.generics.clone();
+    if !generics.params.is_empty() {
+        let where_clause = generics.make_where_clause();
+        where_clause.predicates.push(syn::parse_quote!(Self: conductor::serde::Serialize + std::clone::Clone));
+        for field_type in &fields_type_vec {
+            where_clause.predicates.push(syn::parse_quote!(#field_type: conductor::serde::Serialize));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Spanned to the field type so that an unsupported type is reported on the field itself rather
+    // than somewhere inside the generated code.
+    let data_type_fns = fields_type_vec.iter().map(|field_type| quote_spanned! {field_type.span()=>
+        <#field_type as conductor::schema::ToConductorDataType>::conductor_data_type
+    });
+
     let body_tokens = quote! {
-        impl conductor::schema::ConductorSchema for #struct_name {
+        impl #impl_generics conductor::schema::ConductorSchema for #struct_name #ty_generics #where_clause {
+            const SCHEMA_FIELDS: &'static [conductor::schema::SchemaField] = &[
+                #( (#column_names, #data_type_fns), )*
+            ];
+
+            const INDEXED_COLUMNS: &'static [&'static str] = &[#( #indexed_columns ),*];
+
             fn generate_schema() ->  std::collections::HashMap<std::string::String,conductor::schema::DataTypes> {
-                let mut schema = std::collections::HashMap::new();
+                Self::SCHEMA_FIELDS.iter()
+                    .map(|(column_name, data_type)| (std::string::String::from(*column_name), data_type()))
+                    .collect()
+            }
+        }
+        impl #impl_generics conductor::producer::Base for #struct_name #ty_generics #where_clause {
+            fn to_data_map(&self) -> std::result::Result<std::collections::HashMap<std::string::String, conductor::serde_json::Value>, conductor::producer::Error> {
+                let mut data = std::collections::HashMap::new();
                 #(
-                    schema.insert(std::string::String::from(stringify!(#fields_vec)), #fields_type_vec::conductor_data_type());
+                    let value = conductor::serde_json::to_value(&self.#fields_vec).map_err(conductor::producer::Error::JsonSerialisationFailure)?;
+                    data.insert(std::string::String::from(#column_names), value);
                 )*
-                schema
+                Ok(data)
             }
         }
-        impl conductor::producer::Base for #struct_name {}
     };
     let mut tokens = quote! {
-        impl conductor::producer::Producer for #struct_name {}
+        impl #impl_generics conductor::producer::Producer for #struct_name #ty_generics #where_clause {}
     };
-    tokens.append_all(body_tokens.clone());
+    tokens.append_all(body_tokens);
     #[cfg(feature = "async")]
     {
         tokens.append_all(quote! {
-            impl conductor::producer::AsyncProducer for #struct_name {}
+            impl #impl_generics conductor::producer::AsyncProducer for #struct_name #ty_generics #where_clause {}
         });
-        tokens.append_all(body_tokens);
     }
     tokens.into()
 }
\ No newline at end of file
@@ -0,0 +1,156 @@
+//! A minimal http server used to exercise the producer clients without a running Conductor instance.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+/// A request as it was received by the mock server.
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub request_line: String,
+    /// The headers in the order they were sent with their names in lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Which connection the request was sent on counting from 1 in the order they were accepted.
+    pub connection: usize,
+}
+
+impl ReceivedRequest {
+    /// The value of the first header with the name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Checks the request was sent to `route`, the method followed by the path such as
+    /// `POST /v1/producer/emit`, and decodes its msgpack body.
+    pub fn decode<T: DeserializeOwned>(&self, route: &str) -> T {
+        assert!(self.request_line.starts_with(&format!("{} ", route)), "expected a request to {} but got {}", route, self.request_line.trim_end());
+        rmp_serde::from_read_ref(&self.body).expect("the request body couldn't be decoded")
+    }
+}
+
+/// Encodes a response body as msgpack the same way Conductor encodes its responses.
+pub fn msgpack<T: Serialize>(value: &T) -> Vec<u8> {
+    rmp_serde::to_vec_named(value).expect("couldn't encode the mock response")
+}
+
+///
+/// The same as `serve` for a server which answers a single request with `status` and `result`
+/// encoded as msgpack.
+///
+pub fn serve_result<T: Serialize>(status: u16, result: &T) -> (Url, mpsc::Receiver<ReceivedRequest>) {
+    serve(vec![(status, msgpack(result))])
+}
+
+///
+/// Waits for the next request the server handled, checks it was sent to `route` and decodes its
+/// msgpack body. See `ReceivedRequest::decode`.
+///
+pub fn expect_request<T: DeserializeOwned>(requests: &mpsc::Receiver<ReceivedRequest>, route: &str) -> T {
+    requests.recv().expect("the request wasn't sent").decode(route)
+}
+
+///
+/// Starts a server on a random local port which answers each incoming request with the next
+/// status and body from `responses`. Once every response has been used the server stops.
+///
+/// Returns the url of the server and a receiver which yields every request the server handled.
+///
+pub fn serve(responses: Vec<(u16, Vec<u8>)>) -> (Url, mpsc::Receiver<ReceivedRequest>) {
+    serve_with_delay(responses, Duration::from_secs(0))
+}
+
+///
+/// The same as `serve` except that the server waits for `delay` after receiving each request before
+/// it responds. Useful to simulate a slow Conductor instance.
+///
+pub fn serve_with_delay(responses: Vec<(u16, Vec<u8>)>, delay: Duration) -> (Url, mpsc::Receiver<ReceivedRequest>) {
+    serve_connections(responses, delay, false)
+}
+
+///
+/// The same as `serve` except that connections are kept open after each response so that clients
+/// can send their next request on them. `ReceivedRequest::connection` tells which connection each
+/// request was sent on.
+///
+pub fn serve_keep_alive(responses: Vec<(u16, Vec<u8>)>) -> (Url, mpsc::Receiver<ReceivedRequest>) {
+    serve_connections(responses, Duration::from_secs(0), true)
+}
+
+fn serve_connections(responses: Vec<(u16, Vec<u8>)>, delay: Duration, keep_alive: bool) -> (Url, mpsc::Receiver<ReceivedRequest>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("couldn't bind the mock server");
+    let address = listener.local_addr().expect("mock server has no local address");
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut open: Option<BufReader<TcpStream>> = None;
+        let mut connections = 0;
+        for (status, body) in responses {
+            //connections which are closed without sending a request don't use up a response
+            let (mut reader, request_line) = loop {
+                let mut reader = match open.take() {
+                    Some(reader) => reader,
+                    None => match listener.accept() {
+                        Ok((stream, _)) => {
+                            connections += 1;
+                            BufReader::new(stream)
+                        }
+                        Err(_) => return,
+                    },
+                };
+                let mut request_line = String::new();
+                match reader.read_line(&mut request_line) {
+                    Ok(0) => continue,
+                    Ok(_) => break (reader, request_line),
+                    Err(_) if keep_alive => continue,
+                    Err(_) => return,
+                }
+            };
+            let mut content_length = 0;
+            let mut headers = Vec::new();
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).unwrap_or(0) == 0 || header == "\r\n" {
+                    break;
+                }
+                if let Some(length) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap_or(0);
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            if reader.read_exact(&mut request_body).is_err() {
+                return;
+            }
+            let _ = sender.send(ReceivedRequest {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body: request_body,
+                connection: connections,
+            });
+
+            thread::sleep(delay);
+            let header = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+                status,
+                body.len(),
+                if keep_alive { "keep-alive" } else { "close" }
+            );
+            let stream = reader.get_mut();
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(&body);
+            //a client which opens another connection instead is served once the open one times out
+            if keep_alive && stream.set_read_timeout(Some(Duration::from_secs(1))).is_ok() {
+                open = Some(reader);
+            }
+        }
+    });
+    let url = Url::parse(&format!("http://{}", address)).expect("mock server address isn't a valid url");
+    (url, receiver)
+}
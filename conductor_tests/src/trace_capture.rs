@@ -0,0 +1,76 @@
+//! A tracing subscriber which keeps every span it's given so that tests can check which spans were
+//! opened and with which fields.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// A span as it was opened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedSpan {
+    pub name: &'static str,
+    /// The recorded fields with their values formatted as strings.
+    pub fields: BTreeMap<String, String>,
+}
+
+struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// Keeps every span opened while it's the default subscriber in the order they were opened.
+#[derive(Clone, Default)]
+pub struct CaptureSubscriber {
+    spans: Arc<Mutex<Vec<CapturedSpan>>>,
+}
+
+impl CaptureSubscriber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The spans which were opened so far.
+    pub fn spans(&self) -> Vec<CapturedSpan> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl Subscriber for CaptureSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = BTreeMap::new();
+        span.record(&mut FieldVisitor(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(CapturedSpan { name: span.metadata().name(), fields });
+        //a span's id is its position counting from 1 since ids can't be zero
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let index = usize::try_from(span.into_u64() - 1).unwrap();
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut FieldVisitor(&mut spans[index].fields));
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
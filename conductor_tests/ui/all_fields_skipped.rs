@@ -0,0 +1,12 @@
+use conductor::derive::Producer;
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize, Producer)]
+struct Identity {
+    #[producer_skip_field]
+    uuid: String,
+    #[producer_skip_field]
+    name: String,
+}
+
+fn main() {}
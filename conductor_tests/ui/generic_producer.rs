@@ -0,0 +1,34 @@
+use conductor::derive::Producer;
+use conductor::producer::Base;
+use conductor::schema::{ConductorSchema, DataTypes, ToConductorDataType};
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize, Producer)]
+struct Reading<T: ToConductorDataType> {
+    value: T,
+    ts_offset: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Producer)]
+struct Tagged<T, U>
+where
+    T: ToConductorDataType,
+    U: ToConductorDataType,
+{
+    value: T,
+    tag: U,
+    #[producer_skip_field]
+    uuid: String,
+}
+
+fn assert_producer<P: conductor::producer::Producer>() {}
+
+fn main() {
+    assert_eq!(Reading::<f64>::generate_schema()["value"], DataTypes::Double);
+    assert_eq!(Reading::<bool>::generate_schema()["value"], DataTypes::Bool);
+    assert_eq!(Tagged::<i32, String>::generate_schema()["tag"], DataTypes::String);
+    assert_producer::<Reading<u8>>();
+    assert_producer::<Tagged<i32, String>>();
+    let reading = Reading { value: 1.5, ts_offset: 0 };
+    assert_eq!(reading.to_data_map().unwrap()["value"], 1.5);
+}
@@ -0,0 +1,10 @@
+use conductor::derive::Producer;
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize, Producer)]
+struct Uptime {
+    host: String,
+    uptime: std::time::Duration,
+}
+
+fn main() {}